@@ -0,0 +1,131 @@
+//! Splits lines of LC3 assembly into tokens
+
+use super::AsmError;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(super) enum TokenKind {
+    /// Opcodes, directives, registers and labels
+    Ident(String),
+    Number(i32),
+    Str(String),
+    Comma,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(super) struct Token {
+    pub kind: TokenKind,
+    /// The 1-based column where the token starts
+    pub column: usize,
+}
+
+/// Splits a line of assembly into tokens. Everything after a `;` that is not inside a
+/// string is a comment, and is ignored
+pub(super) fn tokenize_line(line: &str, line_number: usize) -> Result<Vec<Token>, AsmError> {
+    let chars = line.chars().collect::<Vec<char>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let column = i + 1;
+        match chars[i] {
+            ';' => break,
+            ',' => {
+                tokens.push(Token {
+                    kind: TokenKind::Comma,
+                    column,
+                });
+                i += 1;
+            }
+            '"' => {
+                let (string, len) = lex_string(&chars[i..], line_number, column)?;
+                tokens.push(Token {
+                    kind: TokenKind::Str(string),
+                    column,
+                });
+                i += len;
+            }
+            ch if ch.is_whitespace() => i += 1,
+            _ => {
+                let len = chars[i..]
+                    .iter()
+                    .take_while(|ch| !ch.is_whitespace() && !matches!(ch, ',' | ';' | '"'))
+                    .count();
+                let word = chars[i..i + len].iter().collect::<String>();
+                let kind = classify_word(word, line_number, column)?;
+                tokens.push(Token { kind, column });
+                i += len;
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Lexes a string literal starting at the opening quote in `chars`. Returns the
+/// contents of the string, and the number of characters used including the quotes
+fn lex_string(chars: &[char], line: usize, column: usize) -> Result<(String, usize), AsmError> {
+    let mut string = String::new();
+    let mut i = 1;
+    while i < chars.len() {
+        match chars[i] {
+            '"' => return Ok((string, i + 1)),
+            '\\' => {
+                let escaped = match chars.get(i + 1) {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('0') => '\0',
+                    Some('"') => '"',
+                    Some('\\') => '\\',
+                    Some(other) => {
+                        return Err(AsmError::new(
+                            line,
+                            column + i,
+                            format!("unknown escape sequence \\{other}"),
+                        ))
+                    }
+                    None => break,
+                };
+                string.push(escaped);
+                i += 2;
+            }
+            ch => {
+                string.push(ch);
+                i += 1;
+            }
+        }
+    }
+    Err(AsmError::new(line, column, "unterminated string"))
+}
+
+/// Determines if a word is a number or an identifier.
+///
+/// Numbers are decimal when prefixed with `#` or when they start with a digit or `-`,
+/// and hexadecimal when prefixed with `x` or `0x`
+fn classify_word(word: String, line: usize, column: usize) -> Result<TokenKind, AsmError> {
+    let invalid_number = || AsmError::new(line, column, format!("invalid number {word}"));
+
+    let first = word.chars().next().unwrap_or_default();
+    let hex_digits = word
+        .strip_prefix("0x")
+        .or_else(|| word.strip_prefix("0X"))
+        .or_else(|| word.strip_prefix(['x', 'X']));
+    if let Some(digits) = hex_digits {
+        let is_hex = !digits.is_empty() && digits.chars().all(|ch| ch.is_ascii_hexdigit());
+        if is_hex {
+            let value = i32::from_str_radix(digits, 16).map_err(|_| invalid_number())?;
+            return Ok(TokenKind::Number(value));
+        }
+        if first.is_ascii_digit() {
+            return Err(invalid_number());
+        }
+    }
+
+    if let Some(digits) = word.strip_prefix('#') {
+        let value = digits.parse::<i32>().map_err(|_| invalid_number())?;
+        Ok(TokenKind::Number(value))
+    } else if first.is_ascii_digit() || first == '-' {
+        let value = word.parse::<i32>().map_err(|_| invalid_number())?;
+        Ok(TokenKind::Number(value))
+    } else {
+        Ok(TokenKind::Ident(word))
+    }
+}
@@ -0,0 +1,756 @@
+//! An assembler for LC3 assembly, which produces programs in the format expected by
+//! `Lc3Vm::load_program`.
+//!
+//! The assembler follows the syntax used by `lc3as`. Each line may contain a label,
+//! an instruction or directive and its operands, and a comment starting with `;`.
+//! Opcodes, directives and registers are case insensitive, while labels are case
+//! sensitive. Numbers are decimal when prefixed with `#` or written without a prefix,
+//! and hexadecimal when prefixed with `x`
+
+mod lexer;
+#[cfg(test)]
+mod tests;
+
+use std::{collections::BTreeMap, fmt};
+
+use crate::{symbols::SymbolTable, vm::ops::OpCode, TrapVector};
+use lexer::{tokenize_line, Token, TokenKind};
+
+/// An error in the assembly source, at the given 1-based line and column
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct AsmError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl AsmError {
+    fn new(line: usize, column: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// An assembled program
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Assembly {
+    /// The address where the program is loaded
+    pub origin: u16,
+    /// The assembled program, which is loaded starting at `origin`
+    pub words: Vec<u16>,
+    /// The labels defined in the program
+    pub symbols: SymbolTable,
+    /// The 1-based source line of each instruction and directive that produced words,
+    /// by the address of its first word
+    pub lines: BTreeMap<u16, usize>,
+}
+
+impl Assembly {
+    /// Returns the program in the `.obj` format, which contains the origin followed by
+    /// the words of the program, all in big endian
+    pub fn to_obj_bytes(&self) -> Vec<u8> {
+        [self.origin]
+            .iter()
+            .chain(&self.words)
+            .flat_map(|word| word.to_be_bytes())
+            .collect()
+    }
+}
+
+/// Assembles LC3 assembly source code. If the source contains errors, all of the
+/// errors that were found are returned
+pub fn assemble(source: &str) -> Result<Assembly, Vec<AsmError>> {
+    let mut statements = Vec::new();
+    let mut errors = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        match parse_line(line, index + 1) {
+            Ok(Some(statement)) => {
+                let is_end = matches!(
+                    statement.operation,
+                    Some(Operation {
+                        mnemonic: Mnemonic::End,
+                        ..
+                    })
+                );
+                statements.push(statement);
+                // Anything after .END is not assembled
+                if is_end {
+                    break;
+                }
+            }
+            Ok(None) => (),
+            Err(e) => errors.push(e),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let layout = layout(&statements)?;
+    let mut words = Vec::new();
+    let mut lines = BTreeMap::new();
+    for (statement, address) in statements.iter().zip(&layout.addresses) {
+        let Some(operation) = &statement.operation else {
+            continue;
+        };
+        match encode(operation, statement.line, *address, &layout.symbols) {
+            Ok(encoded) => {
+                if !encoded.is_empty() {
+                    lines.insert(*address, statement.line);
+                }
+                words.extend(encoded)
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    Ok(Assembly {
+        origin: layout.origin,
+        words,
+        symbols: layout.symbols,
+        lines,
+    })
+}
+
+/// The instructions and directives understood by the assembler
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Mnemonic {
+    Add,
+    And,
+    /// `BR` with the `nzp` condition bits to test
+    Br(u16),
+    Jmp,
+    Ret,
+    Jsr,
+    Jsrr,
+    Ld,
+    Ldi,
+    Ldr,
+    Lea,
+    Not,
+    Rti,
+    St,
+    Sti,
+    Str,
+    Trap,
+    /// A `TRAP` instruction written using the name of its trap routine, like `HALT`
+    TrapAlias(TrapVector),
+    Orig,
+    End,
+    Fill,
+    Blkw,
+    Stringz,
+}
+
+impl Mnemonic {
+    fn parse(word: &str) -> Option<Self> {
+        let upper = word.to_ascii_uppercase();
+        let mnemonic = match upper.as_str() {
+            "ADD" => Self::Add,
+            "AND" => Self::And,
+            "JMP" => Self::Jmp,
+            "RET" => Self::Ret,
+            "JSR" => Self::Jsr,
+            "JSRR" => Self::Jsrr,
+            "LD" => Self::Ld,
+            "LDI" => Self::Ldi,
+            "LDR" => Self::Ldr,
+            "LEA" => Self::Lea,
+            "NOT" => Self::Not,
+            "RTI" => Self::Rti,
+            "ST" => Self::St,
+            "STI" => Self::Sti,
+            "STR" => Self::Str,
+            "TRAP" => Self::Trap,
+            "GETC" => Self::TrapAlias(TrapVector::Getc),
+            "OUT" => Self::TrapAlias(TrapVector::Out),
+            "PUTS" => Self::TrapAlias(TrapVector::Puts),
+            "IN" => Self::TrapAlias(TrapVector::In),
+            "PUTSP" => Self::TrapAlias(TrapVector::Putsp),
+            "HALT" => Self::TrapAlias(TrapVector::Halt),
+            ".ORIG" => Self::Orig,
+            ".END" => Self::End,
+            ".FILL" => Self::Fill,
+            ".BLKW" => Self::Blkw,
+            ".STRINGZ" => Self::Stringz,
+            _ => return Self::parse_br(&upper),
+        };
+        Some(mnemonic)
+    }
+
+    /// Parses the `BR` instruction, which may be followed by any of the `n`, `z` and
+    /// `p` condition codes in that order. `BR` without condition codes always branches
+    fn parse_br(upper: &str) -> Option<Self> {
+        let conditions = upper.strip_prefix("BR")?;
+        if conditions.is_empty() {
+            return Some(Self::Br(0b111));
+        }
+        let mut bits = 0;
+        let mut remaining = conditions;
+        for (flag, bit) in [('N', 0b100), ('Z', 0b010), ('P', 0b001)] {
+            if let Some(rest) = remaining.strip_prefix(flag) {
+                bits |= bit;
+                remaining = rest;
+            }
+        }
+        remaining.is_empty().then_some(Self::Br(bits))
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum OperandKind {
+    Register(u16),
+    Number(i32),
+    Label(String),
+    Str(String),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Operand {
+    kind: OperandKind,
+    column: usize,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Operation {
+    mnemonic: Mnemonic,
+    column: usize,
+    operands: Vec<Operand>,
+}
+
+/// A line of assembly which contains a label, an operation, or both
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Statement {
+    line: usize,
+    label: Option<(String, usize)>,
+    operation: Option<Operation>,
+}
+
+/// Parses a line of assembly. Returns `None` for lines which only contain whitespace
+/// and comments
+fn parse_line(line: &str, line_number: usize) -> Result<Option<Statement>, AsmError> {
+    let mut tokens = tokenize_line(line, line_number)?.into_iter().peekable();
+
+    let mut label = None;
+    if let Some(Token {
+        kind: TokenKind::Ident(word),
+        column,
+    }) = tokens.peek()
+    {
+        if Mnemonic::parse(word).is_none() {
+            let name = word.strip_suffix(':').unwrap_or(word);
+            if !is_valid_label(name) {
+                return Err(AsmError::new(
+                    line_number,
+                    *column,
+                    format!("invalid label {word}"),
+                ));
+            }
+            label = Some((name.to_string(), *column));
+            tokens.next();
+        }
+    }
+
+    let operation = match tokens.next() {
+        None => None,
+        Some(Token {
+            kind: TokenKind::Ident(word),
+            column,
+        }) => {
+            let mnemonic = Mnemonic::parse(&word).ok_or_else(|| {
+                AsmError::new(line_number, column, format!("unknown instruction {word}"))
+            })?;
+            let operands = parse_operands(tokens, line_number)?;
+            Some(Operation {
+                mnemonic,
+                column,
+                operands,
+            })
+        }
+        Some(token) => {
+            return Err(AsmError::new(
+                line_number,
+                token.column,
+                "expected an instruction or directive",
+            ))
+        }
+    };
+
+    if label.is_none() && operation.is_none() {
+        return Ok(None);
+    }
+    Ok(Some(Statement {
+        line: line_number,
+        label,
+        operation,
+    }))
+}
+
+/// Parses the comma separated operands of an instruction or directive
+fn parse_operands(
+    tokens: impl Iterator<Item = Token>,
+    line: usize,
+) -> Result<Vec<Operand>, AsmError> {
+    let mut operands = Vec::new();
+    let mut expect_operand = true;
+    let mut last_comma = None;
+    for token in tokens {
+        if token.kind == TokenKind::Comma {
+            if expect_operand {
+                return Err(AsmError::new(line, token.column, "expected an operand"));
+            }
+            expect_operand = true;
+            last_comma = Some(token.column);
+            continue;
+        }
+        if !expect_operand {
+            return Err(AsmError::new(line, token.column, "expected a comma"));
+        }
+        let kind = match token.kind {
+            TokenKind::Ident(word) => match parse_register(&word) {
+                Some(register) => OperandKind::Register(register),
+                None => OperandKind::Label(word),
+            },
+            TokenKind::Number(value) => OperandKind::Number(value),
+            TokenKind::Str(string) => OperandKind::Str(string),
+            TokenKind::Comma => unreachable!(),
+        };
+        operands.push(Operand {
+            kind,
+            column: token.column,
+        });
+        expect_operand = false;
+    }
+    if let (true, Some(column)) = (expect_operand, last_comma) {
+        return Err(AsmError::new(
+            line,
+            column,
+            "expected an operand after the comma",
+        ));
+    }
+    Ok(operands)
+}
+
+fn parse_register(word: &str) -> Option<u16> {
+    let mut chars = word.chars();
+    let (Some('r' | 'R'), Some(digit), None) = (chars.next(), chars.next(), chars.next()) else {
+        return None;
+    };
+    digit.to_digit(8).map(|register| register as u16)
+}
+
+fn is_valid_label(name: &str) -> bool {
+    let mut chars = name.chars();
+    let starts_with_letter = chars
+        .next()
+        .is_some_and(|ch| ch.is_ascii_alphabetic() || ch == '_');
+    starts_with_letter && chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
+/// The result of the first pass of the assembler
+struct Layout {
+    origin: u16,
+    /// The address of each statement
+    addresses: Vec<u16>,
+    symbols: SymbolTable,
+}
+
+/// The first pass of the assembler, which determines the address of every statement
+/// and label
+fn layout(statements: &[Statement]) -> Result<Layout, Vec<AsmError>> {
+    let mut errors = Vec::new();
+    let mut origin = None;
+    let mut ended = false;
+    let mut symbols = SymbolTable::new();
+    let mut addresses = Vec::new();
+    // Use a u32 so that programs which run past the end of memory can be detected
+    let mut location: u32 = 0;
+
+    for statement in statements {
+        addresses.push(location as u16);
+        if ended {
+            // Everything after .END is ignored
+            continue;
+        }
+        if let Some(Operation {
+            mnemonic: Mnemonic::Orig,
+            column,
+            operands,
+        }) = &statement.operation
+        {
+            if origin.is_some() {
+                errors.push(AsmError::new(
+                    statement.line,
+                    *column,
+                    "only one .ORIG directive is allowed",
+                ));
+                continue;
+            }
+            match single_number(operands, statement.line, *column, 0, 0xffff) {
+                Ok(address) => {
+                    origin = Some(address as u16);
+                    location = address as u32;
+                    *addresses.last_mut().unwrap() = address as u16;
+                }
+                Err(e) => errors.push(e),
+            }
+            if let Some((_, column)) = &statement.label {
+                errors.push(AsmError::new(
+                    statement.line,
+                    *column,
+                    "labels cannot be placed on .ORIG",
+                ));
+            }
+            continue;
+        }
+        if origin.is_none() {
+            errors.push(AsmError::new(
+                statement.line,
+                1,
+                "the program must start with a .ORIG directive",
+            ));
+            // Report this only once
+            origin = Some(0);
+        }
+
+        if let Some((label, column)) = &statement.label {
+            if symbols.insert(label.clone(), location as u16).is_some() {
+                errors.push(AsmError::new(
+                    statement.line,
+                    *column,
+                    format!("label {label} is defined more than once"),
+                ));
+            }
+        }
+        if let Some(operation) = &statement.operation {
+            match size(operation, statement.line) {
+                Ok(size) => location += size,
+                Err(e) => errors.push(e),
+            }
+        }
+        if location > 0x10000 {
+            errors.push(AsmError::new(
+                statement.line,
+                1,
+                "the program does not fit into memory",
+            ));
+            break;
+        }
+        if let Some(Operation {
+            mnemonic: Mnemonic::End,
+            ..
+        }) = statement.operation
+        {
+            ended = true;
+        }
+    }
+
+    let Some(origin) = origin else {
+        errors.push(AsmError::new(1, 1, "the program has no .ORIG directive"));
+        return Err(errors);
+    };
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(Layout {
+        origin,
+        addresses,
+        symbols,
+    })
+}
+
+/// Returns the number of words an operation takes up in memory
+fn size(operation: &Operation, line: usize) -> Result<u32, AsmError> {
+    match operation.mnemonic {
+        Mnemonic::Orig | Mnemonic::End => Ok(0),
+        Mnemonic::Blkw => {
+            single_number(&operation.operands, line, operation.column, 1, 0xffff).map(|n| n as u32)
+        }
+        Mnemonic::Stringz => {
+            let string = single_string(&operation.operands, line, operation.column)?;
+            Ok(string.chars().count() as u32 + 1)
+        }
+        _ => Ok(1),
+    }
+}
+
+/// The second pass of the assembler, which encodes an operation at `address`
+fn encode(
+    operation: &Operation,
+    line: usize,
+    address: u16,
+    symbols: &SymbolTable,
+) -> Result<Vec<u16>, AsmError> {
+    let encoder = Encoder {
+        operation,
+        line,
+        address,
+        symbols,
+    };
+    encoder.encode()
+}
+
+struct Encoder<'a> {
+    operation: &'a Operation,
+    line: usize,
+    address: u16,
+    symbols: &'a SymbolTable,
+}
+
+impl Encoder<'_> {
+    fn encode(&self) -> Result<Vec<u16>, AsmError> {
+        let operands = &self.operation.operands;
+        let word = match self.operation.mnemonic {
+            Mnemonic::Orig | Mnemonic::End => {
+                self.expect_count(if self.operation.mnemonic == Mnemonic::Orig {
+                    1
+                } else {
+                    0
+                })?;
+                return Ok(Vec::new());
+            }
+            Mnemonic::Fill => {
+                self.expect_count(1)?;
+                let value = match &operands[0].kind {
+                    OperandKind::Label(_) => self.label_address(&operands[0])?,
+                    _ => self.number(&operands[0], -0x8000, 0xffff)? as u16,
+                };
+                return Ok(vec![value]);
+            }
+            Mnemonic::Blkw => {
+                let count = single_number(operands, self.line, self.operation.column, 1, 0xffff)?;
+                return Ok(vec![0; count as usize]);
+            }
+            Mnemonic::Stringz => {
+                let string = single_string(operands, self.line, self.operation.column)?;
+                let mut words = Vec::new();
+                for ch in string.chars() {
+                    if !ch.is_ascii() {
+                        return Err(self.error(
+                            operands[0].column,
+                            format!("{ch} is not an ASCII character"),
+                        ));
+                    }
+                    words.push(ch as u16);
+                }
+                words.push(0);
+                return Ok(words);
+            }
+            Mnemonic::Add | Mnemonic::And => {
+                self.expect_count(3)?;
+                let opcode = if self.operation.mnemonic == Mnemonic::Add {
+                    OpCode::Add
+                } else {
+                    OpCode::And
+                };
+                let dr = self.register(&operands[0])?;
+                let sr1 = self.register(&operands[1])?;
+                let last = match operands[2].kind {
+                    OperandKind::Register(sr2) => sr2,
+                    _ => 1 << 5 | self.signed(&operands[2], 5)?,
+                };
+                Self::instr(opcode, dr << 9 | sr1 << 6 | last)
+            }
+            Mnemonic::Br(conditions) => {
+                self.expect_count(1)?;
+                let offset = self.pc_offset(&operands[0], 9)?;
+                Self::instr(OpCode::Br, conditions << 9 | offset)
+            }
+            Mnemonic::Jmp | Mnemonic::Jsrr => {
+                self.expect_count(1)?;
+                let opcode = if self.operation.mnemonic == Mnemonic::Jmp {
+                    OpCode::Jmp
+                } else {
+                    OpCode::Jsr
+                };
+                let base = self.register(&operands[0])?;
+                Self::instr(opcode, base << 6)
+            }
+            Mnemonic::Ret => {
+                self.expect_count(0)?;
+                // RET is JMP R7
+                Self::instr(OpCode::Jmp, 7 << 6)
+            }
+            Mnemonic::Jsr => {
+                self.expect_count(1)?;
+                let offset = self.pc_offset(&operands[0], 11)?;
+                Self::instr(OpCode::Jsr, 1 << 11 | offset)
+            }
+            Mnemonic::Ld | Mnemonic::Ldi | Mnemonic::Lea | Mnemonic::St | Mnemonic::Sti => {
+                self.expect_count(2)?;
+                let opcode = match self.operation.mnemonic {
+                    Mnemonic::Ld => OpCode::Ld,
+                    Mnemonic::Ldi => OpCode::Ldi,
+                    Mnemonic::Lea => OpCode::Lea,
+                    Mnemonic::St => OpCode::St,
+                    _ => OpCode::Sti,
+                };
+                let register = self.register(&operands[0])?;
+                let offset = self.pc_offset(&operands[1], 9)?;
+                Self::instr(opcode, register << 9 | offset)
+            }
+            Mnemonic::Ldr | Mnemonic::Str => {
+                self.expect_count(3)?;
+                let opcode = if self.operation.mnemonic == Mnemonic::Ldr {
+                    OpCode::Ldr
+                } else {
+                    OpCode::Str
+                };
+                let register = self.register(&operands[0])?;
+                let base = self.register(&operands[1])?;
+                let offset = self.signed(&operands[2], 6)?;
+                Self::instr(opcode, register << 9 | base << 6 | offset)
+            }
+            Mnemonic::Not => {
+                self.expect_count(2)?;
+                let dr = self.register(&operands[0])?;
+                let sr = self.register(&operands[1])?;
+                Self::instr(OpCode::Not, dr << 9 | sr << 6 | 0x3f)
+            }
+            Mnemonic::Rti => {
+                self.expect_count(0)?;
+                Self::instr(OpCode::Rti, 0)
+            }
+            Mnemonic::Trap => {
+                self.expect_count(1)?;
+                let trap_vec = self.number(&operands[0], 0, 0xff)?;
+                Self::instr(OpCode::Trap, trap_vec as u16)
+            }
+            Mnemonic::TrapAlias(trap_vec) => {
+                self.expect_count(0)?;
+                Self::instr(OpCode::Trap, trap_vec as u16)
+            }
+        };
+        Ok(vec![word])
+    }
+
+    fn instr(opcode: OpCode, operands: u16) -> u16 {
+        (opcode as u16) << 12 | operands
+    }
+
+    fn error(&self, column: usize, message: impl Into<String>) -> AsmError {
+        AsmError::new(self.line, column, message)
+    }
+
+    fn expect_count(&self, count: usize) -> Result<(), AsmError> {
+        let operands = &self.operation.operands;
+        if operands.len() == count {
+            return Ok(());
+        }
+        let column = operands
+            .get(count)
+            .map_or(self.operation.column, |operand| operand.column);
+        Err(self.error(
+            column,
+            format!("expected {count} operand(s), found {}", operands.len()),
+        ))
+    }
+
+    fn register(&self, operand: &Operand) -> Result<u16, AsmError> {
+        match operand.kind {
+            OperandKind::Register(register) => Ok(register),
+            _ => Err(self.error(operand.column, "expected a register")),
+        }
+    }
+
+    fn number(&self, operand: &Operand, min: i32, max: i32) -> Result<i32, AsmError> {
+        let OperandKind::Number(value) = operand.kind else {
+            return Err(self.error(operand.column, "expected a number"));
+        };
+        if value < min || value > max {
+            return Err(self.error(
+                operand.column,
+                format!("{value} is out of range, expected a value from {min} to {max}"),
+            ));
+        }
+        Ok(value)
+    }
+
+    /// Parses a signed immediate value that fits into `bits` bits
+    fn signed(&self, operand: &Operand, bits: u32) -> Result<u16, AsmError> {
+        let max = (1 << (bits - 1)) - 1;
+        let value = self.number(operand, -max - 1, max)?;
+        Ok(value as u16 & ((1 << bits) - 1))
+    }
+
+    fn label_address(&self, operand: &Operand) -> Result<u16, AsmError> {
+        let OperandKind::Label(label) = &operand.kind else {
+            return Err(self.error(operand.column, "expected a label"));
+        };
+        self.symbols
+            .address(label)
+            .ok_or_else(|| self.error(operand.column, format!("undefined label {label}")))
+    }
+
+    /// Computes the PC relative offset to a label, which must fit into `bits` bits.
+    /// Numbers are used as the offset directly
+    fn pc_offset(&self, operand: &Operand, bits: u32) -> Result<u16, AsmError> {
+        if let OperandKind::Number(_) = operand.kind {
+            return self.signed(operand, bits);
+        }
+        let target = self.label_address(operand)?;
+        // The PC has already been incremented when the offset is added to it
+        let offset = target as i32 - (self.address as i32 + 1);
+        let max = (1 << (bits - 1)) - 1;
+        if offset < -max - 1 || offset > max {
+            return Err(self.error(
+                operand.column,
+                format!("label is too far away, the offset {offset} does not fit into {bits} bits"),
+            ));
+        }
+        Ok(offset as u16 & ((1 << bits) - 1))
+    }
+}
+
+/// Parses the operands of a directive that takes a single number from `min` to `max`
+fn single_number(
+    operands: &[Operand],
+    line: usize,
+    column: usize,
+    min: i32,
+    max: i32,
+) -> Result<i32, AsmError> {
+    match operands {
+        [Operand {
+            kind: OperandKind::Number(value),
+            column,
+        }] => {
+            if *value < min || *value > max {
+                return Err(AsmError::new(
+                    line,
+                    *column,
+                    format!("{value} is out of range, expected a value from {min} to {max}"),
+                ));
+            }
+            Ok(*value)
+        }
+        [operand] => Err(AsmError::new(line, operand.column, "expected a number")),
+        _ => Err(AsmError::new(line, column, "expected a single number")),
+    }
+}
+
+/// Parses the operands of a directive that takes a single string
+fn single_string(operands: &[Operand], line: usize, column: usize) -> Result<&str, AsmError> {
+    match operands {
+        [Operand {
+            kind: OperandKind::Str(string),
+            ..
+        }] => Ok(string),
+        [operand] => Err(AsmError::new(line, operand.column, "expected a string")),
+        _ => Err(AsmError::new(line, column, "expected a single string")),
+    }
+}
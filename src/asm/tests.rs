@@ -0,0 +1,219 @@
+use super::*;
+use crate::vm::{
+    console::{IoConsole, OutputBuffer},
+    Lc3Vm,
+};
+
+const HELLO_PROGRAM: &str = r#"
+; Prints a greeting a few times
+        .ORIG x3000
+        AND R1, R1, #0
+        ADD R1, R1, #3      ; Loop counter
+LOOP    LEA R0, GREETING
+        PUTS
+        ADD R1, R1, #-1
+        BRp LOOP
+        HALT
+GREETING .STRINGZ "Hi\n"
+        .END
+"#;
+
+#[test]
+#[allow(clippy::unusual_byte_groupings)]
+fn test_assemble_program() {
+    let assembly = assemble(HELLO_PROGRAM).unwrap();
+    assert_eq!(assembly.origin, 0x3000);
+    assert_eq!(
+        assembly.words,
+        vec![
+            0b0101_001_001_1_00000,
+            0b0001_001_001_1_00011,
+            0b1110_000_000000100,
+            0xf022,
+            0b0001_001_001_1_11111,
+            0b0000_001_111111100,
+            0xf025,
+            'H' as u16,
+            'i' as u16,
+            '\n' as u16,
+            0,
+        ]
+    );
+    assert_eq!(assembly.symbols.address("LOOP"), Some(0x3002));
+    assert_eq!(assembly.symbols.address("GREETING"), Some(0x3007));
+}
+
+#[test]
+fn test_run_assembled_program() {
+    let assembly = assemble(HELLO_PROGRAM).unwrap();
+    let output = OutputBuffer::new();
+    let mut vm = Lc3Vm::with_console(IoConsole::new("".as_bytes(), output.clone()));
+    for (address, word) in (assembly.origin..).zip(&assembly.words) {
+        vm.memory_mut().write(address, *word);
+    }
+    vm.run().unwrap();
+    assert!(output.to_string_lossy().starts_with("Hi\nHi\nHi\n"));
+}
+
+#[test]
+#[allow(clippy::unusual_byte_groupings)]
+fn test_assemble_instructions() {
+    let source = "
+        .ORIG x3000
+START   add r2, r3, r4
+        AND R0, R1, #-16
+        BR START
+        BRnz START
+        JMP R5
+        RET
+        JSR START
+        JSRR R3
+        LD R1, DATA
+        LDI R1, DATA
+        LDR R1, R6, #-1
+        LEA R1, DATA
+        NOT R4, R2
+        RTI
+        ST R1, DATA
+        STI R1, DATA
+        STR R1, R6, #31
+        TRAP x25
+        GETC
+        OUT
+        PUTS
+        IN
+        PUTSP
+        HALT
+DATA    .FILL xBEEF
+        .FILL START
+        .FILL #-1
+        .BLKW 2
+        .END
+    ";
+    let assembly = assemble(source).unwrap();
+    assert_eq!(
+        assembly.words,
+        vec![
+            0b0001_010_011_0_00_100,
+            0b0101_000_001_1_10000,
+            0b0000_111_111111101,
+            0b0000_110_111111100,
+            0b1100_000_101_000000,
+            0b1100_000_111_000000,
+            0b0100_1_11111111001,
+            0b0100_0_00_011_000000,
+            0b0010_001_000001111,
+            0b1010_001_000001110,
+            0b0110_001_110_111111,
+            0b1110_001_000001100,
+            0b1001_100_010_111111,
+            0b1000_000000000000,
+            0b0011_001_000001001,
+            0b1011_001_000001000,
+            0b0111_001_110_011111,
+            0xf025,
+            0xf020,
+            0xf021,
+            0xf022,
+            0xf023,
+            0xf024,
+            0xf025,
+            0xbeef,
+            0x3000,
+            0xffff,
+            0,
+            0,
+        ]
+    );
+}
+
+#[test]
+fn test_to_obj_bytes() {
+    let assembly = assemble(".ORIG x3000\n.FILL x1234\n.END").unwrap();
+    assert_eq!(assembly.to_obj_bytes(), vec![0x30, 0x00, 0x12, 0x34]);
+}
+
+#[test]
+fn test_number_formats() {
+    let source = ".ORIG 0x4000\n.FILL #10\n.FILL 10\n.FILL x1f\n.FILL X1F\n.FILL -3\n.END";
+    let assembly = assemble(source).unwrap();
+    assert_eq!(assembly.origin, 0x4000);
+    assert_eq!(assembly.words, vec![10, 10, 0x1f, 0x1f, 0xfffd]);
+}
+
+#[test]
+fn test_ignores_after_end() {
+    let assembly = assemble(".ORIG x3000\nHALT\n.END\nthis is not assembled").unwrap();
+    assert_eq!(assembly.words, vec![0xf025]);
+}
+
+#[test]
+fn test_undefined_label() {
+    let errors = assemble(".ORIG x3000\n  LD R0, MISSING\n.END").unwrap_err();
+    assert_eq!(
+        errors,
+        vec![AsmError::new(2, 10, "undefined label MISSING")]
+    );
+}
+
+#[test]
+fn test_immediate_out_of_range() {
+    let errors = assemble(".ORIG x3000\nADD R0, R0, #16\n.END").unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!((errors[0].line, errors[0].column), (2, 13));
+}
+
+#[test]
+fn test_multiple_errors() {
+    let source = ".ORIG x3000\nA ADD R0, R0\nA HALT\nFOO R1\n.END";
+    let errors = assemble(source).unwrap_err();
+    // Parsing errors are reported first
+    assert_eq!(errors, vec![AsmError::new(4, 5, "unknown instruction R1")]);
+
+    let source = ".ORIG x3000\nA ADD R0, R0\nA HALT\n.END";
+    let errors = assemble(source).unwrap_err();
+    assert_eq!(
+        errors,
+        vec![AsmError::new(3, 1, "label A is defined more than once")]
+    );
+
+    let source = ".ORIG x3000\nA ADD R0, R0\n.END";
+    let errors = assemble(source).unwrap_err();
+    assert_eq!(
+        errors,
+        vec![AsmError::new(2, 3, "expected 3 operand(s), found 2")]
+    );
+}
+
+#[test]
+fn test_missing_orig() {
+    let errors = assemble("ADD R0, R0, #1").unwrap_err();
+    assert_eq!(errors[0].line, 1);
+    assert!(assemble("; only a comment").is_err());
+}
+
+#[test]
+fn test_unterminated_string() {
+    let errors = assemble(".ORIG x3000\n.STRINGZ \"abc\n.END").unwrap_err();
+    assert_eq!(errors, vec![AsmError::new(2, 10, "unterminated string")]);
+}
+
+#[test]
+fn test_source_lines() {
+    let assembly = assemble(HELLO_PROGRAM).unwrap();
+    // Lines with only labels, comments or the .ORIG and .END directives are skipped
+    let lines = assembly.lines.into_iter().collect::<Vec<(u16, usize)>>();
+    assert_eq!(
+        lines,
+        vec![
+            (0x3000, 4),
+            (0x3001, 5),
+            (0x3002, 6),
+            (0x3003, 7),
+            (0x3004, 8),
+            (0x3005, 9),
+            (0x3006, 10),
+            (0x3007, 11),
+        ]
+    );
+}
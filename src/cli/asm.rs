@@ -0,0 +1,59 @@
+//! The `lc3 asm` subcommand, which assembles a source file into an object file
+
+use std::{
+    fs,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use rust_vm::asm::assemble;
+
+pub const USAGE: &str = "asm SOURCE_PATH [-o OUTPUT_PATH]
+        Assembles SOURCE_PATH into an object file, and writes its symbol table next to
+        it with the .sym extension. OUTPUT_PATH defaults to SOURCE_PATH with the .obj
+        extension";
+
+/// Runs the subcommand with the arguments that follow `asm`. Returns the exit code
+pub fn run(args: &[String]) -> Result<i32, String> {
+    let mut source_path = None;
+    let mut output_path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => {
+                let path = args.next().ok_or(format!("Missing value for {arg}"))?;
+                output_path = Some(PathBuf::from(path));
+            }
+            _ if source_path.is_none() && !arg.starts_with('-') => {
+                source_path = Some(PathBuf::from(arg))
+            }
+            _ => return Err(format!("Unexpected argument: {arg}")),
+        }
+    }
+    let source_path = source_path.ok_or("No assembly source file given")?;
+    let output_path = output_path.unwrap_or_else(|| source_path.with_extension("obj"));
+
+    let source = fs::read_to_string(&source_path)
+        .map_err(|e| format!("Failed to read {}: {e}", source_path.display()))?;
+    let assembly = match assemble(&source) {
+        Ok(assembly) => assembly,
+        Err(errors) => {
+            for error in errors {
+                eprintln!("{}:{error}", source_path.display());
+            }
+            return Ok(1);
+        }
+    };
+
+    fs::write(&output_path, assembly.to_obj_bytes())
+        .map_err(|e| format!("Failed to write {}: {e}", output_path.display()))?;
+    let sym_path = output_path.with_extension("sym");
+    fs::File::create(&sym_path)
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            assembly.symbols.write_sym(&mut writer)?;
+            writer.flush()
+        })
+        .map_err(|e| format!("Failed to write {}: {e}", sym_path.display()))?;
+    Ok(0)
+}
@@ -0,0 +1,62 @@
+//! The `lc3 convert` subcommand, which converts programs between the `.obj`, `.hex`
+//! and `.bin` formats
+
+use std::{fs, path::PathBuf};
+
+use rust_vm::{ProgramFormat, ProgramImage};
+
+pub const USAGE: &str = "convert INPUT_PATH -o OUTPUT_PATH [--from FORMAT] [--to FORMAT]
+        Converts the program at INPUT_PATH into another format, and writes it to
+        OUTPUT_PATH. FORMAT is obj, hex or bin, and defaults to the format given by the
+        file's extension. The format of the input is detected if it is not known";
+
+/// Runs the subcommand with the arguments that follow `convert`. Returns the exit code
+pub fn run(args: &[String]) -> Result<i32, String> {
+    let mut input_path = None;
+    let mut output_path = None;
+    let mut from = None;
+    let mut to = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => {
+                let path = args.next().ok_or(format!("Missing value for {arg}"))?;
+                output_path = Some(PathBuf::from(path));
+            }
+            "--from" => from = Some(format_value(arg, args.next())?),
+            "--to" => to = Some(format_value(arg, args.next())?),
+            _ if input_path.is_none() && !arg.starts_with('-') => {
+                input_path = Some(PathBuf::from(arg))
+            }
+            _ => return Err(format!("Unexpected argument: {arg}")),
+        }
+    }
+    let input_path = input_path.ok_or("No input file given")?;
+    let output_path = output_path.ok_or("No output file given")?;
+    let to = to
+        .or_else(|| ProgramFormat::from_path(&output_path))
+        .ok_or(format!(
+            "Unknown format for {}, use --to to choose one",
+            output_path.display()
+        ))?;
+
+    let image = match from {
+        Some(from) => {
+            let bytes = fs::read(&input_path)
+                .map_err(|e| format!("Failed to read {}: {e}", input_path.display()))?;
+            ProgramImage::parse(&bytes, from)
+        }
+        None => ProgramImage::from_path(&input_path),
+    }
+    .map_err(|e| format!("Failed to load {}: {e}", input_path.display()))?;
+
+    fs::write(&output_path, image.to_bytes(to))
+        .map_err(|e| format!("Failed to write {}: {e}", output_path.display()))?;
+    Ok(0)
+}
+
+/// Parses the format given for the command line option `option`
+fn format_value(option: &str, value: Option<&String>) -> Result<ProgramFormat, String> {
+    let value = value.ok_or(format!("Missing value for {option}"))?;
+    ProgramFormat::from_extension(value).ok_or(format!("Invalid value for {option}: {value}"))
+}
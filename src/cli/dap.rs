@@ -0,0 +1,19 @@
+//! The `lc3 dap` subcommand, which lets editors debug LC3 programs
+
+use std::io;
+
+use rust_vm::debugger::dap;
+
+pub const USAGE: &str = "dap
+        Runs a Debug Adapter Protocol server on standard input and output, for
+        debugging LC3 programs in editors";
+
+/// Runs the subcommand with the arguments that follow `dap`. Returns the exit code
+pub fn run(args: &[String]) -> Result<i32, String> {
+    if let Some(arg) = args.first() {
+        return Err(format!("Unexpected argument: {arg}"));
+    }
+    dap::serve(io::stdin(), io::stdout())
+        .map_err(|e| format!("Debug adapter connection failed: {e}"))?;
+    Ok(0)
+}
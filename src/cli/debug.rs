@@ -0,0 +1,473 @@
+//! The `lc3 debug` subcommand, an interactive debugger for LC3 programs
+
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    ops::RangeInclusive,
+    path::PathBuf,
+    rc::Rc,
+    sync::atomic::Ordering,
+};
+
+use rust_vm::{
+    debugger::{parse_number, Debugger, StopReason, WatchKind, Watchpoint, DEFAULT_HISTORY_LIMIT},
+    disasm::disassemble,
+    ConditionFlag, Console, InputThread, Lc3Vm, RegisterName, VmError,
+};
+
+use super::{interrupt, symbols};
+
+pub const USAGE: &str = "debug PROGRAM_PATH... [--sym SYM_PATH]... [--entry ADDRESS]
+            [--ignore-privilege] [--boot-os] [--history COUNT]
+        Runs the programs at PROGRAM_PATH in an interactive debugger. Labels are read
+        from each SYM_PATH, or from each PROGRAM_PATH with the .sym extension if it
+        exists. The last COUNT executed instructions can be undone (100000 by
+        default)";
+
+const HELP: &str = "Commands:
+  b, break [LOCATION]       Add a breakpoint, or list the breakpoints
+  d, delete LOCATION        Remove a breakpoint
+  w, watch [LOCATION [COUNT] [KIND]]
+                            Stop when an instruction accesses the COUNT words starting at
+                            LOCATION, or list the watchpoints. KIND is write (the
+                            default), read or access
+  u, unwatch LOCATION       Remove the watchpoints starting at LOCATION
+  s, step [COUNT]           Execute COUNT instructions, stepping into subroutines
+  n, next                   Execute one instruction, stepping over subroutines and traps
+  f, finish                 Execute until the current subroutine returns
+  c, continue               Execute until a breakpoint is reached or the program halts
+  rs, reverse-step [COUNT]  Undo the last COUNT executed instructions
+  rc, reverse-continue      Execute backwards until a breakpoint or write watchpoint is
+                            reached
+  rw, reverse-write LOCATION
+                            Execute backwards until the instruction that last wrote to
+                            LOCATION is undone
+  r, regs                   Show the registers
+  x, mem LOCATION [COUNT]   Show COUNT words of memory starting at LOCATION
+  set TARGET VALUE          Set a register (R0-R7, PC, COND, PSR) or a memory location.
+                            Setting memory cannot be reversed, and forgets the executed
+                            instructions
+  l, list [LOCATION]        Disassemble the instructions around LOCATION or the PC
+  h, help                   Show this help
+  q, quit                   Exit the debugger
+LOCATION is a label or an address like x3000. Values are numbers like x1F, #-1 or 12.
+Pressing enter repeats the last command that executed instructions";
+
+/// The number of instructions shown before and after the listed location
+const LIST_CONTEXT: u16 = 5;
+
+/// Runs the subcommand with the arguments that follow `debug`. Returns the exit code
+pub fn run(args: &[String]) -> Result<i32, String> {
+    let mut program_paths = Vec::new();
+    let mut sym_paths = Vec::new();
+    let mut entry = None;
+    let mut ignore_privilege = false;
+    let mut boot_os = false;
+    let mut history_limit = DEFAULT_HISTORY_LIMIT;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--sym" => {
+                let path = args.next().ok_or(format!("Missing value for {arg}"))?;
+                sym_paths.push(PathBuf::from(path));
+            }
+            "--entry" => {
+                let address = args.next().ok_or(format!("Missing value for {arg}"))?;
+                let address =
+                    parse_number(address).ok_or(format!("Invalid value for {arg}: {address}"))?;
+                entry = Some(address);
+            }
+            "--history" => {
+                let count = args.next().ok_or(format!("Missing value for {arg}"))?;
+                history_limit = count
+                    .parse()
+                    .map_err(|_| format!("Invalid value for {arg}: {count}"))?;
+            }
+            "--ignore-privilege" => ignore_privilege = true,
+            "--boot-os" => boot_os = true,
+            _ if !arg.starts_with('-') => program_paths.push(PathBuf::from(arg)),
+            _ => return Err(format!("Unexpected argument: {arg}")),
+        }
+    }
+    if program_paths.is_empty() {
+        return Err("No LC3 program given".to_string());
+    }
+
+    // The debugger commands and the program's keyboard input both come from standard
+    // input, so they share the thread that reads it
+    let input = Rc::new(RefCell::new(InputThread::spawn(io::stdin())));
+    let mut vm = Lc3Vm::with_console(DebugConsole {
+        input: Rc::clone(&input),
+    });
+    if boot_os {
+        vm.load_os();
+    }
+    vm.load_programs(&program_paths)
+        .map_err(|e| format!("Failed to load LC3 program: {e}"))?;
+    if let Some(entry) = entry {
+        vm.registers_mut().set_program_counter(entry);
+    }
+    vm.set_ignore_privilege(ignore_privilege);
+    vm.set_symbols(symbols::read_symbols(&program_paths, &sym_paths)?);
+    if boot_os {
+        vm.boot_os();
+    }
+
+    let mut debugger = Debugger::new(vm);
+    debugger.set_history_limit(history_limit);
+    interrupt::forward_to(debugger.interrupt_flag());
+
+    let mut session = Session {
+        debugger,
+        input,
+        last_command: None,
+    };
+    println!("Type \"help\" for a list of commands");
+    session.print_current_instruction();
+    session.run();
+    Ok(0)
+}
+
+/// A console that shares standard input with the debugger's command line
+struct DebugConsole {
+    input: Rc<RefCell<InputThread>>,
+}
+
+impl Console for DebugConsole {
+    fn read_char(&mut self) -> io::Result<u8> {
+        self.input.borrow_mut().read_char()
+    }
+
+    fn poll_char(&mut self) -> io::Result<Option<u8>> {
+        Ok(self.input.borrow_mut().poll_char())
+    }
+
+    fn write_chars(&mut self, chars: &[u8]) -> io::Result<()> {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(chars)?;
+        stdout.flush()
+    }
+}
+
+struct Session {
+    debugger: Debugger,
+    input: Rc<RefCell<InputThread>>,
+    /// The last command that resumed execution, which is repeated by an empty line
+    last_command: Option<String>,
+}
+
+impl Session {
+    /// Reads and executes commands until the user quits or the input ends
+    fn run(&mut self) {
+        loop {
+            print!("(lc3) ");
+            // The prompt is only cosmetic, so a failed flush can be ignored
+            let _ = io::stdout().flush();
+            let Some(line) = self.read_line() else {
+                println!();
+                return;
+            };
+            let line = match (line.trim(), &self.last_command) {
+                ("", Some(last_command)) => last_command.clone(),
+                (line, _) => line.to_string(),
+            };
+            match self.execute(&line) {
+                Ok(true) => return,
+                Ok(false) => (),
+                Err(e) => println!("{e}"),
+            }
+        }
+    }
+
+    /// Reads a line of input. Returns `None` at the end of the input
+    fn read_line(&mut self) -> Option<String> {
+        let mut line = Vec::new();
+        let mut input = self.input.borrow_mut();
+        loop {
+            match input.read_char() {
+                Ok(b'\n') => break,
+                Ok(byte) => line.push(byte),
+                Err(_) if line.is_empty() => return None,
+                Err(_) => break,
+            }
+        }
+        Some(String::from_utf8_lossy(&line).into_owned())
+    }
+
+    /// Executes a command. Returns `true` if the debugger should exit
+    fn execute(&mut self, line: &str) -> Result<bool, String> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(false);
+        };
+        let args = words.collect::<Vec<&str>>();
+        match (command, &args[..]) {
+            ("b" | "break", []) => self.print_breakpoints(),
+            ("b" | "break", [location]) => {
+                let address = self.location(location)?;
+                if self.debugger.add_breakpoint(address) {
+                    println!("Breakpoint added at {}", self.describe(address));
+                } else {
+                    println!(
+                        "There already is a breakpoint at {}",
+                        self.describe(address)
+                    );
+                }
+            }
+            ("d" | "delete", [location]) => {
+                let address = self.location(location)?;
+                if self.debugger.remove_breakpoint(address) {
+                    println!("Breakpoint removed from {}", self.describe(address));
+                } else {
+                    println!("There is no breakpoint at {}", self.describe(address));
+                }
+            }
+            ("w" | "watch", []) => self.print_watchpoints(),
+            ("w" | "watch", [location, rest @ ..]) => self.watch(location, rest)?,
+            ("u" | "unwatch", [location]) => {
+                let address = self.location(location)?;
+                let watchpoints = self
+                    .debugger
+                    .watchpoints()
+                    .iter()
+                    .filter(|watchpoint| *watchpoint.range.start() == address)
+                    .cloned()
+                    .collect::<Vec<Watchpoint>>();
+                for watchpoint in &watchpoints {
+                    self.debugger.remove_watchpoint(watchpoint);
+                }
+                if watchpoints.is_empty() {
+                    println!("There is no watchpoint at {}", self.describe(address));
+                } else {
+                    println!("Watchpoints removed from {}", self.describe(address));
+                }
+            }
+            ("s" | "step", []) => self.resume(line, |debugger| debugger.step(1)),
+            ("s" | "step", [count]) => {
+                let count = count
+                    .parse::<usize>()
+                    .map_err(|_| format!("Invalid instruction count: {count}"))?;
+                self.resume(line, |debugger| debugger.step(count))
+            }
+            ("n" | "next", []) => self.resume(line, Debugger::step_over),
+            ("f" | "finish", []) => self.resume(line, Debugger::finish),
+            ("c" | "continue", []) => self.resume(line, Debugger::continue_execution),
+            ("rs" | "reverse-step", []) => self.resume(line, |debugger| Ok(debugger.step_back(1))),
+            ("rs" | "reverse-step", [count]) => {
+                let count = count
+                    .parse::<usize>()
+                    .map_err(|_| format!("Invalid instruction count: {count}"))?;
+                self.resume(line, |debugger| Ok(debugger.step_back(count)))
+            }
+            ("rc" | "reverse-continue", []) => {
+                self.resume(line, |debugger| Ok(debugger.reverse_continue()))
+            }
+            ("rw" | "reverse-write", [location]) => {
+                let address = self.location(location)?;
+                self.resume(line, |debugger| Ok(debugger.back_to_write(address)))
+            }
+            ("r" | "regs", []) => self.print_registers(),
+            ("x" | "mem", [location]) => self.print_memory(location, "1")?,
+            ("x" | "mem", [location, count]) => self.print_memory(location, count)?,
+            ("set", [target, value]) => self.set(target, value)?,
+            ("l" | "list", []) => {
+                let pc = self.debugger.vm().registers().program_counter();
+                self.print_listing(pc);
+            }
+            ("l" | "list", [location]) => {
+                let address = self.location(location)?;
+                self.print_listing(address);
+            }
+            ("h" | "help", []) => println!("{HELP}"),
+            ("q" | "quit", []) => return Ok(true),
+            _ => return Err(format!("Unknown command: {line}. Type \"help\" for help")),
+        }
+        Ok(false)
+    }
+
+    /// Resumes execution with `command`, and reports why execution stopped
+    fn resume<F>(&mut self, line: &str, command: F)
+    where
+        F: FnOnce(&mut Debugger) -> Result<StopReason, VmError>,
+    {
+        self.last_command = Some(line.to_string());
+        self.debugger
+            .interrupt_flag()
+            .store(false, Ordering::Relaxed);
+        match command(&mut self.debugger) {
+            Ok(StopReason::Done) => (),
+            Ok(StopReason::Breakpoint(address)) => {
+                println!("Breakpoint reached at {}", self.describe(address))
+            }
+            Ok(StopReason::Watchpoint(hit)) => {
+                println!("Watchpoint: {}", hit.describe(self.debugger.symbols()))
+            }
+            Ok(StopReason::Halted) => {
+                println!("The program has halted");
+                self.last_command = None;
+                return;
+            }
+            Ok(StopReason::Interrupted) => println!("Interrupted"),
+            Ok(StopReason::HistoryStart) => println!("Reached the oldest recorded instruction"),
+            Err(e) => {
+                println!("Error: {}", e.with_symbols(self.debugger.symbols()));
+                self.last_command = None;
+            }
+        }
+        self.print_current_instruction();
+    }
+
+    fn print_current_instruction(&self) {
+        let pc = self.debugger.vm().registers().program_counter();
+        self.print_disassembly(pc..=pc);
+    }
+
+    /// Prints the instructions around `address`
+    fn print_listing(&self, address: u16) {
+        let start = address.saturating_sub(LIST_CONTEXT);
+        let end = address.saturating_add(LIST_CONTEXT);
+        self.print_disassembly(start..=end);
+    }
+
+    /// Prints the disassembly of `range`, marking the PC with `>` and breakpoints
+    /// with `*`
+    fn print_disassembly(&self, range: RangeInclusive<u16>) {
+        let vm = self.debugger.vm();
+        let pc = vm.registers().program_counter();
+        let breakpoints = self.debugger.breakpoints().collect::<Vec<u16>>();
+        for line in disassemble(vm.memory(), range, Some(self.debugger.symbols())) {
+            let pc_marker = if line.address == pc { '>' } else { ' ' };
+            let breakpoint_marker = if breakpoints.contains(&line.address) {
+                '*'
+            } else {
+                ' '
+            };
+            println!("{pc_marker}{breakpoint_marker} {line}");
+        }
+    }
+
+    fn print_breakpoints(&self) {
+        let breakpoints = self.debugger.breakpoints().collect::<Vec<u16>>();
+        if breakpoints.is_empty() {
+            println!("There are no breakpoints");
+        }
+        for address in breakpoints {
+            println!("Breakpoint at {}", self.describe(address));
+        }
+    }
+
+    /// Adds a watchpoint with the arguments `[COUNT] [KIND]` that follow its location
+    fn watch(&mut self, location: &str, args: &[&str]) -> Result<(), String> {
+        let start = self.location(location)?;
+        let (count, kind) = match args {
+            [] => ("1", "write"),
+            [kind] if kind.parse::<u16>().is_err() => ("1", *kind),
+            [count] => (*count, "write"),
+            [count, kind] => (*count, *kind),
+            _ => return Err("Usage: watch LOCATION [COUNT] [KIND]".to_string()),
+        };
+        let count = count
+            .parse::<u16>()
+            .ok()
+            .filter(|count| *count > 0)
+            .ok_or(format!("Invalid word count: {count}"))?;
+        let kind = match kind {
+            "write" => WatchKind::Write,
+            "read" => WatchKind::Read,
+            "access" => WatchKind::Access,
+            _ => return Err(format!("Invalid watchpoint kind: {kind}")),
+        };
+        let end = start.saturating_add(count - 1);
+        let watchpoint = Watchpoint {
+            range: start..=end,
+            kind,
+        };
+        let description = self.describe_watchpoint(&watchpoint);
+        if self.debugger.add_watchpoint(watchpoint) {
+            println!("Watchpoint added: {description}");
+        } else {
+            println!("There already is a watchpoint: {description}");
+        }
+        Ok(())
+    }
+
+    fn print_watchpoints(&self) {
+        let watchpoints = self.debugger.watchpoints();
+        if watchpoints.is_empty() {
+            println!("There are no watchpoints");
+        }
+        for watchpoint in watchpoints {
+            println!("Watchpoint: {}", self.describe_watchpoint(watchpoint));
+        }
+    }
+
+    /// Formats a watchpoint like `write x4000 (TABLE) to x4009 (TABLE+9)`
+    fn describe_watchpoint(&self, watchpoint: &Watchpoint) -> String {
+        let kind = match watchpoint.kind {
+            WatchKind::Read => "read",
+            WatchKind::Write => "write",
+            WatchKind::Access => "access",
+        };
+        let (start, end) = (*watchpoint.range.start(), *watchpoint.range.end());
+        if start == end {
+            format!("{kind} {}", self.describe(start))
+        } else {
+            format!("{kind} {} to {}", self.describe(start), self.describe(end))
+        }
+    }
+
+    fn print_registers(&self) {
+        let registers = self.debugger.vm().registers();
+        for id in 0..8 {
+            let register = RegisterName::general(id);
+            let value = registers.get_reg_value(register);
+            println!("{register:<4} x{value:04X}  {}", value as i16);
+        }
+        println!("PC   x{:04X}", registers.program_counter());
+        let flag = match self.debugger.vm().get_cond_flag() {
+            ConditionFlag::Neg => 'n',
+            ConditionFlag::Zro => 'z',
+            ConditionFlag::Pos => 'p',
+        };
+        println!("COND {flag}");
+        println!("PSR  x{:04X}", registers.psr());
+    }
+
+    fn print_memory(&self, location: &str, count: &str) -> Result<(), String> {
+        let address = self.location(location)?;
+        let count = count
+            .parse::<u16>()
+            .map_err(|_| format!("Invalid word count: {count}"))?;
+        let vm = self.debugger.vm();
+        for offset in 0..count {
+            let address = address.wrapping_add(offset);
+            let value = vm.peek_memory(address);
+            println!("x{address:04X}  x{value:04X}  {}", value as i16);
+        }
+        Ok(())
+    }
+
+    /// Sets a register or a memory location to a value
+    fn set(&mut self, target: &str, value: &str) -> Result<(), String> {
+        let value = parse_number(value).ok_or(format!("Invalid value: {value}"))?;
+        if let Ok(register) = target.parse::<RegisterName>() {
+            let registers = self.debugger.vm_mut().registers_mut();
+            registers.set_reg_value(register, value);
+        } else {
+            let address = self.location(target)?;
+            self.debugger.poke_memory(address, value);
+        }
+        Ok(())
+    }
+
+    fn location(&self, location: &str) -> Result<u16, String> {
+        self.debugger
+            .resolve_location(location)
+            .ok_or(format!("Unknown location: {location}"))
+    }
+
+    /// Formats an address along with its label and offset
+    fn describe(&self, address: u16) -> String {
+        self.debugger.symbols().describe(address)
+    }
+}
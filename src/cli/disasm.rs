@@ -0,0 +1,56 @@
+//! The `lc3 disasm` subcommand, which prints the disassembly of a program
+
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use rust_vm::{disasm::disassemble, Memory, ProgramImage};
+
+use super::symbols;
+
+pub const USAGE: &str = "disasm OBJECT_PATH [--sym SYM_PATH]
+        Prints the disassembly of the program at OBJECT_PATH, in the .obj, .hex or .bin
+        format, using the labels in the symbol table at SYM_PATH, or in OBJECT_PATH with
+        the .sym extension if it exists";
+
+/// Runs the subcommand with the arguments that follow `disasm`. Returns the exit code
+pub fn run(args: &[String]) -> Result<i32, String> {
+    let mut object_path = None;
+    let mut sym_path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--sym" => {
+                let path = args.next().ok_or(format!("Missing value for {arg}"))?;
+                sym_path = Some(PathBuf::from(path));
+            }
+            _ if object_path.is_none() && !arg.starts_with('-') => {
+                object_path = Some(PathBuf::from(arg))
+            }
+            _ => return Err(format!("Unexpected argument: {arg}")),
+        }
+    }
+    let object_path = object_path.ok_or("No object file given")?;
+
+    let sym_paths = Vec::from_iter(sym_path);
+    let symbols = symbols::read_symbols(&[&object_path], &sym_paths)?;
+
+    let image = ProgramImage::from_path(&object_path)
+        .map_err(|e| format!("Failed to load {}: {e}", object_path.display()))?;
+
+    let mut memory = Memory::new();
+    for (address, word) in image.range().zip(image.words()) {
+        memory.write(address, *word);
+    }
+    let mut stdout = io::stdout().lock();
+    for line in disassemble(&memory, image.range(), Some(&symbols)) {
+        match writeln!(stdout, "{line}") {
+            Ok(()) => (),
+            // The output was closed early, like when piped into `head`
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => break,
+            Err(e) => return Err(format!("Failed to write the disassembly: {e}")),
+        }
+    }
+    Ok(0)
+}
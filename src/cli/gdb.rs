@@ -0,0 +1,31 @@
+//! The `--gdb` option, which lets GDB control the program over a TCP connection
+
+use std::net::{Ipv4Addr, TcpListener};
+
+use rust_vm::{
+    debugger::{gdb::GdbStub, Debugger},
+    Lc3Vm,
+};
+
+/// Waits for a GDB connection on `port` of the local host, and serves the remote
+/// serial protocol on it until the client disconnects
+pub fn serve(vm: Lc3Vm, port: u16) -> Result<(), String> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+        .map_err(|e| format!("Failed to listen on port {port}: {e}"))?;
+    eprintln!(
+        "Waiting for a GDB connection on {}:{port}",
+        Ipv4Addr::LOCALHOST
+    );
+    let (stream, address) = listener
+        .accept()
+        .map_err(|e| format!("Failed to accept a GDB connection: {e}"))?;
+    eprintln!("GDB connected from {address}");
+    // Packets are small and each one waits for a reply, so they are sent right away
+    let _ = stream.set_nodelay(true);
+    let reader = stream
+        .try_clone()
+        .map_err(|e| format!("Failed to accept a GDB connection: {e}"))?;
+    let mut stub = GdbStub::new(Debugger::new(vm));
+    stub.serve(reader, stream)
+        .map_err(|e| format!("GDB connection failed: {e}"))
+}
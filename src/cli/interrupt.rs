@@ -0,0 +1,44 @@
+//! Forwards Ctrl-C to a flag instead of terminating the process, so that long running
+//! commands can be stopped
+
+use std::sync::{atomic::AtomicBool, Arc};
+
+/// Makes Ctrl-C set `flag` instead of terminating the process. Does nothing on
+/// platforms without signals
+pub fn forward_to(flag: Arc<AtomicBool>) {
+    imp::forward_to(flag)
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    };
+
+    use libc::c_int;
+
+    static FLAG: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+    extern "C" fn handle_interrupt(_signal: c_int) {
+        if let Some(flag) = FLAG.get() {
+            flag.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn forward_to(flag: Arc<AtomicBool>) {
+        if FLAG.set(flag).is_err() {
+            return;
+        }
+        let handler = handle_interrupt as extern "C" fn(c_int);
+        // SAFETY: the handler only stores to an atomic
+        unsafe { libc::signal(libc::SIGINT, handler as libc::sighandler_t) };
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use std::sync::{atomic::AtomicBool, Arc};
+
+    pub fn forward_to(_flag: Arc<AtomicBool>) {}
+}
@@ -0,0 +1,11 @@
+//! Modules used by the `lc3` binary, which are not part of the library
+
+pub mod asm;
+pub mod convert;
+pub mod dap;
+pub mod debug;
+pub mod disasm;
+pub mod gdb;
+pub mod interrupt;
+pub mod symbols;
+pub mod terminal;
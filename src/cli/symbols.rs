@@ -0,0 +1,35 @@
+//! Reads the symbol tables of the programs given on the command line
+
+use std::{fs, path::Path};
+
+use rust_vm::symbols::SymbolTable;
+
+/// Reads and merges the symbol tables at `sym_paths`. If no symbol tables are given,
+/// the `.sym` files next to the programs at `program_paths` are read if they exist
+pub fn read_symbols<P, S>(program_paths: &[P], sym_paths: &[S]) -> Result<SymbolTable, String>
+where
+    P: AsRef<Path>,
+    S: AsRef<Path>,
+{
+    let sym_paths = if sym_paths.is_empty() {
+        program_paths
+            .iter()
+            .map(|path| path.as_ref().with_extension("sym"))
+            .filter(|path| path.exists())
+            .collect()
+    } else {
+        sym_paths
+            .iter()
+            .map(|path| path.as_ref().to_path_buf())
+            .collect::<Vec<_>>()
+    };
+    let mut symbols = SymbolTable::new();
+    for path in sym_paths {
+        let source = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        let file_symbols = SymbolTable::parse_sym(&source)
+            .map_err(|e| format!("Failed to parse {}: {e}", path.display()))?;
+        symbols.merge(&file_symbols);
+    }
+    Ok(symbols)
+}
@@ -0,0 +1,122 @@
+//! Raw terminal mode for running interactive programs. In raw mode, characters typed
+//! on the keyboard are available to the program immediately instead of after a line
+//! has been entered, and they are not echoed by the terminal, which is how the LC3
+//! keyboard behaves
+
+use std::io;
+
+/// Keeps the terminal in raw mode while it is alive. The original terminal settings
+/// are restored when it is dropped, when the process panics, or when it is
+/// interrupted with Ctrl-C
+pub struct RawMode {
+    _private: (),
+}
+
+impl RawMode {
+    /// Puts the terminal connected to standard input into raw mode. Returns `None`
+    /// if standard input is not a terminal, or raw mode is not supported on this
+    /// platform
+    pub fn enable() -> io::Result<Option<Self>> {
+        if imp::enable()? {
+            Ok(Some(Self { _private: () }))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        imp::restore();
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::{io, panic, sync::OnceLock};
+
+    use libc::{c_int, termios, STDIN_FILENO};
+
+    /// The terminal settings from before raw mode was enabled
+    static ORIGINAL: OnceLock<termios> = OnceLock::new();
+
+    pub fn enable() -> io::Result<bool> {
+        // SAFETY: `isatty` has no preconditions
+        if unsafe { libc::isatty(STDIN_FILENO) } != 1 {
+            return Ok(false);
+        }
+
+        // SAFETY: `termios` is a plain C struct, for which all zeroes is a valid
+        // value. It is fully initialised by `tcgetattr` before being used
+        let mut original: termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(STDIN_FILENO, &mut original) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let original = *ORIGINAL.get_or_init(|| original);
+
+        let mut raw = original;
+        // Disable line buffering and echo. Signals stay enabled so that Ctrl-C still
+        // interrupts programs that are stuck in a loop
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+
+        install_restore_handlers();
+        // SAFETY: `raw` is a valid `termios` obtained from `tcgetattr`
+        if unsafe { libc::tcsetattr(STDIN_FILENO, libc::TCSANOW, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(true)
+    }
+
+    pub fn restore() {
+        if let Some(original) = ORIGINAL.get() {
+            // SAFETY: `original` is a valid `termios` obtained from `tcgetattr`.
+            // `tcsetattr` is async-signal-safe, so this can be called from the
+            // signal handler
+            unsafe { libc::tcsetattr(STDIN_FILENO, libc::TCSANOW, original) };
+        }
+    }
+
+    extern "C" fn handle_interrupt(_signal: c_int) {
+        restore();
+        // Use the exit status of a process killed by SIGINT
+        // SAFETY: `_exit` is async-signal-safe
+        unsafe { libc::_exit(128 + libc::SIGINT) };
+    }
+
+    extern "C" fn restore_at_exit() {
+        restore();
+    }
+
+    /// Makes sure that the terminal is restored if the process panics, is
+    /// interrupted, or exits while raw mode is enabled
+    fn install_restore_handlers() {
+        static INSTALLED: OnceLock<()> = OnceLock::new();
+        INSTALLED.get_or_init(|| {
+            let default_hook = panic::take_hook();
+            panic::set_hook(Box::new(move |info| {
+                restore();
+                default_hook(info);
+            }));
+
+            let handler = handle_interrupt as extern "C" fn(c_int);
+            // SAFETY: the handlers only call async-signal-safe functions
+            unsafe {
+                libc::signal(libc::SIGINT, handler as libc::sighandler_t);
+                libc::atexit(restore_at_exit);
+            }
+        });
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use std::io;
+
+    pub fn enable() -> io::Result<bool> {
+        Ok(false)
+    }
+
+    pub fn restore() {}
+}
@@ -0,0 +1,705 @@
+//! This module contains a server for the Debug Adapter Protocol, which lets editors
+//! debug LC3 programs at the level of their assembly source.
+//!
+//! Source lines are mapped to addresses with the line information of the assembler
+//! when an assembly file is launched. When an object file is launched, the lines that
+//! define the labels in its `.sym` file are mapped instead. The program's output is
+//! sent as `output` events, and text entered in the editor's debug console is typed
+//! on the keyboard.
+//!
+//! Like in the GDB stub, memory references are word addresses, while offsets and
+//! counts of memory requests are in bytes, with the most significant byte of each word
+//! first
+
+#[cfg(test)]
+mod tests;
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, BufRead, BufReader, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use serde_json::{json, Value};
+
+use super::{parse_number, Debugger, StopReason};
+use crate::{
+    asm::assemble,
+    symbols::SymbolTable,
+    vm::{
+        console::Console,
+        loader::ProgramImage,
+        registers::{ConditionFlag, RegisterName},
+        Lc3Vm,
+    },
+};
+
+/// The id of the only thread, as the LC3 runs a single program
+const THREAD_ID: u64 = 1;
+/// The reference of the registers scope
+const REGISTERS_REFERENCE: u64 = 1;
+/// How often a program waiting for keyboard input checks if it was paused
+const INPUT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Serves the Debug Adapter Protocol on `reader` and `writer`, until the client
+/// disconnects or closes the connection. The program is chosen by the client with a
+/// `launch` request, whose arguments are:
+///
+/// - `program`: the path of an assembly source file, or of a program in one of the
+///   formats of `ProgramFormat`
+/// - `source`: the assembly source of the program, which defaults to `program` with
+///   the `.asm` extension
+/// - `stopOnEntry`: whether to stop before the first instruction is executed
+pub fn serve<R, W>(reader: R, writer: W) -> io::Result<()>
+where
+    R: Read + Send + 'static,
+    W: Write + Send + 'static,
+{
+    let transport = Arc::new(Mutex::new(Transport::new(writer)));
+    let (keys, keyboard) = mpsc::channel();
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let console = DapConsole {
+        keyboard,
+        interrupted: Arc::clone(&interrupted),
+        transport: Arc::clone(&transport),
+    };
+    let mut debugger = Debugger::new(Lc3Vm::with_console(console));
+    debugger.set_interrupt_flag(Arc::clone(&interrupted));
+    let requests = spawn_reader(reader, keys, interrupted);
+
+    let mut session = Session {
+        debugger,
+        transport,
+        source: None,
+        breakpoints: Vec::new(),
+        stop_on_entry: false,
+    };
+    while let Ok(request) = requests.recv() {
+        if !session.dispatch(&request)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Writes messages to the client, numbering them in order
+struct Transport<W> {
+    writer: W,
+    seq: u64,
+}
+
+impl<W: Write> Transport<W> {
+    fn new(writer: W) -> Self {
+        Self { writer, seq: 0 }
+    }
+
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        let body = message.to_string();
+        write!(self.writer, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+        self.writer.flush()
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+}
+
+type SharedTransport<W> = Arc<Mutex<Transport<W>>>;
+
+fn lock<W>(transport: &SharedTransport<W>) -> std::sync::MutexGuard<'_, Transport<W>> {
+    // The lock can only be poisoned if writing a message panicked, and the next
+    // message can still be written
+    transport.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A console that sends the program's output to the client, and reads the keyboard
+/// input typed in the client's debug console
+struct DapConsole<W> {
+    keyboard: Receiver<u8>,
+    interrupted: Arc<AtomicBool>,
+    transport: SharedTransport<W>,
+}
+
+impl<W: Write> Console for DapConsole<W> {
+    /// Waits for input until the client pauses the program, which makes reading fail
+    fn read_char(&mut self) -> io::Result<u8> {
+        loop {
+            match self.keyboard.recv_timeout(INPUT_POLL_INTERVAL) {
+                Ok(char) => return Ok(char),
+                Err(RecvTimeoutError::Timeout) if !self.interrupted.load(Ordering::Relaxed) => {}
+                Err(RecvTimeoutError::Timeout) => return Err(ErrorKind::Interrupted.into()),
+                Err(RecvTimeoutError::Disconnected) => return Err(ErrorKind::UnexpectedEof.into()),
+            }
+        }
+    }
+
+    fn poll_char(&mut self) -> io::Result<Option<u8>> {
+        Ok(self.keyboard.try_recv().ok())
+    }
+
+    fn write_chars(&mut self, chars: &[u8]) -> io::Result<()> {
+        let output = String::from_utf8_lossy(chars);
+        lock(&self.transport).event("output", json!({ "category": "stdout", "output": output }))
+    }
+}
+
+/// Reads the messages sent by the client on a new thread. `pause` requests and the
+/// keyboard input from `evaluate` requests take effect as soon as they arrive, since
+/// the requests are only answered once a running program stops
+fn spawn_reader<R>(reader: R, keys: Sender<u8>, interrupted: Arc<AtomicBool>) -> Receiver<Value>
+where
+    R: Read + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut reader = BufReader::new(reader);
+        while let Some(message) = read_message(&mut reader) {
+            match message["command"].as_str() {
+                Some("pause") => interrupted.store(true, Ordering::Relaxed),
+                Some("evaluate") if message["arguments"]["context"] == "repl" => {
+                    let text = message["arguments"]["expression"].as_str().unwrap_or("");
+                    for byte in text.bytes().chain([b'\n']) {
+                        let _ = keys.send(byte);
+                    }
+                }
+                _ => (),
+            }
+            if sender.send(message).is_err() {
+                return;
+            }
+        }
+    });
+    receiver
+}
+
+/// Reads a message with its `Content-Length` header. Returns `None` at the end of the
+/// input, or if the message cannot be read
+fn read_message(reader: &mut impl BufRead) -> Option<Value> {
+    let mut len = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                len = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let mut body = vec![0; len?];
+    reader.read_exact(&mut body).ok()?;
+    serde_json::from_slice(&body).ok()
+}
+
+/// The assembly source of the launched program, and the source line of each address
+struct Source {
+    path: PathBuf,
+    lines: BTreeMap<u16, usize>,
+}
+
+impl Source {
+    /// Returns the address of the first instruction at or after `line`, and its line
+    fn address_of(&self, line: usize) -> Option<(u16, usize)> {
+        self.lines
+            .iter()
+            .filter(|(_, address_line)| **address_line >= line)
+            .min_by_key(|(address, address_line)| (**address_line, **address))
+            .map(|(address, line)| (*address, *line))
+    }
+
+    fn is(&self, path: &str) -> bool {
+        let canonical = |path: &Path| fs::canonicalize(path).unwrap_or(path.to_path_buf());
+        canonical(Path::new(path)) == canonical(&self.path)
+    }
+}
+
+/// What to do after a response has been sent
+enum Then {
+    Nothing,
+    /// Tell the client that breakpoints can be set
+    Initialize,
+    /// Start the program, or stop on entry if requested
+    Start,
+    Resume(Resume),
+    Disconnect,
+}
+
+#[derive(Clone, Copy)]
+enum Resume {
+    Continue,
+    Next,
+    StepIn,
+    StepOut,
+    StepBack,
+    ReverseContinue,
+}
+
+struct Session<W> {
+    debugger: Debugger,
+    transport: SharedTransport<W>,
+    source: Option<Source>,
+    /// The breakpoints set with the last `setBreakpoints` request
+    breakpoints: Vec<u16>,
+    stop_on_entry: bool,
+}
+
+impl<W: Write> Session<W> {
+    /// Answers a request. Returns `false` once the client has disconnected
+    fn dispatch(&mut self, request: &Value) -> io::Result<bool> {
+        if request["type"] != "request" {
+            return Ok(true);
+        }
+        let command = request["command"].as_str().unwrap_or("");
+        let (result, then) = match self.handle(command, &request["arguments"]) {
+            Ok((body, then)) => (Ok(body), then),
+            Err(message) => (Err(message), Then::Nothing),
+        };
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": command,
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(Value::Null) => (),
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+        lock(&self.transport).send(response)?;
+
+        match then {
+            Then::Nothing => (),
+            Then::Initialize => lock(&self.transport).event("initialized", json!({}))?,
+            Then::Start if self.stop_on_entry => self.stopped("entry", None)?,
+            Then::Start => self.resume(Resume::Continue)?,
+            Then::Resume(resume) => self.resume(resume)?,
+            Then::Disconnect => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn handle(&mut self, command: &str, args: &Value) -> Result<(Value, Then), String> {
+        let body = match command {
+            "initialize" => json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsSetVariable": true,
+                "supportsReadMemoryRequest": true,
+                "supportsWriteMemoryRequest": true,
+                "supportsStepBack": true,
+            }),
+            "launch" => {
+                self.launch(args)?;
+                return Ok((Value::Null, Then::Initialize));
+            }
+            "setBreakpoints" => self.set_breakpoints(args),
+            "configurationDone" => return Ok((Value::Null, Then::Start)),
+            "threads" => json!({ "threads": [{ "id": THREAD_ID, "name": "LC3" }] }),
+            "stackTrace" => self.stack_trace(),
+            "scopes" => json!({
+                "scopes": [{
+                    "name": "Registers",
+                    "presentationHint": "registers",
+                    "variablesReference": REGISTERS_REFERENCE,
+                    "expensive": false,
+                }]
+            }),
+            "variables" => self.variables(args),
+            "setVariable" => self.set_variable(args)?,
+            "readMemory" => self.read_memory(args)?,
+            "writeMemory" => self.write_memory(args)?,
+            "evaluate" => self.evaluate(args)?,
+            "continue" => {
+                let body = json!({ "allThreadsContinued": true });
+                return Ok((body, Then::Resume(Resume::Continue)));
+            }
+            "next" => return Ok((Value::Null, Then::Resume(Resume::Next))),
+            "stepIn" => return Ok((Value::Null, Then::Resume(Resume::StepIn))),
+            "stepOut" => return Ok((Value::Null, Then::Resume(Resume::StepOut))),
+            "stepBack" => return Ok((Value::Null, Then::Resume(Resume::StepBack))),
+            "reverseContinue" => {
+                return Ok((Value::Null, Then::Resume(Resume::ReverseContinue)));
+            }
+            // The program is paused by the reader thread, and reports the stop when
+            // it stops running
+            "pause" => Value::Null,
+            "disconnect" | "terminate" => return Ok((Value::Null, Then::Disconnect)),
+            _ => return Err(format!("Unsupported request: {command}")),
+        };
+        Ok((body, Then::Nothing))
+    }
+
+    fn launch(&mut self, args: &Value) -> Result<(), String> {
+        let program = args["program"]
+            .as_str()
+            .ok_or("The launch configuration has no program")?;
+        let program = PathBuf::from(program);
+        let boot_os = args["bootOs"].as_bool().unwrap_or(false);
+        let vm = self.debugger.vm_mut();
+        if boot_os {
+            vm.load_os();
+        }
+        let source = if program.extension().is_some_and(|ext| ext == "asm") {
+            let text = read_file(&program)?;
+            let assembly = assemble(&text).map_err(|errors| {
+                let errors = errors.iter().map(|e| format!("{}:{e}", program.display()));
+                errors.collect::<Vec<String>>().join("\n")
+            })?;
+            let image = ProgramImage::new(assembly.origin, assembly.words)
+                .map_err(|e| format!("Failed to load {}: {e}", program.display()))?;
+            vm.load_image(&image);
+            vm.set_symbols(assembly.symbols);
+            Some(Source {
+                path: program,
+                lines: assembly.lines,
+            })
+        } else {
+            vm.load_program(&program)
+                .map_err(|e| format!("Failed to load {}: {e}", program.display()))?;
+            let sym_path = program.with_extension("sym");
+            if sym_path.exists() {
+                let symbols = SymbolTable::parse_sym(&read_file(&sym_path)?)
+                    .map_err(|e| format!("Failed to parse {}: {e}", sym_path.display()))?;
+                vm.set_symbols(symbols);
+            }
+            let path = match args["source"].as_str() {
+                Some(path) => PathBuf::from(path),
+                None => program.with_extension("asm"),
+            };
+            fs::read_to_string(&path).ok().map(|text| Source {
+                lines: label_lines(&text, vm.symbols()),
+                path,
+            })
+        };
+        if boot_os {
+            vm.boot_os();
+        }
+        if let Some(ignore_privilege) = args["ignorePrivilege"].as_bool() {
+            vm.set_ignore_privilege(ignore_privilege);
+        }
+        self.source = source;
+        self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+        Ok(())
+    }
+
+    fn set_breakpoints(&mut self, args: &Value) -> Value {
+        for address in self.breakpoints.drain(..) {
+            self.debugger.remove_breakpoint(address);
+        }
+        let source = self
+            .source
+            .as_ref()
+            .filter(|source| source.is(args["source"]["path"].as_str().unwrap_or("")));
+        let requested = args["breakpoints"].as_array().cloned().unwrap_or_default();
+        let mut breakpoints = Vec::new();
+        for breakpoint in requested {
+            let line = breakpoint["line"].as_u64().unwrap_or(0) as usize;
+            match source.and_then(|source| source.address_of(line)) {
+                Some((address, line)) => {
+                    self.debugger.add_breakpoint(address);
+                    self.breakpoints.push(address);
+                    breakpoints.push(json!({
+                        "verified": true,
+                        "line": line,
+                        "instructionReference": memory_reference(address),
+                    }));
+                }
+                None => breakpoints.push(json!({
+                    "verified": false,
+                    "line": line,
+                    "message": "There is no instruction at or after this line",
+                })),
+            }
+        }
+        json!({ "breakpoints": breakpoints })
+    }
+
+    /// Returns the only stack frame, which is the current instruction. The LC3 has no
+    /// frame pointer convention, so callers cannot be found
+    fn stack_trace(&self) -> Value {
+        let pc = self.debugger.vm().registers().program_counter();
+        let name = self
+            .debugger
+            .symbols()
+            .label_offset(pc)
+            .unwrap_or_else(|| format!("x{pc:04X}"));
+        let mut frame = json!({
+            "id": 0,
+            "name": name,
+            "line": 0,
+            "column": 0,
+            "instructionPointerReference": memory_reference(pc),
+        });
+        let line = self
+            .source
+            .as_ref()
+            .and_then(|source| Some((source, source.lines.get(&pc)?)));
+        if let Some((source, line)) = line {
+            frame["line"] = json!(line);
+            frame["column"] = json!(1);
+            frame["source"] = json!({
+                "name": source.path.file_name().map(|name| name.to_string_lossy()),
+                "path": source.path.to_string_lossy(),
+            });
+        }
+        json!({ "stackFrames": [frame], "totalFrames": 1 })
+    }
+
+    fn variables(&self, args: &Value) -> Value {
+        if args["variablesReference"] != REGISTERS_REFERENCE {
+            return json!({ "variables": [] });
+        }
+        let vm = self.debugger.vm();
+        let registers = vm.registers();
+        let mut variables = (0..8)
+            .map(RegisterName::general)
+            .chain([RegisterName::PC])
+            .map(|register| {
+                let value = registers.get_reg_value(register);
+                json!({
+                    "name": register.to_string(),
+                    "value": format!("x{value:04X} ({})", value as i16),
+                    "variablesReference": 0,
+                    "memoryReference": memory_reference(value),
+                })
+            })
+            .collect::<Vec<Value>>();
+        let flag = match vm.get_cond_flag() {
+            ConditionFlag::Neg => "n",
+            ConditionFlag::Zro => "z",
+            ConditionFlag::Pos => "p",
+        };
+        variables.push(json!({ "name": "COND", "value": flag, "variablesReference": 0 }));
+        variables.push(json!({
+            "name": "PSR",
+            "value": format!("x{:04X}", registers.psr()),
+            "variablesReference": 0,
+        }));
+        json!({ "variables": variables })
+    }
+
+    fn set_variable(&mut self, args: &Value) -> Result<Value, String> {
+        let name = args["name"].as_str().unwrap_or("");
+        let register = name
+            .parse::<RegisterName>()
+            .map_err(|_| format!("Unknown register: {name}"))?;
+        let value = args["value"].as_str().unwrap_or("");
+        let value = parse_number(value).ok_or(format!("Invalid value: {value}"))?;
+        let registers = self.debugger.vm_mut().registers_mut();
+        registers.set_reg_value(register, value);
+        let value = registers.get_reg_value(register);
+        Ok(json!({ "value": format!("x{value:04X}") }))
+    }
+
+    /// Reads memory without the side effects of reading the device registers
+    fn read_memory(&self, args: &Value) -> Result<Value, String> {
+        let start = byte_offset(args)?;
+        let count = args["count"].as_u64().unwrap_or(0);
+        let end = i64::try_from(count)
+            .ok()
+            .and_then(|count| start.checked_add(count))
+            .ok_or(format!("Invalid count: {count}"))?
+            .min(MEMORY_BYTES);
+        let vm = self.debugger.vm();
+        let data = (start..end)
+            .map(|index| {
+                let word = vm.peek_memory((index / 2) as u16);
+                if index % 2 == 0 {
+                    (word >> 8) as u8
+                } else {
+                    word as u8
+                }
+            })
+            .collect::<Vec<u8>>();
+        Ok(json!({
+            "address": memory_reference((start / 2) as u16),
+            "data": base64_encode(&data),
+            "unreadableBytes": count - data.len() as u64,
+        }))
+    }
+
+    /// Writes memory without the side effects of writing the device registers
+    fn write_memory(&mut self, args: &Value) -> Result<Value, String> {
+        let start = byte_offset(args)?;
+        let data =
+            base64_decode(args["data"].as_str().unwrap_or("")).ok_or("Invalid base64 data")?;
+        if start % 2 != 0 || data.len() % 2 != 0 {
+            return Err("Memory can only be written in whole words".to_string());
+        }
+        let end = i64::try_from(data.len())
+            .ok()
+            .and_then(|length| start.checked_add(length));
+        if end.is_none_or(|end| end > MEMORY_BYTES) {
+            return Err("Memory cannot be written past xFFFF".to_string());
+        }
+        // The written words end at xFFFF at the latest, which was checked above
+        let first = (start / 2) as u16;
+        for (index, word) in data.chunks(2).enumerate() {
+            let word = u16::from_be_bytes([word[0], word[1]]);
+            self.debugger.poke_memory(first + index as u16, word);
+        }
+        Ok(json!({ "bytesWritten": data.len() }))
+    }
+
+    /// Evaluates registers and memory locations. Text entered in the debug console is
+    /// typed on the keyboard by the reader thread instead
+    fn evaluate(&self, args: &Value) -> Result<Value, String> {
+        if args["context"] == "repl" {
+            return Ok(json!({ "result": "", "variablesReference": 0 }));
+        }
+        let expression = args["expression"].as_str().unwrap_or("");
+        let vm = self.debugger.vm();
+        let value = match expression.parse::<RegisterName>() {
+            Ok(register) => vm.registers().get_reg_value(register),
+            Err(()) => {
+                let address = self
+                    .debugger
+                    .resolve_location(expression)
+                    .ok_or(format!("Unknown location: {expression}"))?;
+                vm.peek_memory(address)
+            }
+        };
+        Ok(json!({
+            "result": format!("x{value:04X} ({})", value as i16),
+            "variablesReference": 0,
+        }))
+    }
+
+    /// Resumes the program and reports why it stopped
+    fn resume(&mut self, resume: Resume) -> io::Result<()> {
+        self.debugger
+            .interrupt_flag()
+            .store(false, Ordering::Relaxed);
+        let result = match resume {
+            Resume::Continue => self.debugger.continue_execution(),
+            Resume::Next => self.debugger.step_over(),
+            Resume::StepIn => self.debugger.step(1),
+            Resume::StepOut => self.debugger.finish(),
+            Resume::StepBack => Ok(self.debugger.step_back(1)),
+            Resume::ReverseContinue => Ok(self.debugger.reverse_continue()),
+        };
+        match result {
+            Ok(StopReason::Done) => self.stopped("step", None),
+            Ok(StopReason::Breakpoint(_)) => self.stopped("breakpoint", None),
+            Ok(StopReason::Watchpoint(hit)) => {
+                let description = hit.describe(self.debugger.symbols());
+                self.stopped("data breakpoint", Some(description))
+            }
+            Ok(StopReason::Interrupted) => self.stopped("pause", None),
+            Ok(StopReason::HistoryStart) => {
+                let description = "Reached the oldest recorded instruction".to_string();
+                self.stopped("step", Some(description))
+            }
+            Ok(StopReason::Halted) => {
+                let mut transport = lock(&self.transport);
+                transport.event("exited", json!({ "exitCode": 0 }))?;
+                transport.event("terminated", json!({}))
+            }
+            Err(e) => {
+                let description = e.with_symbols(self.debugger.symbols()).to_string();
+                self.stopped("exception", Some(description))
+            }
+        }
+    }
+
+    fn stopped(&self, reason: &str, description: Option<String>) -> io::Result<()> {
+        let mut body = json!({
+            "reason": reason,
+            "threadId": THREAD_ID,
+            "allThreadsStopped": true,
+        });
+        if let Some(description) = description {
+            body["description"] = json!(description);
+            body["text"] = json!(description);
+        }
+        lock(&self.transport).event("stopped", body)
+    }
+}
+
+/// The number of bytes in memory
+const MEMORY_BYTES: i64 = 0x20000;
+
+/// Returns the byte offset of the memory that a `readMemory` or `writeMemory` request
+/// refers to, which must be inside memory
+fn byte_offset(args: &Value) -> Result<i64, String> {
+    let reference = args["memoryReference"].as_str().unwrap_or("");
+    let address = parse_number(reference).ok_or(format!("Invalid address: {reference}"))?;
+    let offset = args["offset"].as_i64().unwrap_or(0);
+    (i64::from(address) * 2)
+        .checked_add(offset)
+        .filter(|start| (0..MEMORY_BYTES).contains(start))
+        .ok_or(format!("Invalid offset: {offset}"))
+}
+
+fn memory_reference(address: u16) -> String {
+    format!("0x{address:04x}")
+}
+
+fn read_file(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {e}", path.display()))
+}
+
+/// Maps the lines of `source` that define a label in `symbols` to the label's address
+fn label_lines(source: &str, symbols: &SymbolTable) -> BTreeMap<u16, usize> {
+    source
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let code = line.split(';').next().unwrap_or("");
+            let label = code.split_whitespace().next()?;
+            Some((symbols.address(label)?, index + 1))
+        })
+        .collect()
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(data: &[u8]) -> String {
+    let mut encoded = String::new();
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (index, byte)| {
+            bits | u32::from(*byte) << (16 - 8 * index)
+        });
+        for index in 0..4 {
+            if index <= chunk.len() {
+                let digit = (bits >> (18 - 6 * index)) & 0x3f;
+                encoded.push(BASE64_ALPHABET[digit as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+fn base64_decode(encoded: &str) -> Option<Vec<u8>> {
+    let digits = encoded
+        .trim_end_matches('=')
+        .bytes()
+        .map(|char| BASE64_ALPHABET.iter().position(|digit| *digit == char))
+        .collect::<Option<Vec<usize>>>()?;
+    let mut data = Vec::new();
+    for chunk in digits.chunks(4) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (index, digit)| {
+            bits | (*digit as u32) << (18 - 6 * index)
+        });
+        let len = chunk.len().checked_sub(1)?;
+        data.extend(bits.to_be_bytes()[1..=len].iter());
+    }
+    Some(data)
+}
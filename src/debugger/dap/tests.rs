@@ -0,0 +1,367 @@
+use std::io::Cursor;
+
+use tempfile::TempDir;
+
+use super::*;
+use crate::vm::console::OutputBuffer;
+
+const PROGRAM: &str = "; Counts down from 3
+        .ORIG x3000
+        AND R0, R0, #0
+        ADD R0, R0, #3
+
+LOOP    ADD R0, R0, #-1
+        BRp LOOP
+        LEA R0, DONE
+        PUTS
+        HALT
+DONE    .STRINGZ \"done\"
+        .END
+";
+
+/// Writes `source` to `name` in a new temporary directory
+fn source_file(name: &str, source: &str) -> (TempDir, String) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(name);
+    fs::write(&path, source).unwrap();
+    (dir, path.to_string_lossy().into_owned())
+}
+
+/// Sends the requests to a server, and returns the messages it sent back
+fn exchange(requests: &[(&str, Value)]) -> Vec<Value> {
+    let mut input = String::new();
+    for (seq, (command, arguments)) in requests.iter().enumerate() {
+        let body = json!({
+            "seq": seq + 1,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        })
+        .to_string();
+        input += &format!("Content-Length: {}\r\n\r\n{body}", body.len());
+    }
+    let output = OutputBuffer::new();
+    serve(Cursor::new(input.into_bytes()), output.clone()).unwrap();
+
+    let contents = output.contents();
+    let mut reader = &contents[..];
+    let mut messages = Vec::new();
+    while let Some(message) = read_message(&mut reader) {
+        messages.push(message);
+    }
+    messages
+}
+
+/// Returns the response to `command`
+fn response<'a>(messages: &'a [Value], command: &str) -> &'a Value {
+    messages
+        .iter()
+        .find(|message| message["type"] == "response" && message["command"] == command)
+        .unwrap()
+}
+
+/// Returns the names of the events, and the reasons of `stopped` events
+fn events(messages: &[Value]) -> Vec<String> {
+    messages
+        .iter()
+        .filter(|message| message["type"] == "event" && message["event"] != "output")
+        .map(|message| match message["body"]["reason"].as_str() {
+            Some(reason) => format!("{} {reason}", message["event"].as_str().unwrap()),
+            None => message["event"].as_str().unwrap().to_string(),
+        })
+        .collect()
+}
+
+fn output(messages: &[Value]) -> String {
+    messages
+        .iter()
+        .filter(|message| message["event"] == "output")
+        .map(|message| message["body"]["output"].as_str().unwrap())
+        .collect()
+}
+
+#[test]
+fn test_breakpoints() {
+    let (_dir, path) = source_file("count.asm", PROGRAM);
+    let messages = exchange(&[
+        ("initialize", json!({})),
+        ("launch", json!({ "program": path })),
+        (
+            "setBreakpoints",
+            json!({
+                "source": { "path": path },
+                "breakpoints": [{ "line": 5 }, { "line": 7 }, { "line": 100 }],
+            }),
+        ),
+        ("configurationDone", json!({})),
+        ("stackTrace", json!({ "threadId": 1 })),
+        ("continue", json!({ "threadId": 1 })),
+        ("continue", json!({ "threadId": 1 })),
+        ("continue", json!({ "threadId": 1 })),
+        ("continue", json!({ "threadId": 1 })),
+    ]);
+    assert_eq!(
+        response(&messages, "initialize")["body"]["supportsConfigurationDoneRequest"],
+        true
+    );
+    // Breakpoints on lines without instructions move to the next instruction
+    let breakpoints = &response(&messages, "setBreakpoints")["body"]["breakpoints"];
+    assert_eq!(breakpoints[0]["line"], 6);
+    assert_eq!(breakpoints[0]["verified"], true);
+    assert_eq!(breakpoints[0]["instructionReference"], "0x3002");
+    assert_eq!(breakpoints[1]["line"], 7);
+    assert_eq!(breakpoints[2]["verified"], false);
+
+    let frame = &response(&messages, "stackTrace")["body"]["stackFrames"][0];
+    assert_eq!(frame["name"], "LOOP");
+    assert_eq!(frame["line"], 6);
+    assert_eq!(frame["source"]["path"], path);
+    assert_eq!(
+        events(&messages),
+        vec![
+            "initialized",
+            "stopped breakpoint",
+            "stopped breakpoint",
+            "stopped breakpoint",
+            "stopped breakpoint",
+            "stopped breakpoint",
+        ]
+    );
+}
+
+#[test]
+fn test_stepping_and_registers() {
+    let (_dir, path) = source_file("count.asm", PROGRAM);
+    let messages = exchange(&[
+        ("launch", json!({ "program": path, "stopOnEntry": true })),
+        ("configurationDone", json!({})),
+        ("stepIn", json!({ "threadId": 1 })),
+        ("next", json!({ "threadId": 1 })),
+        ("scopes", json!({ "frameId": 0 })),
+        (
+            "setVariable",
+            json!({ "variablesReference": 1, "name": "R1", "value": "x1F" }),
+        ),
+        ("variables", json!({ "variablesReference": 1 })),
+        (
+            "evaluate",
+            json!({ "expression": "DONE", "context": "watch" }),
+        ),
+        ("continue", json!({ "threadId": 1 })),
+    ]);
+    assert_eq!(
+        events(&messages),
+        vec![
+            "initialized",
+            "stopped entry",
+            "stopped step",
+            "stopped step",
+            "exited",
+            "terminated",
+        ]
+    );
+    let scopes = &response(&messages, "scopes")["body"]["scopes"];
+    assert_eq!(scopes[0]["variablesReference"], REGISTERS_REFERENCE);
+
+    let variables = &response(&messages, "variables")["body"]["variables"];
+    let variable = |name: &str| {
+        variables
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|variable| variable["name"] == name)
+            .unwrap()["value"]
+            .clone()
+    };
+    assert_eq!(variable("R0"), "x0003 (3)");
+    assert_eq!(variable("R1"), "x001F (31)");
+    assert_eq!(variable("PC"), "x3002 (12290)");
+    assert_eq!(variable("COND"), "p");
+    assert_eq!(variable("PSR"), "x8001");
+    assert_eq!(
+        response(&messages, "evaluate")["body"]["result"],
+        "x0064 (100)"
+    );
+    assert_eq!(output(&messages), "doneLC3 VM execution halted\n");
+}
+
+#[test]
+fn test_memory() {
+    let (_dir, path) = source_file("count.asm", PROGRAM);
+    let messages = exchange(&[
+        ("launch", json!({ "program": path, "stopOnEntry": true })),
+        ("configurationDone", json!({})),
+        (
+            "writeMemory",
+            json!({ "memoryReference": "0x4000", "offset": 2, "data": base64_encode(&[0xbe, 0xef]) }),
+        ),
+        (
+            "readMemory",
+            json!({ "memoryReference": "0x3fff", "offset": 2, "count": 6 }),
+        ),
+        (
+            "writeMemory",
+            json!({ "memoryReference": "0x4000", "offset": 1, "data": base64_encode(&[0xbe, 0xef]) }),
+        ),
+        // The top word of memory can be written, but not past it
+        (
+            "writeMemory",
+            json!({ "memoryReference": "0xffff", "data": base64_encode(&[0x80, 0x01]) }),
+        ),
+        (
+            "readMemory",
+            json!({ "memoryReference": "0xffff", "count": 2 }),
+        ),
+        (
+            "writeMemory",
+            json!({ "memoryReference": "0xffff", "data": base64_encode(&[0x80, 0x01, 0, 0]) }),
+        ),
+        // Device registers are written without side effects
+        (
+            "writeMemory",
+            json!({ "memoryReference": "0xfe00", "data": base64_encode(&[0x80, 0]) }),
+        ),
+        (
+            "readMemory",
+            json!({ "memoryReference": "0xfe00", "count": 2 }),
+        ),
+    ]);
+    let messages = messages
+        .iter()
+        .filter(|message| message["type"] == "response")
+        .collect::<Vec<&Value>>();
+    assert_eq!(messages[2]["body"]["bytesWritten"], 2);
+    let data = messages[3]["body"]["data"].as_str().unwrap();
+    assert_eq!(base64_decode(data).unwrap(), vec![0, 0, 0xbe, 0xef, 0, 0]);
+    assert_eq!(messages[4]["success"], false);
+    assert_eq!(messages[5]["body"]["bytesWritten"], 2);
+    let data = messages[6]["body"]["data"].as_str().unwrap();
+    assert_eq!(base64_decode(data).unwrap(), vec![0x80, 0x01]);
+    assert_eq!(messages[7]["success"], false);
+    let data = messages[9]["body"]["data"].as_str().unwrap();
+    assert_eq!(base64_decode(data).unwrap(), vec![0x80, 0]);
+}
+
+#[test]
+fn test_memory_out_of_range() {
+    let (_dir, path) = source_file("count.asm", PROGRAM);
+    let data = base64_encode(&[0xbe, 0xef]);
+    let messages = exchange(&[
+        ("launch", json!({ "program": path, "stopOnEntry": true })),
+        ("configurationDone", json!({})),
+        (
+            "readMemory",
+            json!({ "memoryReference": "0x0000", "offset": -2, "count": 2 }),
+        ),
+        (
+            "readMemory",
+            json!({ "memoryReference": "0xffff", "offset": i64::MAX, "count": 2 }),
+        ),
+        (
+            "readMemory",
+            json!({ "memoryReference": "0xffff", "count": u64::MAX }),
+        ),
+        (
+            "writeMemory",
+            json!({ "memoryReference": "0x0000", "offset": -2, "data": data }),
+        ),
+        (
+            "writeMemory",
+            json!({ "memoryReference": "0xffff", "offset": i64::MAX - 1, "data": data }),
+        ),
+        (
+            "readMemory",
+            json!({ "memoryReference": "0xffff", "count": 4 }),
+        ),
+    ]);
+    let messages = messages
+        .iter()
+        .filter(|message| message["type"] == "response")
+        .collect::<Vec<&Value>>();
+    for message in &messages[2..7] {
+        assert_eq!(message["success"], false);
+    }
+    // The reads past the top of memory are reported as unreadable
+    assert_eq!(messages[7]["body"]["unreadableBytes"], 2);
+}
+
+#[test]
+fn test_keyboard_input() {
+    let source = ".ORIG x3000\nGETC\nOUT\nHALT\n.END";
+    let (_dir, path) = source_file("echo.asm", source);
+    let messages = exchange(&[
+        ("launch", json!({ "program": path })),
+        ("evaluate", json!({ "expression": "a", "context": "repl" })),
+        ("configurationDone", json!({})),
+    ]);
+    assert!(output(&messages).starts_with('a'));
+}
+
+#[test]
+fn test_launch_object_file() {
+    let assembly = assemble(PROGRAM).unwrap();
+    let (dir, source_path) = source_file("count.asm", PROGRAM);
+    let program = dir.path().join("count.obj");
+    fs::write(&program, assembly.to_obj_bytes()).unwrap();
+    let mut sym = Vec::new();
+    assembly.symbols.write_sym(&mut sym).unwrap();
+    fs::write(dir.path().join("count.sym"), sym).unwrap();
+
+    let messages = exchange(&[
+        ("launch", json!({ "program": program })),
+        (
+            "setBreakpoints",
+            json!({
+                "source": { "path": source_path },
+                "breakpoints": [{ "line": 3 }, { "line": 10 }],
+            }),
+        ),
+    ]);
+    // Only the lines with labels from the symbol table can be mapped
+    let breakpoints = &response(&messages, "setBreakpoints")["body"]["breakpoints"];
+    assert_eq!(breakpoints[0]["line"], 6);
+    assert_eq!(breakpoints[1]["line"], 11);
+    assert_eq!(breakpoints[1]["instructionReference"], "0x3007");
+}
+
+#[test]
+fn test_launch_privilege() {
+    let source = ".ORIG x3000\nLDI R0, KBSR\nHALT\nKBSR .FILL xFE00\n.END";
+    let (_dir, path) = source_file("poll.asm", source);
+    let launch =
+        |arguments: Value| exchange(&[("launch", arguments), ("configurationDone", json!({}))]);
+    let messages = launch(json!({ "program": path }));
+    assert_eq!(events(&messages)[1], "stopped exception");
+    let messages = launch(json!({ "program": path, "ignorePrivilege": true }));
+    assert_eq!(
+        events(&messages),
+        vec!["initialized", "exited", "terminated"]
+    );
+    // The operating system handles the exception itself
+    let messages = launch(json!({ "program": path, "bootOs": true }));
+    assert!(output(&messages).contains("access control violation"));
+    let messages = launch(json!({ "program": path, "bootOs": true, "ignorePrivilege": true }));
+    assert!(!output(&messages).contains("access control violation"));
+}
+
+#[test]
+fn test_launch_errors() {
+    let (_dir, path) = source_file("bad.asm", ".ORIG x3000\nADD R0, R0\n.END");
+    let messages = exchange(&[("launch", json!({ "program": path })), ("step", json!({}))]);
+    let launch = response(&messages, "launch");
+    assert_eq!(launch["success"], false);
+    assert!(launch["message"]
+        .as_str()
+        .unwrap()
+        .ends_with("bad.asm:2:1: expected 3 operand(s), found 2"));
+    assert_eq!(response(&messages, "step")["success"], false);
+}
+
+#[test]
+fn test_base64() {
+    for data in [&b""[..], b"a", b"ab", b"abc", b"\xff\x00\x10\x20"] {
+        assert_eq!(base64_decode(&base64_encode(data)).unwrap(), data);
+    }
+    assert_eq!(base64_encode(b"LC3"), "TEMz");
+    assert_eq!(base64_encode(b"LC"), "TEM=");
+}
@@ -0,0 +1,458 @@
+//! This module contains a stub for the GDB remote serial protocol, which lets GDB and
+//! other front ends that speak the protocol control a `Debugger`.
+//!
+//! The LC3 is word addressed, so addresses in memory packets are word addresses,
+//! while lengths are counted in bytes like in every other target. Words and registers
+//! are sent with the most significant byte first, like in `.obj` files. The registers
+//! are numbered R0 to R7, followed by the PC (8) and the PSR (9). Besides software
+//! breakpoints, watchpoints on memory and the device registers are supported, and so
+//! is executing backwards with the `bs` and `bc` packets
+
+#[cfg(test)]
+mod tests;
+
+use std::{
+    fmt::Write as _,
+    io::{self, Read, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+};
+
+use super::{Debugger, StopReason, WatchKind, Watchpoint};
+use crate::vm::{error::VmError, registers::RegisterName};
+
+/// The number of registers sent by the `g` packet
+const REGISTER_COUNT: usize = 10;
+/// The largest packet the stub accepts, which is advertised to the client
+const PACKET_SIZE: usize = 0x1000;
+/// The byte sent by the client to interrupt a running program
+const INTERRUPT: u8 = 0x03;
+
+/// The signal reported when the program stopped at a breakpoint or after a step
+const SIGTRAP: u8 = 5;
+/// The signal reported when the program was interrupted by the client
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGABRT: u8 = 6;
+const SIGSEGV: u8 = 11;
+
+/// Serves the GDB remote serial protocol for a debugger
+pub struct GdbStub {
+    debugger: Debugger,
+    /// The reply to the `?` packet, which describes why the program last stopped
+    last_stop: String,
+    /// Set by the `QStartNoAckMode` packet, after which packets are not acknowledged
+    no_ack: bool,
+    /// Set when the client detaches or kills the program
+    done: bool,
+}
+
+impl GdbStub {
+    pub fn new(debugger: Debugger) -> Self {
+        Self {
+            debugger,
+            last_stop: format!("S{SIGTRAP:02x}"),
+            no_ack: false,
+            done: false,
+        }
+    }
+
+    pub fn debugger(&self) -> &Debugger {
+        &self.debugger
+    }
+
+    pub fn into_debugger(self) -> Debugger {
+        self.debugger
+    }
+
+    /// Handles the packets read from `reader` and writes the replies to `writer`, until
+    /// the client detaches, kills the program or closes the connection.
+    ///
+    /// `reader` is read on its own thread, so that the client can interrupt a running
+    /// program
+    pub fn serve<R, W>(&mut self, reader: R, mut writer: W) -> io::Result<()>
+    where
+        R: Read + Send + 'static,
+        W: Write,
+    {
+        let bytes = spawn_reader(reader, self.debugger.interrupt_flag());
+        self.done = false;
+        while !self.done {
+            let Some(packet) = self.read_packet(&bytes, &mut writer)? else {
+                return Ok(());
+            };
+            let reply = self.handle(&packet, &mut writer)?;
+            if let Some(reply) = reply {
+                write_packet(&mut writer, &reply)?;
+            }
+            if packet == "QStartNoAckMode" {
+                self.no_ack = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the next packet and acknowledges it. Returns `None` when the connection is
+    /// closed
+    fn read_packet<W: Write>(
+        &self,
+        bytes: &mpsc::Receiver<u8>,
+        writer: &mut W,
+    ) -> io::Result<Option<String>> {
+        loop {
+            // Acknowledgements are skipped, and so are interrupts, which have already
+            // set the interrupt flag
+            match bytes.recv() {
+                Ok(b'$') => (),
+                Ok(_) => continue,
+                Err(_) => return Ok(None),
+            }
+            let mut data = Vec::new();
+            loop {
+                match bytes.recv() {
+                    Ok(b'#') => break,
+                    Ok(byte) => data.push(byte),
+                    Err(_) => return Ok(None),
+                }
+            }
+            let (Ok(high), Ok(low)) = (bytes.recv(), bytes.recv()) else {
+                return Ok(None);
+            };
+            let checksum = std::str::from_utf8(&[high, low])
+                .ok()
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok());
+            if self.no_ack || checksum == Some(checksum_of(&data)) {
+                if !self.no_ack {
+                    writer.write_all(b"+")?;
+                }
+                return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+            }
+            writer.write_all(b"-")?;
+            writer.flush()?;
+        }
+    }
+
+    /// Executes a packet. Returns the reply, or `None` if the packet has none
+    fn handle<W: Write>(&mut self, packet: &str, writer: &mut W) -> io::Result<Option<String>> {
+        let Some(command) = packet.chars().next() else {
+            return Ok(Some(String::new()));
+        };
+        let args = &packet[command.len_utf8()..];
+        let reply = match command {
+            '?' => self.last_stop.clone(),
+            'g' => self.read_registers(),
+            'G' => ok_or_error(self.write_registers(args)),
+            'p' => self.read_register(args).unwrap_or_else(error),
+            'P' => ok_or_error(self.write_register(args)),
+            'm' => self.read_memory(args).unwrap_or_else(error),
+            'M' => ok_or_error(self.write_memory(args)),
+            'Z' | 'z' => self.breakpoint(command == 'Z', args),
+            's' | 'c' => {
+                if let Err(reply) = self.set_resume_address(args) {
+                    return Ok(Some(reply));
+                }
+                self.debugger
+                    .interrupt_flag()
+                    .store(false, Ordering::Relaxed);
+                let result = if command == 's' {
+                    self.debugger.step(1)
+                } else {
+                    self.debugger.continue_execution()
+                };
+                self.stop_reply(result, writer)?
+            }
+            'b' if args == "s" || args == "c" => {
+                self.debugger
+                    .interrupt_flag()
+                    .store(false, Ordering::Relaxed);
+                let reason = if args == "s" {
+                    self.debugger.step_back(1)
+                } else {
+                    self.debugger.reverse_continue()
+                };
+                self.stop_reply(Ok(reason), writer)?
+            }
+            'H' => "OK".to_string(),
+            'D' => {
+                self.done = true;
+                "OK".to_string()
+            }
+            'k' => {
+                self.done = true;
+                return Ok(None);
+            }
+            _ => self.query(packet),
+        };
+        Ok(Some(reply))
+    }
+
+    /// Answers the general queries that the stub supports. Unsupported packets get an
+    /// empty reply
+    fn query(&self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            format!("PacketSize={PACKET_SIZE:x};QStartNoAckMode+;ReverseStep+;ReverseContinue+")
+        } else if packet == "qAttached" {
+            "1".to_string()
+        } else if packet == "QStartNoAckMode" {
+            "OK".to_string()
+        } else {
+            String::new()
+        }
+    }
+
+    fn read_registers(&self) -> String {
+        let registers = self.debugger.vm().registers();
+        (0..REGISTER_COUNT)
+            .map(|id| registers.get_reg_value(register_name(id)))
+            .fold(String::new(), |mut hex, value| {
+                let _ = write!(hex, "{value:04x}");
+                hex
+            })
+    }
+
+    fn write_registers(&mut self, args: &str) -> Option<()> {
+        let values = parse_words(args)?;
+        if values.len() != REGISTER_COUNT {
+            return None;
+        }
+        let registers = self.debugger.vm_mut().registers_mut();
+        for (id, value) in values.into_iter().enumerate() {
+            registers.set_reg_value(register_name(id), value);
+        }
+        Some(())
+    }
+
+    fn read_register(&self, args: &str) -> Option<String> {
+        let id = usize::from_str_radix(args, 16).ok()?;
+        if id >= REGISTER_COUNT {
+            return None;
+        }
+        let value = self
+            .debugger
+            .vm()
+            .registers()
+            .get_reg_value(register_name(id));
+        Some(format!("{value:04x}"))
+    }
+
+    fn write_register(&mut self, args: &str) -> Option<()> {
+        let (id, value) = args.split_once('=')?;
+        let id = usize::from_str_radix(id, 16).ok()?;
+        let [value] = parse_words(value)?[..] else {
+            return None;
+        };
+        if id >= REGISTER_COUNT {
+            return None;
+        }
+        let registers = self.debugger.vm_mut().registers_mut();
+        registers.set_reg_value(register_name(id), value);
+        Some(())
+    }
+
+    /// Reads memory without the side effects of reading the device registers
+    fn read_memory(&self, args: &str) -> Option<String> {
+        let (address, len) = parse_address_len(args)?;
+        // Replies may be shorter than requested, and must fit into a packet
+        let len = len.min(PACKET_SIZE / 2);
+        let vm = self.debugger.vm();
+        let mut hex = String::new();
+        for index in 0..len {
+            let word = vm.peek_memory(address.wrapping_add((index / 2) as u16));
+            let byte = if index % 2 == 0 {
+                word >> 8
+            } else {
+                word & 0xff
+            };
+            let _ = write!(hex, "{byte:02x}");
+        }
+        Some(hex)
+    }
+
+    fn write_memory(&mut self, args: &str) -> Option<()> {
+        let (location, data) = args.split_once(':')?;
+        let (address, len) = parse_address_len(location)?;
+        let words = parse_words(data)?;
+        if len != words.len() * 2 {
+            return None;
+        }
+        // Like reads, writes past xFFFF wrap around to x0000
+        for (index, word) in words.into_iter().enumerate() {
+            self.debugger
+                .poke_memory(address.wrapping_add(index as u16), word);
+        }
+        Some(())
+    }
+
+    /// Adds or removes a software breakpoint (type 0), or a write (2), read (3) or
+    /// access (4) watchpoint. The length of a watchpoint is in bytes. Hardware
+    /// breakpoints are not supported
+    fn breakpoint(&mut self, insert: bool, args: &str) -> String {
+        let fields = args.split(',').collect::<Vec<&str>>();
+        let [kind, address, len] = fields[..] else {
+            return error();
+        };
+        let kind = match kind {
+            "0" => None,
+            "2" => Some(WatchKind::Write),
+            "3" => Some(WatchKind::Read),
+            "4" => Some(WatchKind::Access),
+            _ => return String::new(),
+        };
+        let (Ok(address), Ok(len)) = (
+            u16::from_str_radix(address, 16),
+            u16::from_str_radix(len, 16),
+        ) else {
+            return error();
+        };
+        let Some(kind) = kind else {
+            if insert {
+                self.debugger.add_breakpoint(address);
+            } else {
+                self.debugger.remove_breakpoint(address);
+            }
+            return "OK".to_string();
+        };
+        let words = len.div_ceil(2).max(1);
+        let watchpoint = Watchpoint {
+            range: address..=address.saturating_add(words - 1),
+            kind,
+        };
+        if insert {
+            self.debugger.add_watchpoint(watchpoint);
+        } else {
+            self.debugger.remove_watchpoint(&watchpoint);
+        }
+        "OK".to_string()
+    }
+
+    /// Sets the PC to the address that `s` and `c` packets can resume from
+    fn set_resume_address(&mut self, args: &str) -> Result<(), String> {
+        if args.is_empty() {
+            return Ok(());
+        }
+        let address = u16::from_str_radix(args, 16).map_err(|_| error())?;
+        let registers = self.debugger.vm_mut().registers_mut();
+        registers.set_program_counter(address);
+        Ok(())
+    }
+
+    /// Returns the stop reply for the result of resuming the program. Errors are shown
+    /// to the user with an `O` packet before the reply
+    fn stop_reply<W: Write>(
+        &mut self,
+        result: Result<StopReason, VmError>,
+        writer: &mut W,
+    ) -> io::Result<String> {
+        let reply = match result {
+            Ok(StopReason::Done | StopReason::Breakpoint(_)) => format!("S{SIGTRAP:02x}"),
+            Ok(StopReason::Watchpoint(hit)) => {
+                let kind = match hit.access {
+                    WatchKind::Write => "watch",
+                    _ => "rwatch",
+                };
+                format!("T{SIGTRAP:02x}{kind}:{:04x};", hit.address)
+            }
+            Ok(StopReason::Interrupted) => format!("S{SIGINT:02x}"),
+            Ok(StopReason::HistoryStart) => format!("T{SIGTRAP:02x}replaylog:begin;"),
+            Ok(StopReason::Halted) => "W00".to_string(),
+            Err(e) => {
+                let message = format!("{}\n", e.with_symbols(self.debugger.symbols()));
+                let hex = message.bytes().fold(String::from("O"), |mut hex, byte| {
+                    let _ = write!(hex, "{byte:02x}");
+                    hex
+                });
+                write_packet(writer, &hex)?;
+                format!("S{:02x}", signal(&e))
+            }
+        };
+        self.last_stop = reply.clone();
+        Ok(reply)
+    }
+}
+
+/// Reads the bytes sent by the client on a new thread. Interrupts set `interrupted`
+/// as soon as they arrive, so that a running program stops
+fn spawn_reader<R>(mut reader: R, interrupted: Arc<AtomicBool>) -> mpsc::Receiver<u8>
+where
+    R: Read + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buffer = [0; 512];
+        while let Ok(len @ 1..) = reader.read(&mut buffer) {
+            for &byte in &buffer[..len] {
+                if byte == INTERRUPT {
+                    interrupted.store(true, Ordering::Relaxed);
+                }
+                if sender.send(byte).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    receiver
+}
+
+fn write_packet<W: Write>(writer: &mut W, data: &str) -> io::Result<()> {
+    let checksum = checksum_of(data.as_bytes());
+    write!(writer, "${data}#{checksum:02x}")?;
+    writer.flush()
+}
+
+/// Returns the sum of the bytes modulo 256, which is the checksum of a packet
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn register_name(id: usize) -> RegisterName {
+    match id {
+        8 => RegisterName::PC,
+        9 => RegisterName::Psr,
+        _ => RegisterName::general(id as u16),
+    }
+}
+
+/// Parses the `ADDRESS,LENGTH` arguments of memory packets
+fn parse_address_len(args: &str) -> Option<(u16, usize)> {
+    let (address, len) = args.split_once(',')?;
+    let address = u16::from_str_radix(address, 16).ok()?;
+    let len = usize::from_str_radix(len, 16).ok()?;
+    Some((address, len))
+}
+
+/// Parses hex digits into words of 4 digits each
+fn parse_words(hex: &str) -> Option<Vec<u16>> {
+    if !hex.len().is_multiple_of(4) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(4)
+        .map(|start| u16::from_str_radix(&hex[start..start + 4], 16).ok())
+        .collect()
+}
+
+/// Returns the signal reported to the client when an error stopped the program
+fn signal(error: &VmError) -> u8 {
+    match error {
+        VmError::IllegalOpcode { .. }
+        | VmError::PrivilegeViolation { .. }
+        | VmError::BadTrapVector { .. } => SIGILL,
+        VmError::AccessViolation { .. } => SIGSEGV,
+        _ => SIGABRT,
+    }
+}
+
+fn ok_or_error(result: Option<()>) -> String {
+    match result {
+        Some(()) => "OK".to_string(),
+        None => error(),
+    }
+}
+
+/// Returns the error reply, which is used for all invalid packets
+fn error() -> String {
+    "E01".to_string()
+}
@@ -0,0 +1,236 @@
+use std::io::Cursor;
+
+use super::*;
+use crate::{
+    asm::assemble,
+    vm::{
+        console::{IoConsole, OutputBuffer},
+        Lc3Vm,
+    },
+};
+
+const PROGRAM: &str = "
+        .ORIG x3000
+        AND R0, R0, #0
+        ADD R0, R0, #5
+LOOP    ADD R0, R0, #-1
+        BRp LOOP
+        HALT
+        .END
+";
+
+fn stub(source: &str) -> GdbStub {
+    let assembly = assemble(source).unwrap();
+    let mut vm = Lc3Vm::with_console(IoConsole::new("".as_bytes(), OutputBuffer::new()));
+    for (address, word) in (assembly.origin..).zip(&assembly.words) {
+        vm.memory_mut().write(address, *word);
+    }
+    GdbStub::new(Debugger::new(vm))
+}
+
+fn packet(data: &str) -> String {
+    format!("${data}#{:02x}", checksum_of(data.as_bytes()))
+}
+
+/// Sends the packets to the stub, and returns the packets it sent back
+fn exchange(stub: &mut GdbStub, packets: &[&str]) -> Vec<String> {
+    let input = packets.iter().map(|data| packet(data)).collect::<String>();
+    let mut output = Vec::new();
+    stub.serve(Cursor::new(input.into_bytes()), &mut output)
+        .unwrap();
+    let output = String::from_utf8(output).unwrap();
+    output
+        .split('$')
+        .skip(1)
+        .map(|packet| {
+            let (data, checksum) = packet.split_once('#').unwrap();
+            assert_eq!(
+                &checksum[..2],
+                format!("{:02x}", checksum_of(data.as_bytes()))
+            );
+            data.to_string()
+        })
+        .collect()
+}
+
+#[test]
+fn test_registers() {
+    let mut stub = stub(PROGRAM);
+    let replies = exchange(&mut stub, &["s", "s", "g", "p8", "pa"]);
+    assert_eq!(
+        replies,
+        vec![
+            "S05",
+            "S05",
+            "0005000000000000000000000000000030028001",
+            "3002",
+            "E01",
+        ]
+    );
+
+    let registers = "0001000200030004000500060007000830048004";
+    let replies = exchange(
+        &mut stub,
+        &[&format!("G{registers}"), "P1=abcd", "p1", "G00"],
+    );
+    assert_eq!(replies, vec!["OK", "OK", "abcd", "E01"]);
+    assert_eq!(stub.debugger().vm().registers().program_counter(), 0x3004);
+    assert_eq!(stub.debugger().vm().registers().psr(), 0x8004);
+}
+
+#[test]
+fn test_memory() {
+    let mut stub = stub(PROGRAM);
+    // Addresses are word addresses, and lengths are counted in bytes
+    let replies = exchange(
+        &mut stub,
+        &[
+            "m3003,3",
+            "M4000,4:beef1234",
+            "m3fff,6",
+            "M4000,4:beef",
+            "m4000",
+        ],
+    );
+    assert_eq!(replies, vec!["03fef0", "OK", "0000beef1234", "E01", "E01"]);
+    assert_eq!(stub.debugger().vm().peek_memory(0x4001), 0x1234);
+
+    // Writes at the top of memory wrap around
+    let replies = exchange(&mut stub, &["Mffff,2:8001", "Mffff,4:80025678"]);
+    assert_eq!(replies, vec!["OK", "OK"]);
+    assert_eq!(stub.debugger().vm().peek_memory(0xffff), 0x8002);
+    assert_eq!(stub.debugger().vm().peek_memory(0x0000), 0x5678);
+
+    // The device registers are set without side effects, which forgets the history
+    let replies = exchange(&mut stub, &["s", "Mfe00,2:8000"]);
+    assert_eq!(replies, vec!["S05", "OK"]);
+    assert_eq!(stub.debugger().vm().peek_memory(0xfe00), 0x8000);
+    assert_eq!(stub.debugger().history_len(), 0);
+}
+
+#[test]
+fn test_breakpoints() {
+    let mut stub = stub(PROGRAM);
+    let replies = exchange(
+        &mut stub,
+        &["Z0,3003,2", "c", "p0", "c", "p0", "z0,3003,2", "c", "?"],
+    );
+    assert_eq!(
+        replies,
+        vec!["OK", "S05", "0004", "S05", "0003", "OK", "W00", "W00"]
+    );
+    // Hardware breakpoints are not supported
+    assert_eq!(exchange(&mut stub, &["Z1,3003,2"]), vec![""]);
+}
+
+#[test]
+fn test_resume_address() {
+    let mut stub = stub(PROGRAM);
+    let replies = exchange(&mut stub, &["s3001", "p0", "p8"]);
+    assert_eq!(replies, vec!["S05", "0005", "3002"]);
+}
+
+#[test]
+fn test_error_stop_reply() {
+    let mut stub = stub(".ORIG x3000\nAND R0, R0, #0\n.FILL xD000\n.END");
+    let replies = exchange(&mut stub, &["c"]);
+    let message = "illegal opcode in instruction xD000 at x3001\n";
+    let hex = message
+        .bytes()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    assert_eq!(replies, vec![format!("O{hex}"), "S04".to_string()]);
+}
+
+#[test]
+fn test_acknowledgements() {
+    let mut stub = stub(PROGRAM);
+    let input = format!("+$g#00{}{}$g#00", packet("QStartNoAckMode"), packet("D"));
+    let mut output = Vec::new();
+    stub.serve(Cursor::new(input.into_bytes()), &mut output)
+        .unwrap();
+    // The packet with a bad checksum is rejected, and no packets are acknowledged
+    // after `QStartNoAckMode`. The packet after `D` is not read
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        format!("-+{}{}", packet("OK"), packet("OK"))
+    );
+}
+
+#[test]
+fn test_watchpoints() {
+    let source = "
+        .ORIG x3000
+        LD R0, DATA
+        ADD R0, R0, #1
+        ST R0, DATA
+        HALT
+DATA    .FILL #7
+        .END
+    ";
+    let mut stub = stub(source);
+    let replies = exchange(
+        &mut stub,
+        &[
+            "Z2,3004,2",
+            "Z3,3004,1",
+            "c",
+            "c",
+            "z3,3004,1",
+            "c",
+            "z2,3004,2",
+            "c",
+        ],
+    );
+    assert_eq!(
+        replies,
+        vec![
+            "OK",
+            "OK",
+            "T05rwatch:3004;",
+            "T05watch:3004;",
+            "OK",
+            "W00",
+            "OK",
+            "W00"
+        ]
+    );
+}
+
+#[test]
+fn test_reverse_execution() {
+    let mut stub = stub(PROGRAM);
+    let replies = exchange(
+        &mut stub,
+        &[
+            "qSupported:swbreak+",
+            "c",
+            "bs",
+            "p8",
+            "Z0,3002,1",
+            "bc",
+            "p0",
+            "z0,3002,1",
+            "bc",
+            "p8",
+            "bs",
+        ],
+    );
+    assert_eq!(
+        replies[1..],
+        [
+            "W00",
+            // The last instruction was in the HALT service routine
+            "S05",
+            "0405",
+            "OK",
+            "S05",
+            "0001",
+            "OK",
+            "T05replaylog:begin;",
+            "3000",
+            "T05replaylog:begin;",
+        ]
+    );
+    assert!(replies[0].contains("ReverseStep+;ReverseContinue+"));
+}
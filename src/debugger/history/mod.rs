@@ -0,0 +1,141 @@
+//! This module contains the undo log of the debugger, which records what every
+//! executed instruction changed so that execution can be reversed
+
+#[cfg(test)]
+mod tests;
+
+use std::collections::VecDeque;
+
+use crate::vm::{
+    memory::DeviceRegister,
+    registers::Registers,
+    step::{MemoryWrite, Step},
+    Lc3Vm,
+};
+
+/// The number of instructions that are recorded by default. Older instructions are
+/// forgotten
+pub const DEFAULT_HISTORY_LIMIT: usize = 100_000;
+/// The most memory locations that a step writes to, apart from DDR. An interrupt
+/// pushes the PSR and PC, and the first instruction of its service routine can push
+/// them again with `TRAP` or an exception
+const MAX_MEMORY_WRITES: usize = 4;
+
+/// What an executed instruction changed, and the state it changed
+#[derive(Clone, Debug)]
+pub struct UndoEntry {
+    /// The address of the instruction
+    pub pc: u16,
+    pub instr: u16,
+    /// The registers before the instruction was executed
+    registers: Registers,
+    /// The machine control register before the instruction was executed, as `HALT`
+    /// clears its clock enable bit
+    mcr: u16,
+    /// The memory writes of the instruction, of which the first `write_count` are
+    /// used. They are kept inline, as most instructions write nothing
+    memory_written: [MemoryWrite; MAX_MEMORY_WRITES],
+    write_count: u8,
+}
+
+impl UndoEntry {
+    /// Returns the memory writes of the instruction. Writes to DDR are left out, as
+    /// printed characters cannot be taken back
+    pub fn memory_written(&self) -> &[MemoryWrite] {
+        &self.memory_written[..usize::from(self.write_count)]
+    }
+
+    /// Restores the registers and memory to how they were before the instruction was
+    /// executed. Characters written to the display cannot be taken back, and neither
+    /// can characters read from the keyboard
+    fn undo(&self, vm: &mut Lc3Vm) {
+        let memory = vm.memory_mut();
+        for write in self.memory_written().iter().rev() {
+            memory.write(write.address, write.old_value);
+        }
+        let mcr_address = DeviceRegister::Mcr.address();
+        if memory.peek(mcr_address) != self.mcr {
+            memory.write(mcr_address, self.mcr);
+        }
+        *vm.registers_mut() = self.registers;
+    }
+}
+
+/// The undo log of the executed instructions, from the oldest to the newest
+pub struct History {
+    entries: VecDeque<UndoEntry>,
+    /// The number of instructions that are recorded
+    limit: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            entries: VecDeque::new(),
+            limit: DEFAULT_HISTORY_LIMIT,
+        }
+    }
+}
+
+impl History {
+    /// Returns the state that `record` needs to be given after the next instruction
+    /// has been executed
+    pub fn before_step(vm: &Lc3Vm) -> (Registers, u16) {
+        let mcr = vm.memory().peek(DeviceRegister::Mcr.address());
+        (*vm.registers(), mcr)
+    }
+
+    /// Records an executed instruction, along with the state returned by
+    /// `before_step` before it was executed
+    pub fn record(&mut self, (registers, mcr): (Registers, u16), step: &Step) {
+        if self.limit == 0 {
+            return;
+        }
+        if self.entries.len() == self.limit {
+            self.entries.pop_front();
+        }
+        let ddr_address = DeviceRegister::Ddr.address();
+        let mut memory_written = [MemoryWrite::default(); MAX_MEMORY_WRITES];
+        let mut write_count = 0;
+        for write in &step.memory_written {
+            if write.address != ddr_address {
+                memory_written[usize::from(write_count)] = *write;
+                write_count += 1;
+            }
+        }
+        self.entries.push_back(UndoEntry {
+            pc: step.pc,
+            instr: step.instr,
+            registers,
+            mcr,
+            memory_written,
+            write_count,
+        });
+    }
+
+    /// Undoes the most recently executed instruction, and returns its entry. `None`
+    /// is returned if there is no recorded instruction left
+    pub fn undo(&mut self, vm: &mut Lc3Vm) -> Option<UndoEntry> {
+        let entry = self.entries.pop_back()?;
+        entry.undo(vm);
+        Some(entry)
+    }
+
+    /// Returns the number of recorded instructions
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Sets the number of instructions that are recorded, forgetting the oldest ones
+    /// if there are more
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        let excess = self.entries.len().saturating_sub(limit);
+        self.entries.drain(..excess);
+    }
+
+    /// Forgets every recorded instruction
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
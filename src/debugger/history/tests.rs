@@ -0,0 +1,95 @@
+use super::*;
+use crate::vm::{
+    console::{IoConsole, OutputBuffer},
+    registers::RegisterName,
+    trap_vecs::TrapVector,
+};
+
+#[test]
+#[allow(clippy::unusual_byte_groupings)]
+fn test_undo() {
+    let output = OutputBuffer::new();
+    let mut vm = Lc3Vm::with_console(IoConsole::new("".as_bytes(), output.clone()));
+    vm.set_ignore_privilege(true);
+    let program = [
+        // ST R1, #2
+        0b0011_001_000000010,
+        // STI R1, #2
+        0b1011_001_000000010,
+        // HALT
+        0xf025,
+        0x0000,
+        0xfe06,
+    ];
+    for (address, word) in (Lc3Vm::DEFAULT_PC_START..).zip(program) {
+        vm.memory_mut().write(address, word);
+    }
+    vm.registers_mut().set_reg_value(RegisterName::R1, 0x41);
+    let start = *vm.registers();
+
+    let mut history = History::default();
+    while vm.running() {
+        let before = History::before_step(&vm);
+        let step = vm.step().unwrap();
+        history.record(before, &step);
+    }
+    assert_eq!(history.len(), 4);
+    assert_eq!(vm.memory().peek(0x3003), 0x41);
+
+    // Undoing the native HALT routine lets the machine run again
+    let entry = history.undo(&mut vm).unwrap();
+    assert_eq!(entry.pc, TrapVector::Halt.native_address());
+    assert!(entry.memory_written().is_empty());
+    assert!(vm.running());
+    while history.undo(&mut vm).is_some() {}
+    assert_eq!(*vm.registers(), start);
+    assert_eq!(vm.memory().peek(0x3003), 0);
+    // The character written to the display stays written
+    assert!(output.contents().starts_with(b"A"));
+}
+
+#[test]
+fn test_undo_interrupt_and_trap() {
+    let mut vm = Lc3Vm::with_console(IoConsole::new("a".as_bytes(), OutputBuffer::new()));
+    // The keyboard service routine starts with TRAP x30
+    vm.memory_mut().write(0x0180, 0x1000);
+    vm.memory_mut().write(0x1000, 0xf030);
+    vm.memory_mut().write(0x0030, 0x1010);
+    vm.memory_mut().write(0xfe00, 0x4000);
+    vm.registers_mut().set_reg_value(RegisterName::R6, 0xfd00);
+    let start = *vm.registers();
+
+    // Both push the PSR and PC onto the supervisor stack
+    let mut history = History::default();
+    let before = History::before_step(&vm);
+    let step = vm.step().unwrap();
+    history.record(before, &step);
+    assert_eq!(step.memory_written.len(), 4);
+
+    let entry = history.undo(&mut vm).unwrap();
+    assert_eq!(entry.memory_written(), &step.memory_written[..]);
+    assert_eq!(*vm.registers(), start);
+    for address in 0x2ffc..0x3000 {
+        assert_eq!(vm.memory().peek(address), 0);
+    }
+}
+
+#[test]
+fn test_limit() {
+    let mut vm = Lc3Vm::with_console(IoConsole::new("".as_bytes(), OutputBuffer::new()));
+    let mut history = History::default();
+    history.set_limit(2);
+    for _ in 0..3 {
+        let before = History::before_step(&vm);
+        let step = vm.step().unwrap();
+        history.record(before, &step);
+    }
+    assert_eq!(history.len(), 2);
+    history.set_limit(1);
+    assert_eq!(history.len(), 1);
+    history.set_limit(0);
+    let before = History::before_step(&vm);
+    let step = vm.step().unwrap();
+    history.record(before, &step);
+    assert_eq!(history.len(), 0);
+}
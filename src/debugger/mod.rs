@@ -0,0 +1,477 @@
+//! This module contains the debugger, which controls the execution of an `Lc3Vm` with
+//! breakpoints and stepping commands. It is used by the `lc3 debug` command, and can
+//! be used to build other debugger front ends
+
+pub mod dap;
+pub mod gdb;
+mod history;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    collections::BTreeSet,
+    ops::RangeInclusive,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+pub use self::history::DEFAULT_HISTORY_LIMIT;
+
+use self::history::{History, UndoEntry};
+use crate::{
+    disasm::disassemble_instr,
+    symbols::SymbolTable,
+    vm::{
+        error::VmError,
+        memory::DeviceRegister,
+        ops::OpCode,
+        step::{MemoryRead, MemoryWrite, Step},
+        Lc3Vm,
+    },
+};
+
+/// The reason the debugger stopped executing instructions
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StopReason {
+    /// The requested instructions were executed
+    Done,
+    /// The program counter reached the breakpoint at this address
+    Breakpoint(u16),
+    /// An instruction accessed memory watched by a watchpoint
+    Watchpoint(WatchHit),
+    /// The machine was halted
+    Halted,
+    /// Execution was interrupted through `Debugger::interrupt_flag`
+    Interrupted,
+    /// Executing backwards reached the oldest recorded instruction
+    HistoryStart,
+}
+
+/// The kinds of memory accesses that a watchpoint stops at
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WatchKind {
+    Read,
+    Write,
+    /// Both reads and writes
+    Access,
+}
+
+impl WatchKind {
+    fn matches(self, access: WatchKind) -> bool {
+        self == WatchKind::Access || self == access
+    }
+}
+
+/// Stops execution when an instruction accesses memory in `range`. The range can
+/// include the device registers
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Watchpoint {
+    pub range: RangeInclusive<u16>,
+    pub kind: WatchKind,
+}
+
+/// A memory access that triggered a watchpoint
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct WatchHit {
+    /// The address of the instruction that accessed memory
+    pub pc: u16,
+    pub instr: u16,
+    /// The address that was accessed
+    pub address: u16,
+    /// Either `WatchKind::Read` or `WatchKind::Write`
+    pub access: WatchKind,
+    /// The value before the access. Reads do not change the value, except for some
+    /// device registers
+    pub old_value: u16,
+    pub new_value: u16,
+}
+
+impl WatchHit {
+    /// Describes the access, like `STR R0, R1, #0 at x3005 (LOOP+2) wrote xFE06 (DDR):
+    /// x0000 -> x0041`
+    pub fn describe(&self, symbols: &SymbolTable) -> String {
+        let instr = disassemble_instr(self.instr, self.pc, Some(symbols));
+        let address = match DeviceRegister::from_address(self.address) {
+            Some(register) => format!("x{:04X} ({register})", self.address),
+            None => symbols.describe(self.address),
+        };
+        let pc = symbols.describe(self.pc);
+        match self.access {
+            WatchKind::Write => format!(
+                "{instr} at {pc} wrote {address}: x{:04X} -> x{:04X}",
+                self.old_value, self.new_value
+            ),
+            _ => format!("{instr} at {pc} read {address}: x{:04X}", self.new_value),
+        }
+    }
+}
+
+/// Controls the execution of a VM for debugging. The instructions executed by the
+/// debugger are recorded, so that they can be undone to execute backwards
+pub struct Debugger {
+    vm: Lc3Vm,
+    breakpoints: BTreeSet<u16>,
+    watchpoints: Vec<Watchpoint>,
+    history: History,
+    interrupted: Arc<AtomicBool>,
+}
+
+impl Debugger {
+    pub fn new(vm: Lc3Vm) -> Self {
+        Self {
+            vm,
+            breakpoints: BTreeSet::new(),
+            watchpoints: Vec::new(),
+            history: History::default(),
+            interrupted: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn vm(&self) -> &Lc3Vm {
+        &self.vm
+    }
+
+    pub fn vm_mut(&mut self) -> &mut Lc3Vm {
+        &mut self.vm
+    }
+
+    /// Returns the labels of the program being debugged, which are the VM's symbols
+    pub fn symbols(&self) -> &SymbolTable {
+        self.vm.symbols()
+    }
+
+    pub fn set_symbols(&mut self, symbols: SymbolTable) {
+        self.vm.set_symbols(symbols);
+    }
+
+    /// Returns a flag that stops execution when it is set, such as from a Ctrl-C
+    /// handler. The flag is cleared when execution stops
+    pub fn interrupt_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.interrupted)
+    }
+
+    /// Replaces the interrupt flag, so that a flag created before the debugger can
+    /// stop execution
+    pub fn set_interrupt_flag(&mut self, flag: Arc<AtomicBool>) {
+        self.interrupted = flag;
+    }
+
+    /// Adds a breakpoint at `address`. Returns `false` if there already was one
+    pub fn add_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.insert(address)
+    }
+
+    /// Removes the breakpoint at `address`. Returns `false` if there was none
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    /// Returns the addresses of the breakpoints in ascending order
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Adds a watchpoint. Returns `false` if there already was the same one
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> bool {
+        if self.watchpoints.contains(&watchpoint) {
+            return false;
+        }
+        self.watchpoints.push(watchpoint);
+        true
+    }
+
+    /// Removes a watchpoint. Returns `false` if there was none
+    pub fn remove_watchpoint(&mut self, watchpoint: &Watchpoint) -> bool {
+        let count = self.watchpoints.len();
+        self.watchpoints.retain(|existing| existing != watchpoint);
+        self.watchpoints.len() != count
+    }
+
+    /// Returns the watchpoints in the order they were added
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// Resolves a location given as a label, a label with an offset like `LOOP+2`, or
+    /// a number to an address. See `parse_number` for the formats of numbers
+    pub fn resolve_location(&self, location: &str) -> Option<u16> {
+        self.symbols()
+            .resolve(location)
+            .or_else(|| parse_number(location))
+    }
+
+    /// Executes up to `count` instructions. Breakpoints do not stop stepping
+    pub fn step(&mut self, count: usize) -> Result<StopReason, VmError> {
+        for _ in 0..count {
+            if !self.vm.running() {
+                return Ok(StopReason::Halted);
+            }
+            let step = self.execute()?;
+            if let Some(hit) = watch_hit(&self.watchpoints, &step) {
+                return Ok(StopReason::Watchpoint(hit));
+            }
+        }
+        Ok(self.stop_reason(StopReason::Done))
+    }
+
+    /// Executes one instruction. Subroutine calls and trap routines are executed until
+    /// they return
+    pub fn step_over(&mut self) -> Result<StopReason, VmError> {
+        if !self.vm.running() {
+            return Ok(StopReason::Halted);
+        }
+        let step = self.execute()?;
+        if let Some(hit) = watch_hit(&self.watchpoints, &step) {
+            return Ok(StopReason::Watchpoint(hit));
+        }
+        let entered = routines_entered(&step);
+        if entered > 0 {
+            return self.run_until_returned(entered);
+        }
+        Ok(self.stop_reason(StopReason::Done))
+    }
+
+    /// Executes instructions until the current subroutine or trap routine returns
+    pub fn finish(&mut self) -> Result<StopReason, VmError> {
+        self.run_until_returned(1)
+    }
+
+    /// Executes instructions until `count` of the routines that are currently being
+    /// executed have returned
+    fn run_until_returned(&mut self, count: usize) -> Result<StopReason, VmError> {
+        let mut depth = count;
+        self.resume(|step| {
+            depth += routines_entered(step);
+            if is_return(step) {
+                depth -= 1;
+            }
+            depth == 0
+        })
+    }
+
+    /// Executes instructions until a breakpoint is reached or the machine halts
+    pub fn continue_execution(&mut self) -> Result<StopReason, VmError> {
+        self.resume(|_| false)
+    }
+
+    /// Executes instructions until `done` returns `true` for an executed instruction,
+    /// a breakpoint is reached, a watchpoint is triggered, or the machine halts. A
+    /// breakpoint at the current program counter does not stop execution, so that
+    /// execution can be resumed from it
+    fn resume(&mut self, mut done: impl FnMut(&Step) -> bool) -> Result<StopReason, VmError> {
+        let result = loop {
+            if !self.vm.running() {
+                break Ok(StopReason::Halted);
+            }
+            let step = match self.execute() {
+                Ok(step) => step,
+                Err(e) => break Err(e),
+            };
+            let pc = self.vm.registers().program_counter();
+            if step.halted {
+                break Ok(StopReason::Halted);
+            } else if let Some(hit) = watch_hit(&self.watchpoints, &step) {
+                break Ok(StopReason::Watchpoint(hit));
+            } else if done(&step) {
+                break Ok(StopReason::Done);
+            } else if self.breakpoints.contains(&pc) {
+                break Ok(StopReason::Breakpoint(pc));
+            } else if self.interrupted.load(Ordering::Relaxed) {
+                break Ok(StopReason::Interrupted);
+            }
+        };
+        self.interrupted.store(false, Ordering::Relaxed);
+        result
+    }
+
+    /// Executes one instruction, and records it so that it can be undone
+    fn execute(&mut self) -> Result<Step, VmError> {
+        let before = History::before_step(&self.vm);
+        let step = self.vm.step()?;
+        self.history.record(before, &step);
+        Ok(step)
+    }
+
+    /// Returns the number of executed instructions that can be undone
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Sets the number of executed instructions that are recorded so that they can be
+    /// undone, which is `DEFAULT_HISTORY_LIMIT` by default. A limit of 0 turns
+    /// recording off
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history.set_limit(limit);
+    }
+
+    /// Sets a memory location without the side effects of writing the device
+    /// registers. The edit cannot be undone, so the history is cleared, as undoing
+    /// the instructions before it would mix memory from before and after the edit
+    pub fn poke_memory(&mut self, address: u16, value: u16) {
+        self.vm.poke_memory(address, value);
+        self.history.clear();
+    }
+
+    /// Undoes up to `count` of the most recently executed instructions. Breakpoints do
+    /// not stop stepping backwards
+    pub fn step_back(&mut self, count: usize) -> StopReason {
+        for _ in 0..count {
+            if self.history.undo(&mut self.vm).is_none() {
+                return StopReason::HistoryStart;
+            }
+        }
+        StopReason::Done
+    }
+
+    /// Executes backwards until a breakpoint is reached, an undone instruction
+    /// triggers a watchpoint, or the oldest recorded instruction is undone
+    pub fn reverse_continue(&mut self) -> StopReason {
+        self.reverse(|_| false)
+    }
+
+    /// Executes backwards until the most recent instruction that wrote to `address`
+    /// is undone, so that it is the next instruction to be executed
+    pub fn back_to_write(&mut self, address: u16) -> StopReason {
+        self.reverse(|entry| {
+            entry
+                .memory_written()
+                .iter()
+                .any(|write| write.address == address)
+        })
+    }
+
+    /// Undoes instructions until `done` returns `true` for an undone instruction, a
+    /// breakpoint is reached, a write watchpoint is triggered, or the history runs out.
+    /// Like when executing forwards, a breakpoint at the current program counter does
+    /// not stop execution. Reads are not recorded, so read watchpoints do not stop
+    /// execution
+    fn reverse(&mut self, mut done: impl FnMut(&UndoEntry) -> bool) -> StopReason {
+        let reason = loop {
+            let Some(entry) = self.history.undo(&mut self.vm) else {
+                break StopReason::HistoryStart;
+            };
+            let pc = self.vm.registers().program_counter();
+            let hit = find_watch_hit(
+                &self.watchpoints,
+                (entry.pc, entry.instr),
+                &[],
+                entry.memory_written(),
+            );
+            if let Some(hit) = hit {
+                break StopReason::Watchpoint(hit);
+            } else if done(&entry) {
+                break StopReason::Done;
+            } else if self.breakpoints.contains(&pc) {
+                break StopReason::Breakpoint(pc);
+            } else if self.interrupted.load(Ordering::Relaxed) {
+                break StopReason::Interrupted;
+            }
+        };
+        self.interrupted.store(false, Ordering::Relaxed);
+        reason
+    }
+
+    /// Returns the reason execution stopped after stepping
+    fn stop_reason(&self, reason: StopReason) -> StopReason {
+        if !self.vm.running() {
+            StopReason::Halted
+        } else {
+            reason
+        }
+    }
+}
+
+/// Returns the first memory access of a step that triggers one of the watchpoints
+fn watch_hit(watchpoints: &[Watchpoint], step: &Step) -> Option<WatchHit> {
+    find_watch_hit(
+        watchpoints,
+        (step.pc, step.instr),
+        &step.memory_read,
+        &step.memory_written,
+    )
+}
+
+/// Returns the first of the memory accesses made by the instruction `instr` at `pc`
+/// that triggers one of the watchpoints
+fn find_watch_hit(
+    watchpoints: &[Watchpoint],
+    (pc, instr): (u16, u16),
+    memory_read: &[MemoryRead],
+    memory_written: &[MemoryWrite],
+) -> Option<WatchHit> {
+    if watchpoints.is_empty() {
+        return None;
+    }
+    let reads = memory_read
+        .iter()
+        .map(|read| (WatchKind::Read, read.address, read.value, read.value));
+    let writes = memory_written.iter().map(|write| {
+        (
+            WatchKind::Write,
+            write.address,
+            write.old_value,
+            write.new_value,
+        )
+    });
+    reads
+        .chain(writes)
+        .find(|(access, address, _, _)| {
+            watchpoints.iter().any(|watchpoint| {
+                watchpoint.kind.matches(*access) && watchpoint.range.contains(address)
+            })
+        })
+        .map(|(access, address, old_value, new_value)| WatchHit {
+            pc,
+            instr,
+            address,
+            access,
+            old_value,
+            new_value,
+        })
+}
+
+/// Returns the number of routines that were entered by a step. An interrupt service
+/// routine is entered when an interrupt is taken, and the executed instruction can
+/// call a subroutine or trap routine, or raise an exception
+fn routines_entered(step: &Step) -> usize {
+    let is_call = matches!(
+        OpCode::try_from(step.instr >> 12),
+        Ok(OpCode::Jsr | OpCode::Trap)
+    );
+    usize::from(step.interrupt.is_some())
+        + usize::from(step.exception.is_some())
+        + usize::from(is_call)
+}
+
+/// Returns `true` if the executed instruction returned from a subroutine or trap
+/// routine
+fn is_return(step: &Step) -> bool {
+    match OpCode::try_from(step.instr >> 12) {
+        Ok(OpCode::Jmp) => (step.instr >> 6) & 0b111 == 7,
+        Ok(OpCode::Rti) => true,
+        _ => false,
+    }
+}
+
+/// Parses a number written in hexadecimal with an `x` or `0x` prefix, or in decimal
+/// with an optional `#` prefix. Negative decimal numbers are stored in two's
+/// complement
+pub fn parse_number(number: &str) -> Option<u16> {
+    let hex_digits = number
+        .strip_prefix("0x")
+        .or_else(|| number.strip_prefix(['x', 'X']));
+    if let Some(digits) = hex_digits {
+        return u16::from_str_radix(digits, 16).ok();
+    }
+    let value = number
+        .strip_prefix('#')
+        .unwrap_or(number)
+        .parse::<i32>()
+        .ok()?;
+    match value {
+        -0x8000..=-1 => Some(value as u16),
+        _ => u16::try_from(value).ok(),
+    }
+}
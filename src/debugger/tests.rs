@@ -0,0 +1,283 @@
+use super::*;
+use crate::{
+    asm::assemble,
+    vm::console::{IoConsole, OutputBuffer},
+};
+
+const PROGRAM: &str = "
+        .ORIG x3000
+        AND R0, R0, #0
+        JSR DOUBLE
+        JSR DOUBLE
+        ADD R0, R0, #1
+        HALT
+DOUBLE  ADD R1, R0, #0
+        ADD R2, R7, #0
+        JSR ADD_R1
+        ADD R7, R2, #0
+        RET
+ADD_R1  ADD R0, R0, R1
+        ADD R0, R0, #1
+        RET
+        .END
+";
+
+fn debugger(source: &str) -> Debugger {
+    let assembly = assemble(source).unwrap();
+    let mut vm = Lc3Vm::with_console(IoConsole::new("".as_bytes(), OutputBuffer::new()));
+    for (address, word) in (assembly.origin..).zip(&assembly.words) {
+        vm.memory_mut().write(address, *word);
+    }
+    let mut debugger = Debugger::new(vm);
+    debugger.set_symbols(assembly.symbols);
+    debugger
+}
+
+fn pc(debugger: &Debugger) -> u16 {
+    debugger.vm().registers().program_counter()
+}
+
+#[test]
+fn test_step() {
+    let mut debugger = debugger(PROGRAM);
+    assert_eq!(debugger.step(2).unwrap(), StopReason::Done);
+    // Stepping goes into subroutines
+    assert_eq!(pc(&debugger), 0x3005);
+    assert_eq!(debugger.step(100).unwrap(), StopReason::Halted);
+    assert_eq!(debugger.step(1).unwrap(), StopReason::Halted);
+}
+
+#[test]
+fn test_step_over() {
+    let mut debugger = debugger(PROGRAM);
+    debugger.step(1).unwrap();
+    assert_eq!(debugger.step_over().unwrap(), StopReason::Done);
+    assert_eq!(pc(&debugger), 0x3002);
+    assert_eq!(debugger.vm().get_reg_val_by_id(0), 1);
+    assert_eq!(debugger.step_over().unwrap(), StopReason::Done);
+    assert_eq!(debugger.vm().get_reg_val_by_id(0), 3);
+    debugger.step_over().unwrap();
+    assert_eq!(debugger.step_over().unwrap(), StopReason::Halted);
+}
+
+#[test]
+fn test_finish() {
+    let mut debugger = debugger(PROGRAM);
+    // Step into DOUBLE, then into ADD_R1
+    debugger.step(5).unwrap();
+    assert_eq!(pc(&debugger), 0x300a);
+    assert_eq!(debugger.finish().unwrap(), StopReason::Done);
+    assert_eq!(pc(&debugger), 0x3008);
+    assert_eq!(debugger.finish().unwrap(), StopReason::Done);
+    assert_eq!(pc(&debugger), 0x3002);
+}
+
+#[test]
+fn test_breakpoints() {
+    let mut debugger = debugger(PROGRAM);
+    let add_r1 = debugger.resolve_location("ADD_R1").unwrap();
+    assert!(debugger.add_breakpoint(add_r1));
+    assert!(!debugger.add_breakpoint(add_r1));
+    assert_eq!(
+        debugger.continue_execution().unwrap(),
+        StopReason::Breakpoint(add_r1)
+    );
+    // Resuming from a breakpoint does not stop at it again straight away
+    assert_eq!(
+        debugger.continue_execution().unwrap(),
+        StopReason::Breakpoint(add_r1)
+    );
+    assert_eq!(debugger.vm().get_reg_val_by_id(0), 1);
+
+    // Breakpoints stop `next` inside of the called subroutine
+    debugger.finish().unwrap();
+    debugger.finish().unwrap();
+    assert_eq!(debugger.step_over().unwrap(), StopReason::Done);
+
+    assert!(debugger.remove_breakpoint(add_r1));
+    assert_eq!(debugger.breakpoints().count(), 0);
+    assert_eq!(debugger.continue_execution().unwrap(), StopReason::Halted);
+    assert_eq!(debugger.vm().get_reg_val_by_id(0), 4);
+}
+
+#[test]
+fn test_interrupt() {
+    let mut debugger = debugger(".ORIG x3000\nLOOP BR LOOP\n.END");
+    debugger.interrupt_flag().store(true, Ordering::Relaxed);
+    assert_eq!(
+        debugger.continue_execution().unwrap(),
+        StopReason::Interrupted
+    );
+    assert!(!debugger.interrupt_flag().load(Ordering::Relaxed));
+}
+
+#[test]
+fn test_parse_number() {
+    assert_eq!(parse_number("x3000"), Some(0x3000));
+    assert_eq!(parse_number("0xFFFF"), Some(0xffff));
+    assert_eq!(parse_number("#12"), Some(12));
+    assert_eq!(parse_number("12"), Some(12));
+    assert_eq!(parse_number("#-1"), Some(0xffff));
+    assert_eq!(parse_number("65536"), None);
+    assert_eq!(parse_number("LOOP"), None);
+}
+
+#[test]
+fn test_resolve_location() {
+    let debugger = debugger(PROGRAM);
+    assert_eq!(debugger.resolve_location("DOUBLE"), Some(0x3005));
+    assert_eq!(debugger.resolve_location("DOUBLE+2"), Some(0x3007));
+    assert_eq!(debugger.resolve_location("DOUBLE-1"), Some(0x3004));
+    assert_eq!(debugger.resolve_location("x3001"), Some(0x3001));
+    assert_eq!(debugger.resolve_location("#-1"), Some(0xffff));
+    assert_eq!(debugger.resolve_location("MISSING"), None);
+}
+
+const TABLE_PROGRAM: &str = "
+        .ORIG x3000
+        LEA R1, TABLE
+        LDR R0, R1, #1
+        ADD R0, R0, #1
+        STR R0, R1, #2
+        LD R2, CHAR
+        STI R2, DDR
+        HALT
+TABLE   .FILL #1
+        .FILL #2
+        .FILL #3
+CHAR    .FILL x41
+DDR     .FILL xFE06
+        .END
+";
+
+#[test]
+fn test_watchpoints() {
+    let mut debugger = debugger(TABLE_PROGRAM);
+    debugger.vm_mut().set_ignore_privilege(true);
+    let table = debugger.resolve_location("TABLE").unwrap();
+    let write = Watchpoint {
+        range: table..=table + 2,
+        kind: WatchKind::Write,
+    };
+    assert!(debugger.add_watchpoint(write.clone()));
+    assert!(!debugger.add_watchpoint(write.clone()));
+    debugger.add_watchpoint(Watchpoint {
+        range: table + 1..=table + 1,
+        kind: WatchKind::Read,
+    });
+    debugger.add_watchpoint(Watchpoint {
+        range: 0xfe06..=0xfe06,
+        kind: WatchKind::Access,
+    });
+
+    let hit = |pc, instr, address, access, old_value, new_value| {
+        StopReason::Watchpoint(WatchHit {
+            pc,
+            instr,
+            address,
+            access,
+            old_value,
+            new_value,
+        })
+    };
+    // Stepping stops at watchpoints too
+    let stop = debugger.step(5).unwrap();
+    assert_eq!(stop, hit(0x3001, 0x6041, 0x3008, WatchKind::Read, 2, 2));
+    let stop = debugger.continue_execution().unwrap();
+    assert_eq!(stop, hit(0x3003, 0x7042, 0x3009, WatchKind::Write, 3, 3));
+    let StopReason::Watchpoint(write_hit) = stop else {
+        unreachable!()
+    };
+    assert_eq!(
+        write_hit.describe(debugger.symbols()),
+        "STR R0, R1, #2 at x3003 wrote x3009 (TABLE+2): x0003 -> x0003"
+    );
+    // Watchpoints include the device registers
+    let stop = debugger.continue_execution().unwrap();
+    assert_eq!(stop, hit(0x3005, 0xb405, 0xfe06, WatchKind::Write, 0, 0x41));
+
+    assert!(debugger.remove_watchpoint(&write));
+    assert!(!debugger.remove_watchpoint(&write));
+    assert_eq!(debugger.watchpoints().len(), 2);
+    assert_eq!(debugger.continue_execution().unwrap(), StopReason::Halted);
+}
+
+#[test]
+fn test_step_back() {
+    let mut debugger = debugger(PROGRAM);
+    assert_eq!(debugger.step(3).unwrap(), StopReason::Done);
+    let registers = *debugger.vm().registers();
+    assert_eq!(debugger.continue_execution().unwrap(), StopReason::Halted);
+    let executed = debugger.history_len();
+
+    // Undoing HALT lets the program run again
+    assert_eq!(debugger.step_back(executed - 3), StopReason::Done);
+    assert_eq!(*debugger.vm().registers(), registers);
+    assert!(debugger.vm().running());
+    assert_eq!(debugger.step_back(5), StopReason::HistoryStart);
+    assert_eq!(pc(&debugger), 0x3000);
+    assert_eq!(debugger.history_len(), 0);
+
+    // Execution forwards again records new history
+    assert_eq!(debugger.continue_execution().unwrap(), StopReason::Halted);
+    assert_eq!(debugger.history_len(), executed);
+    assert_eq!(debugger.vm().get_reg_val_by_id(0), 4);
+}
+
+#[test]
+fn test_reverse_continue() {
+    let mut debugger = debugger(PROGRAM);
+    debugger.continue_execution().unwrap();
+    let double = debugger.resolve_location("DOUBLE").unwrap();
+    debugger.add_breakpoint(double);
+    // The second call of DOUBLE is reached first
+    assert_eq!(debugger.reverse_continue(), StopReason::Breakpoint(double));
+    assert_eq!(debugger.vm().get_reg_val_by_id(0), 1);
+    assert_eq!(debugger.reverse_continue(), StopReason::Breakpoint(double));
+    assert_eq!(debugger.vm().get_reg_val_by_id(0), 0);
+    assert_eq!(debugger.reverse_continue(), StopReason::HistoryStart);
+}
+
+#[test]
+fn test_back_to_write() {
+    let mut debugger = debugger(TABLE_PROGRAM);
+    debugger.vm_mut().set_ignore_privilege(true);
+    debugger.continue_execution().unwrap();
+    let table = debugger.resolve_location("TABLE").unwrap();
+    assert_eq!(debugger.vm().memory().peek(table + 2), 3);
+
+    assert_eq!(debugger.back_to_write(table + 2), StopReason::Done);
+    assert_eq!(pc(&debugger), 0x3003);
+    assert_eq!(debugger.vm().memory().peek(table + 2), 3);
+    assert_eq!(debugger.back_to_write(table), StopReason::HistoryStart);
+
+    // Undone instructions trigger watchpoints
+    debugger.continue_execution().unwrap();
+    debugger.add_watchpoint(Watchpoint {
+        range: table..=table + 2,
+        kind: WatchKind::Access,
+    });
+    let StopReason::Watchpoint(hit) = debugger.reverse_continue() else {
+        panic!("expected a watchpoint to be triggered");
+    };
+    assert_eq!((hit.pc, hit.access), (0x3003, WatchKind::Write));
+    assert_eq!(pc(&debugger), 0x3003);
+}
+
+#[test]
+fn test_poke_memory() {
+    let output = OutputBuffer::new();
+    let mut vm = Lc3Vm::with_console(IoConsole::new("".as_bytes(), output.clone()));
+    vm.memory_mut().write(0x3000, 0x1021); // ADD R0, R0, #1
+    let mut debugger = Debugger::new(vm);
+    debugger.step(1).unwrap();
+    assert_eq!(debugger.history_len(), 1);
+
+    // Writing the device registers has no side effects, and cannot be undone
+    debugger.poke_memory(DeviceRegister::Ddr.address(), u16::from(b'A'));
+    debugger.poke_memory(0x3000, 0x1022);
+    assert!(output.contents().is_empty());
+    assert_eq!(debugger.vm().peek_memory(0x3000), 0x1022);
+    assert_eq!(debugger.history_len(), 0);
+    assert_eq!(debugger.step_back(1), StopReason::HistoryStart);
+}
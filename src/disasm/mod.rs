@@ -0,0 +1,119 @@
+//! This module contains the disassembler, which renders instructions in LC3 assembly
+//! syntax. Instructions are decoded in the same way as `Lc3Vm::run_op` decodes them
+
+#[cfg(test)]
+mod tests;
+
+use std::{fmt, ops::RangeInclusive};
+
+use crate::{
+    bitwise_utils::sign_extend,
+    symbols::SymbolTable,
+    vm::{memory::Memory, ops::OpCode, trap_vecs::TrapVector},
+};
+
+/// A disassembled word of memory
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DisasmLine {
+    pub address: u16,
+    /// The raw word stored at `address`
+    pub word: u16,
+    /// The label that refers to `address`, if any
+    pub label: Option<String>,
+    /// The word rendered as an instruction
+    pub text: String,
+}
+
+impl fmt::Display for DisasmLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = self.label.as_deref().unwrap_or_default();
+        write!(
+            f,
+            "x{:04X}  {:04X}  {label:<16} {}",
+            self.address, self.word, self.text
+        )
+    }
+}
+
+/// Disassembles the words of memory in `range`. Memory is read without side effects,
+/// so device registers are not affected. Labels in `symbols` are used for addresses,
+/// and the targets of PC-relative instructions are rendered as `LABEL+OFFSET`
+pub fn disassemble(
+    memory: &Memory,
+    range: RangeInclusive<u16>,
+    symbols: Option<&SymbolTable>,
+) -> Vec<DisasmLine> {
+    range
+        .map(|address| {
+            let word = memory.peek(address);
+            DisasmLine {
+                address,
+                word,
+                label: symbols
+                    .and_then(|symbols| symbols.label_at(address))
+                    .map(String::from),
+                text: disassemble_instr(word, address, symbols),
+            }
+        })
+        .collect()
+}
+
+/// Renders the instruction `instr` stored at `address`. Words that are not valid
+/// instructions are rendered as a `.FILL` directive
+pub fn disassemble_instr(instr: u16, address: u16, symbols: Option<&SymbolTable>) -> String {
+    let Ok(opcode) = OpCode::try_from(instr >> 12) else {
+        return format!(".FILL x{instr:04X}");
+    };
+    let dr = (instr >> 9) & 0b111;
+    let sr1 = (instr >> 6) & 0b111;
+    // The target of PC-relative instructions, computed from the incremented PC
+    let target = |bit_count: u16| {
+        let offset = sign_extend(instr & ((1 << bit_count) - 1), bit_count);
+        let target = address.wrapping_add(1).wrapping_add(offset);
+        match symbols.and_then(|symbols| symbols.label_offset(target)) {
+            Some(label) => label,
+            None => format!("x{target:04X}"),
+        }
+    };
+    let second_operand = || {
+        if (instr >> 5) & 1 == 0 {
+            format!("R{}", instr & 0b111)
+        } else {
+            format!("#{}", sign_extend(instr & 0b11111, 5) as i16)
+        }
+    };
+    let offset6 = || sign_extend(instr & 0x3f, 6) as i16;
+
+    match opcode {
+        OpCode::Add => format!("ADD R{dr}, R{sr1}, {}", second_operand()),
+        OpCode::And => format!("AND R{dr}, R{sr1}, {}", second_operand()),
+        OpCode::Br => {
+            let n = if instr & 0x800 != 0 { "n" } else { "" };
+            let z = if instr & 0x400 != 0 { "z" } else { "" };
+            let p = if instr & 0x200 != 0 { "p" } else { "" };
+            if n.is_empty() && z.is_empty() && p.is_empty() {
+                // The branch is never taken
+                String::from("NOP")
+            } else {
+                format!("BR{n}{z}{p} {}", target(9))
+            }
+        }
+        OpCode::Jmp if sr1 == 7 => String::from("RET"),
+        OpCode::Jmp => format!("JMP R{sr1}"),
+        OpCode::Jsr if (instr >> 11) & 1 == 1 => format!("JSR {}", target(11)),
+        OpCode::Jsr => format!("JSRR R{sr1}"),
+        OpCode::Ld => format!("LD R{dr}, {}", target(9)),
+        OpCode::Ldi => format!("LDI R{dr}, {}", target(9)),
+        OpCode::Ldr => format!("LDR R{dr}, R{sr1}, #{}", offset6()),
+        OpCode::Lea => format!("LEA R{dr}, {}", target(9)),
+        OpCode::Not => format!("NOT R{dr}, R{sr1}"),
+        OpCode::Rti => String::from("RTI"),
+        OpCode::St => format!("ST R{dr}, {}", target(9)),
+        OpCode::Sti => format!("STI R{dr}, {}", target(9)),
+        OpCode::Str => format!("STR R{dr}, R{sr1}, #{}", offset6()),
+        OpCode::Trap => match TrapVector::try_from(instr & 0xff) {
+            Ok(trap_vec) => String::from(trap_vec.name()),
+            Err(()) => format!("TRAP x{:02X}", instr & 0xff),
+        },
+    }
+}
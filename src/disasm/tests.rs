@@ -0,0 +1,133 @@
+use super::*;
+use crate::asm::assemble;
+
+const PROGRAM: &str = "
+        .ORIG x3000
+START   ADD R1, R2, #-3
+        AND R0, R1, R7
+        BRnz START
+        BRnzp DATA
+        JMP R5
+        RET
+        JSR START
+        JSRR R3
+        LD R1, DATA
+        LDI R2, DATA
+        LDR R3, R6, #-32
+        LEA R4, DATA
+        NOT R5, R6
+        RTI
+        ST R6, DATA
+        STI R7, DATA
+        STR R0, R1, #31
+        GETC
+        OUT
+        PUTS
+        IN
+        PUTSP
+        HALT
+DATA    .FILL x0000
+        .END
+";
+
+/// Returns memory containing the assembled program, and its symbol table
+fn assembled_memory(source: &str) -> (Memory, SymbolTable, u16) {
+    let assembly = assemble(source).unwrap();
+    let mut memory = Memory::new();
+    for (address, word) in (assembly.origin..).zip(&assembly.words) {
+        memory.write(address, *word);
+    }
+    let end = assembly.origin + assembly.words.len() as u16 - 1;
+    (memory, assembly.symbols, end)
+}
+
+#[test]
+fn test_disassemble_with_symbols() {
+    let (memory, symbols, end) = assembled_memory(PROGRAM);
+    let lines = disassemble(&memory, 0x3000..=end, Some(&symbols));
+    let texts = lines
+        .iter()
+        .map(|line| line.text.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        texts,
+        vec![
+            "ADD R1, R2, #-3",
+            "AND R0, R1, R7",
+            "BRnz START",
+            "BRnzp DATA",
+            "JMP R5",
+            "RET",
+            "JSR START",
+            "JSRR R3",
+            "LD R1, DATA",
+            "LDI R2, DATA",
+            "LDR R3, R6, #-32",
+            "LEA R4, DATA",
+            "NOT R5, R6",
+            "RTI",
+            "ST R6, DATA",
+            "STI R7, DATA",
+            "STR R0, R1, #31",
+            "GETC",
+            "OUT",
+            "PUTS",
+            "IN",
+            "PUTSP",
+            "HALT",
+            "NOP",
+        ]
+    );
+    assert_eq!(lines[0].label.as_deref(), Some("START"));
+    assert_eq!(lines[1].label, None);
+    assert_eq!(lines[23].label.as_deref(), Some("DATA"));
+}
+
+#[test]
+fn test_disassemble_label_offset() {
+    let (memory, mut symbols, _) = assembled_memory(PROGRAM);
+    symbols.insert("OTHER", 0x3020);
+    let lines = disassemble(&memory, 0x3002..=0x3002, Some(&symbols));
+    assert_eq!(lines[0].text, "BRnz START");
+    // Targets without a label are rendered from the closest label before them
+    let text = disassemble_instr(0x0e1a, 0x3003, Some(&symbols));
+    assert_eq!(text, "BRnzp DATA+7");
+    let text = disassemble_instr(0x0e1e, 0x3003, Some(&symbols));
+    assert_eq!(text, "BRnzp OTHER+2");
+}
+
+#[test]
+fn test_disassemble_without_symbols() {
+    let (memory, _, _) = assembled_memory(PROGRAM);
+    let lines = disassemble(&memory, 0x3002..=0x3003, None);
+    assert_eq!(lines[0].text, "BRnz x3000");
+    assert_eq!(lines[1].text, "BRnzp x3017");
+    assert_eq!(
+        lines[0].to_string(),
+        "x3002  0DFD                   BRnz x3000"
+    );
+}
+
+#[test]
+fn test_disassembly_reassembles() {
+    let (memory, _, end) = assembled_memory(PROGRAM);
+    let lines = disassemble(&memory, 0x3000..=end, None);
+    // Addresses are only valid operands for the assembler when given as labels, so
+    // check the instructions that do not use PC-relative offsets. `NOP` is not an
+    // assembler mnemonic
+    let checked = lines
+        .iter()
+        .filter(|line| !line.text.contains(" x") && line.text != "NOP");
+    for line in checked {
+        let source = format!(".ORIG x{:04X}\n{}\n.END", line.address, line.text);
+        assert_eq!(assemble(&source).unwrap().words, vec![line.word], "{line}");
+    }
+}
+
+#[test]
+fn test_disassemble_invalid_instructions() {
+    assert_eq!(disassemble_instr(0xd123, 0x3000, None), ".FILL xD123");
+    assert_eq!(disassemble_instr(0xf0ff, 0x3000, None), "TRAP xFF");
+    // Offsets wrap around the address space
+    assert_eq!(disassemble_instr(0x0e01, 0xfffe, None), "BRnzp x0000");
+}
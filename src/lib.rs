@@ -0,0 +1,34 @@
+//! An emulator for the LC3 ISA.
+//!
+//! The [`Lc3Vm`] struct is the entry point of the library. It owns the [`Registers`]
+//! and [`Memory`] of the machine, and is able to load compiled LC3 programs and run
+//! them.
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use rust_vm::Lc3Vm;
+//!
+//! let mut vm = Lc3Vm::new();
+//! vm.load_program(Path::new("program.obj")).unwrap();
+//! vm.run().unwrap();
+//! ```
+
+pub mod asm;
+mod bitwise_utils;
+pub mod debugger;
+pub mod disasm;
+pub mod symbols;
+pub mod vm;
+
+pub use vm::{
+    console::{Console, InputThread, IoConsole, OutputBuffer, StdConsole},
+    error::{LoadError, VmError},
+    limits::{Limit, RunLimits},
+    loader::{ProgramFormat, ProgramImage},
+    memory::{DeviceRegister, Memory},
+    registers::{ConditionFlag, PrivilegeMode, RegisterName, Registers},
+    step::{MemoryRead, MemoryWrite, RegisterWrite, Step},
+    trace::{Trace, TraceFormat},
+    trap_vecs::TrapVector,
+    Lc3Vm,
+};
@@ -1,29 +1,243 @@
-mod bitwise_utils;
-mod vm;
+mod cli;
 
-use std::{env::args, path::Path, process::exit};
+use std::{env::args, fs::File, process::exit, time::Duration};
 
-use vm::Lc3Vm;
+use cli::terminal::RawMode;
+use rust_vm::{debugger::parse_number, Lc3Vm, RunLimits, Trace, TraceFormat, VmError};
 
 fn print_usage(program_name: &str) {
     eprintln!(
-        "USAGE: {program_name} LC3_PROGRAM_PATH
-        LC3_PROGRAM_PATH: The file path to the LC3 program to execute"
+        "USAGE: {program_name} [OPTIONS] LC3_PROGRAM_PATH...
+        LC3_PROGRAM_PATH: The file path to an LC3 program to execute, in the .obj, .hex
+            or .bin format. Several programs can be given, which are loaded in order and
+            must not overlap
+   or: {program_name} {}
+   or: {program_name} {}
+   or: {program_name} {}
+   or: {program_name} {}
+   or: {program_name} {}
+
+OPTIONS:
+        --sym SYM_PATH: Read the labels used in error messages from the symbol table
+            at SYM_PATH, instead of from each LC3_PROGRAM_PATH with the .sym extension
+        --entry ADDRESS: Start execution at ADDRESS instead of the start of the first
+            program
+        --max-instructions COUNT: Stop the program after executing COUNT instructions
+        --timeout SECONDS: Stop the program after running for SECONDS seconds
+        --no-raw-terminal: Keep the terminal line buffered and echoing input while
+            the program runs
+        --ignore-privilege: Allow programs running in user mode to access system
+            space and the device registers
+        --boot-os: Boot the bundled LC3 operating system, which runs the program in
+            user mode with trap routines written in LC3 assembly
+        --gdb PORT: Wait for GDB to connect to PORT on the local host, and let it
+            control the program with the GDB remote serial protocol
+        --trace TRACE_PATH: Write a record of every executed instruction to TRACE_PATH
+        --trace-format FORMAT: The format of the trace, which is either text (the
+            default) for aligned columns, or jsonl for one JSON object per line",
+        cli::asm::USAGE,
+        cli::disasm::USAGE,
+        cli::convert::USAGE,
+        cli::debug::USAGE,
+        cli::dap::USAGE
     );
 }
 
+/// Options given to the program on the command line
+struct Options {
+    program_paths: Vec<String>,
+    sym_paths: Vec<String>,
+    entry: Option<u16>,
+    limits: RunLimits,
+    raw_terminal: bool,
+    ignore_privilege: bool,
+    boot_os: bool,
+    gdb_port: Option<u16>,
+    trace_path: Option<String>,
+    trace_format: TraceFormat,
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut program_paths = Vec::new();
+    let mut sym_paths = Vec::new();
+    let mut entry = None;
+    let mut limits = RunLimits::none();
+    let mut raw_terminal = true;
+    let mut ignore_privilege = false;
+    let mut boot_os = false;
+    let mut gdb_port = None;
+    let mut trace_path = None;
+    let mut trace_format = TraceFormat::Text;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--sym" => {
+                let path = args.next().ok_or(format!("Missing value for {arg}"))?;
+                sym_paths.push(path.clone());
+            }
+            "--entry" => {
+                let address = args.next().ok_or(format!("Missing value for {arg}"))?;
+                let address =
+                    parse_number(address).ok_or(format!("Invalid value for {arg}: {address}"))?;
+                entry = Some(address);
+            }
+            "--max-instructions" => {
+                let count = option_value(arg, args.next())?;
+                limits = limits.with_max_instructions(count);
+            }
+            "--timeout" => {
+                let seconds: f64 = option_value(arg, args.next())?;
+                let duration = Duration::try_from_secs_f64(seconds)
+                    .map_err(|_| format!("Invalid value for {arg}: {seconds}"))?;
+                limits = limits.with_max_duration(duration);
+            }
+            "--no-raw-terminal" => raw_terminal = false,
+            "--ignore-privilege" => ignore_privilege = true,
+            "--boot-os" => boot_os = true,
+            "--gdb" => gdb_port = Some(option_value(arg, args.next())?),
+            "--trace" => trace_path = Some(option_value(arg, args.next())?),
+            "--trace-format" => trace_format = option_value(arg, args.next())?,
+            _ if !arg.starts_with("--") => program_paths.push(arg.clone()),
+            _ => return Err(format!("Unexpected argument: {arg}")),
+        }
+    }
+    if program_paths.is_empty() {
+        return Err("No LC3 program given".to_string());
+    }
+    Ok(Options {
+        program_paths,
+        sym_paths,
+        entry,
+        limits,
+        raw_terminal,
+        ignore_privilege,
+        boot_os,
+        gdb_port,
+        trace_path,
+        trace_format,
+    })
+}
+
+/// Parses the value given for the command line option `option`
+fn option_value<T: std::str::FromStr>(option: &str, value: Option<&String>) -> Result<T, String> {
+    let value = value.ok_or(format!("Missing value for {option}"))?;
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value for {option}: {value}"))
+}
+
+/// Puts the terminal into raw mode if `enabled` is set and the terminal supports it
+fn enable_raw_mode(enabled: bool) -> Option<RawMode> {
+    if !enabled {
+        return None;
+    }
+    RawMode::enable().unwrap_or_else(|e| {
+        eprintln!("Failed to put the terminal into raw mode: {e}");
+        None
+    })
+}
+
+/// Runs the subcommand named by the first argument. Returns `None` if the arguments
+/// do not start with a subcommand
+fn run_subcommand(args: &[String]) -> Option<Result<i32, String>> {
+    let (subcommand, args) = args.split_first()?;
+    let result = match subcommand.as_str() {
+        "asm" => cli::asm::run(args),
+        "disasm" => cli::disasm::run(args),
+        "convert" => cli::convert::run(args),
+        "debug" => cli::debug::run(args),
+        "dap" => cli::dap::run(args),
+        _ => return None,
+    };
+    Some(result)
+}
+
 fn main() {
     let args = args().collect::<Vec<String>>();
-    if args.len() != 2 {
-        print_usage(&args[0]);
-        exit(1);
+    if let Some(result) = run_subcommand(&args[1..]) {
+        match result {
+            Ok(code) => exit(code),
+            Err(e) => {
+                eprintln!("{e}");
+                print_usage(&args[0]);
+                exit(1);
+            }
+        }
     }
+    let options = match parse_args(&args[1..]) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{e}");
+            print_usage(&args[0]);
+            exit(1);
+        }
+    };
 
-    let file_path = Path::new(&args[1]);
     let mut vm = Lc3Vm::new();
-    if let Err(e) = vm.load_program(file_path) {
-        eprintln!("Failed to load LC3 program: {e}")
+    // The programs are loaded over the operating system, so that they can replace its
+    // trap routines
+    if options.boot_os {
+        vm.load_os();
+    }
+    if let Err(e) = vm.load_programs(&options.program_paths) {
+        eprintln!("Failed to load LC3 program: {e}");
+        exit(1);
     };
-    vm.run();
-    println!("=====Program execution complete=====");
+    if let Some(entry) = options.entry {
+        vm.registers_mut().set_program_counter(entry);
+    }
+    vm.set_run_limits(options.limits);
+    vm.set_ignore_privilege(options.ignore_privilege);
+    match cli::symbols::read_symbols(&options.program_paths, &options.sym_paths) {
+        Ok(symbols) => vm.set_symbols(symbols),
+        Err(e) => {
+            eprintln!("{e}");
+            exit(1);
+        }
+    }
+    if options.boot_os {
+        vm.boot_os();
+    }
+    if let Some(path) = &options.trace_path {
+        match File::create(path) {
+            Ok(file) => vm.set_trace(Trace::new(file, options.trace_format)),
+            Err(e) => {
+                eprintln!("Failed to create trace file {path}: {e}");
+                exit(1);
+            }
+        }
+    }
+    if let Some(port) = options.gdb_port {
+        let result = {
+            let _raw_mode = enable_raw_mode(options.raw_terminal);
+            cli::gdb::serve(vm, port)
+        };
+        if let Err(e) = result {
+            eprintln!("{e}");
+            exit(1);
+        }
+        return;
+    }
+    let result = {
+        // Keep the terminal in raw mode only while the program is running
+        let _raw_mode = enable_raw_mode(options.raw_terminal);
+        vm.run()
+    };
+    match result {
+        Ok(()) => println!("=====Program execution complete====="),
+        Err(e @ VmError::LimitExceeded { .. }) => {
+            eprintln!(
+                "LC3 program execution limit exceeded: {}",
+                e.with_symbols(vm.symbols())
+            );
+            exit(2);
+        }
+        Err(e) => {
+            eprintln!(
+                "LC3 program execution failed: {}",
+                e.with_symbols(vm.symbols())
+            );
+            exit(1);
+        }
+    }
 }
@@ -0,0 +1,177 @@
+//! This module contains the symbol table, which maps the labels of a program to the
+//! addresses they refer to
+
+#[cfg(test)]
+mod tests;
+
+use std::{collections::BTreeMap, error::Error, fmt, io};
+
+/// The largest offset from a label that `SymbolTable::label_offset` uses. Addresses
+/// further away from every label are shown as plain addresses
+const MAX_LABEL_OFFSET: u16 = 0xff;
+
+/// A table of labels and the addresses they refer to
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct SymbolTable {
+    symbols: BTreeMap<String, u16>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a label to the table. If the label was already in the table, its
+    /// address is replaced and the previous address is returned
+    pub fn insert(&mut self, label: impl Into<String>, address: u16) -> Option<u16> {
+        self.symbols.insert(label.into(), address)
+    }
+
+    /// Returns the address of the given label
+    pub fn address(&self, label: &str) -> Option<u16> {
+        self.symbols.get(label).copied()
+    }
+
+    /// Returns the label that refers to `address`. If several labels refer to the
+    /// address, the first one in alphabetical order is returned
+    pub fn label_at(&self, address: u16) -> Option<&str> {
+        self.symbols
+            .iter()
+            .find(|(_, label_address)| **label_address == address)
+            .map(|(label, _)| label.as_str())
+    }
+
+    /// Returns the closest label at or before `address`, and the offset of `address`
+    /// from it
+    pub fn nearest_label(&self, address: u16) -> Option<(&str, u16)> {
+        self.symbols
+            .iter()
+            .filter(|(_, label_address)| **label_address <= address)
+            .map(|(label, label_address)| (label.as_str(), address - label_address))
+            .filter(|(_, offset)| *offset <= MAX_LABEL_OFFSET)
+            .min_by_key(|(label, offset)| (*offset, *label))
+    }
+
+    /// Renders `address` as `LABEL` or `LABEL+OFFSET` using the closest label before
+    /// it. `None` is returned if there is no label close enough
+    pub fn label_offset(&self, address: u16) -> Option<String> {
+        match self.nearest_label(address)? {
+            (label, 0) => Some(label.to_string()),
+            (label, offset) => Some(format!("{label}+{offset}")),
+        }
+    }
+
+    /// Renders `address` in hexadecimal, followed by its `LABEL+OFFSET` form if there
+    /// is a label close enough, like `x3005 (LOOP+2)`
+    pub fn describe(&self, address: u16) -> String {
+        match self.label_offset(address) {
+            Some(label) => format!("x{address:04X} ({label})"),
+            None => format!("x{address:04X}"),
+        }
+    }
+
+    /// Resolves a location written as `LABEL`, `LABEL+OFFSET` or `LABEL-OFFSET` to an
+    /// address. Offsets are decimal
+    pub fn resolve(&self, location: &str) -> Option<u16> {
+        if let Some(address) = self.address(location) {
+            return Some(address);
+        }
+        let split = location.rfind(['+', '-'])?;
+        let (label, signed_offset) = location.split_at(split);
+        let address = self.address(label)?;
+        let offset = signed_offset[1..].parse::<u16>().ok()?;
+        if signed_offset.starts_with('+') {
+            Some(address.wrapping_add(offset))
+        } else {
+            Some(address.wrapping_sub(offset))
+        }
+    }
+
+    /// Adds the labels of `other` to the table, replacing the addresses of labels
+    /// that are in both tables
+    pub fn merge(&mut self, other: &SymbolTable) {
+        for (label, address) in &other.symbols {
+            self.symbols.insert(label.clone(), *address);
+        }
+    }
+
+    /// Returns the number of labels in the table
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Returns the labels and their addresses, ordered by address
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u16)> {
+        let mut symbols = self
+            .symbols
+            .iter()
+            .map(|(label, address)| (label.as_str(), *address))
+            .collect::<Vec<_>>();
+        symbols.sort_by_key(|(label, address)| (*address, *label));
+        symbols.into_iter()
+    }
+
+    /// Parses a symbol table in the `.sym` format produced by `lc3as`. Each entry is a
+    /// label followed by its address in hexadecimal, and the header lines are skipped
+    pub fn parse_sym(source: &str) -> Result<Self, ParseSymError> {
+        let mut table = Self::new();
+        for (index, line) in source.lines().enumerate() {
+            let line = line.trim_start_matches('/').trim();
+            let fields = line.split_whitespace().collect::<Vec<&str>>();
+            let entry = match fields[..] {
+                [label, address] => u16::from_str_radix(address, 16)
+                    .ok()
+                    .map(|address| (label, address)),
+                _ => None,
+            };
+            match entry {
+                Some((label, address)) => {
+                    table.insert(label, address);
+                }
+                None if is_sym_header(line) => (),
+                None => return Err(ParseSymError { line: index + 1 }),
+            }
+        }
+        Ok(table)
+    }
+
+    /// Writes the table in the `.sym` format produced by `lc3as`
+    pub fn write_sym(&self, writer: &mut impl io::Write) -> io::Result<()> {
+        writeln!(writer, "// Symbol table")?;
+        writeln!(writer, "// Scope level 0:")?;
+        writeln!(writer, "//\tSymbol Name       Page Address")?;
+        writeln!(writer, "//\t----------------  ------------")?;
+        for (label, address) in self.iter() {
+            writeln!(writer, "//\t{label:<16}  {address:04X}")?;
+        }
+        writeln!(writer)
+    }
+}
+
+/// Returns true for the lines of a `.sym` file that are not entries of the table
+fn is_sym_header(line: &str) -> bool {
+    line.is_empty()
+        || line.starts_with("Symbol")
+        || line.starts_with("Scope")
+        || line.starts_with('-')
+}
+
+/// The error returned when a `.sym` file contains a line that is not an entry of the
+/// symbol table
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ParseSymError {
+    /// The 1-based line number of the invalid line
+    pub line: usize,
+}
+
+impl fmt::Display for ParseSymError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid symbol table entry on line {}", self.line)
+    }
+}
+
+impl Error for ParseSymError {}
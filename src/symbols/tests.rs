@@ -0,0 +1,90 @@
+use super::*;
+
+#[test]
+fn test_sym_round_trip() {
+    let mut table = SymbolTable::new();
+    table.insert("LOOP", 0x3002);
+    table.insert("MSG", 0x3010);
+    table.insert("START", 0x3000);
+
+    let mut sym = Vec::new();
+    table.write_sym(&mut sym).unwrap();
+    let parsed = SymbolTable::parse_sym(&String::from_utf8(sym).unwrap()).unwrap();
+    assert_eq!(parsed, table);
+}
+
+#[test]
+fn test_parse_sym() {
+    let sym = "// Symbol table
+// Scope level 0:
+//\tSymbol Name       Page Address
+//\t----------------  ------------
+//\tSTART             3000
+//\tDATA              30AB
+
+";
+    let table = SymbolTable::parse_sym(sym).unwrap();
+    assert_eq!(table.len(), 2);
+    assert_eq!(table.address("START"), Some(0x3000));
+    assert_eq!(table.address("DATA"), Some(0x30ab));
+
+    let invalid = "//\tSTART             3000\n//\tDATA\n";
+    assert_eq!(
+        SymbolTable::parse_sym(invalid),
+        Err(ParseSymError { line: 2 })
+    );
+}
+
+#[test]
+fn test_label_at() {
+    let mut table = SymbolTable::new();
+    table.insert("B", 0x3000);
+    table.insert("A", 0x3000);
+    table.insert("C", 0x3001);
+    assert_eq!(table.label_at(0x3000), Some("A"));
+    assert_eq!(table.label_at(0x3001), Some("C"));
+    assert_eq!(table.label_at(0x3002), None);
+    let labels = table.iter().collect::<Vec<_>>();
+    assert_eq!(labels, vec![("A", 0x3000), ("B", 0x3000), ("C", 0x3001)]);
+}
+
+#[test]
+fn test_label_offset() {
+    let mut table = SymbolTable::new();
+    table.insert("START", 0x3000);
+    table.insert("LOOP", 0x3002);
+    assert_eq!(table.label_offset(0x3000).as_deref(), Some("START"));
+    assert_eq!(table.label_offset(0x3001).as_deref(), Some("START+1"));
+    assert_eq!(table.label_offset(0x3005).as_deref(), Some("LOOP+3"));
+    assert_eq!(table.label_offset(0x2fff), None);
+    // Addresses far away from every label are not described by a label
+    assert_eq!(table.label_offset(0xfe00), None);
+
+    assert_eq!(table.describe(0x3005), "x3005 (LOOP+3)");
+    assert_eq!(table.describe(0x2fff), "x2FFF");
+}
+
+#[test]
+fn test_resolve() {
+    let mut table = SymbolTable::new();
+    table.insert("LOOP", 0x3002);
+    assert_eq!(table.resolve("LOOP"), Some(0x3002));
+    assert_eq!(table.resolve("LOOP+3"), Some(0x3005));
+    assert_eq!(table.resolve("LOOP-2"), Some(0x3000));
+    assert_eq!(table.resolve("LOOP+x"), None);
+    assert_eq!(table.resolve("MISSING+1"), None);
+}
+
+#[test]
+fn test_merge() {
+    let mut table = SymbolTable::new();
+    table.insert("MAIN", 0x3000);
+    table.insert("DATA", 0x4000);
+    let mut other = SymbolTable::new();
+    other.insert("DATA", 0x5000);
+    other.insert("HANDLER", 0x6000);
+    table.merge(&other);
+    assert_eq!(table.len(), 3);
+    assert_eq!(table.address("DATA"), Some(0x5000));
+    assert_eq!(table.address("HANDLER"), Some(0x6000));
+}
@@ -0,0 +1,197 @@
+//! This module contains the console used by the VM for keyboard input and display
+//! output. The console is used by the trap routines and the keyboard and display
+//! device registers, and can be replaced to run programs without a terminal
+
+#[cfg(test)]
+mod tests;
+
+use std::{
+    io::{self, stdin, stdout, ErrorKind, Read, Write},
+    sync::{
+        mpsc::{self, Receiver},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+/// A source of keyboard input and a destination for display output
+pub trait Console {
+    /// Reads a single character from the keyboard, blocking until one is available
+    fn read_char(&mut self) -> io::Result<u8>;
+
+    /// Reads a single character from the keyboard if one has been typed, without
+    /// blocking. Returns `None` if no character is available
+    fn poll_char(&mut self) -> io::Result<Option<u8>>;
+
+    /// Writes the given characters to the display
+    fn write_chars(&mut self, chars: &[u8]) -> io::Result<()>;
+}
+
+/// A console that uses the standard input and output of the process. Standard input
+/// is read by a background thread, so that the keyboard can be polled without
+/// blocking. The thread is only started once the program reads from the keyboard
+#[derive(Default)]
+pub struct StdConsole {
+    input: Option<InputThread>,
+}
+
+impl StdConsole {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn input(&mut self) -> &mut InputThread {
+        self.input
+            .get_or_insert_with(|| InputThread::spawn(stdin()))
+    }
+}
+
+impl Console for StdConsole {
+    fn read_char(&mut self) -> io::Result<u8> {
+        self.input().read_char()
+    }
+
+    fn poll_char(&mut self) -> io::Result<Option<u8>> {
+        Ok(self.input().poll_char())
+    }
+
+    fn write_chars(&mut self, chars: &[u8]) -> io::Result<()> {
+        let mut stdout = stdout().lock();
+        stdout.write_all(chars)?;
+        // Programs often print prompts without a trailing newline, so make sure
+        // they are shown immediately
+        stdout.flush()
+    }
+}
+
+/// A console that reads from and writes to any reader and writer, for example
+/// scripted input and an `OutputBuffer` to capture output.
+///
+/// Polling the keyboard reads from the reader directly, so this console should only
+/// be used with readers that do not block, such as in-memory buffers
+pub struct IoConsole<R, W> {
+    reader: R,
+    writer: W,
+}
+
+impl<R, W> IoConsole<R, W>
+where
+    R: Read,
+    W: Write,
+{
+    pub fn new(reader: R, writer: W) -> Self {
+        Self { reader, writer }
+    }
+}
+
+impl<R, W> Console for IoConsole<R, W>
+where
+    R: Read,
+    W: Write,
+{
+    fn read_char(&mut self) -> io::Result<u8> {
+        let mut buf: [u8; 1] = [0];
+        self.reader.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    fn poll_char(&mut self) -> io::Result<Option<u8>> {
+        let mut buf: [u8; 1] = [0];
+        match self.reader.read(&mut buf) {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(buf[0])),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn write_chars(&mut self, chars: &[u8]) -> io::Result<()> {
+        self.writer.write_all(chars)?;
+        self.writer.flush()
+    }
+}
+
+/// Reads input on a background thread, so that it can be polled without blocking
+pub struct InputThread {
+    receiver: Receiver<u8>,
+}
+
+impl InputThread {
+    /// Starts a thread that reads everything from `reader`, until the end of the
+    /// input or until reading fails
+    pub fn spawn(reader: impl Read + Send + 'static) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut reader = reader;
+            let mut buf: [u8; 64] = [0; 64];
+            loop {
+                let len = match reader.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(len) => len,
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(_) => break,
+                };
+                for byte in &buf[..len] {
+                    if sender.send(*byte).is_err() {
+                        // The receiving end was dropped, so nobody needs the input anymore
+                        return;
+                    }
+                }
+            }
+        });
+        Self { receiver }
+    }
+
+    /// Returns the next character, blocking until one is available. An error is
+    /// returned once the end of the input has been reached
+    pub fn read_char(&mut self) -> io::Result<u8> {
+        self.receiver
+            .recv()
+            .map_err(|_| io::Error::from(ErrorKind::UnexpectedEof))
+    }
+
+    /// Returns the next character if one is available, without blocking
+    pub fn poll_char(&mut self) -> Option<u8> {
+        // Both an empty and a disconnected channel mean that no input is available
+        self.receiver.try_recv().ok()
+    }
+}
+
+/// A buffer that collects the output written to it. Clones of the buffer share the
+/// same contents, so a clone can be given to a console while the original is used to
+/// read back what the program printed
+#[derive(Clone, Default)]
+pub struct OutputBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl OutputBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a copy of everything written to the buffer so far
+    pub fn contents(&self) -> Vec<u8> {
+        self.lock().clone()
+    }
+
+    /// Returns everything written to the buffer so far, converted to a `String`
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.lock()).into_owned()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<u8>> {
+        // The lock can only be poisoned if a write panicked, in which case the data
+        // written so far is still usable
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Write for OutputBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lock().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
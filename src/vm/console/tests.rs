@@ -0,0 +1,63 @@
+use super::*;
+
+#[test]
+fn test_io_console() {
+    let output = OutputBuffer::new();
+    let mut console = IoConsole::new("ab".as_bytes(), output.clone());
+    assert_eq!(console.read_char().unwrap(), b'a');
+    assert_eq!(console.read_char().unwrap(), b'b');
+    assert!(console.read_char().is_err());
+
+    console.write_chars(b"hello").unwrap();
+    console.write_chars(b" world").unwrap();
+    assert_eq!(output.contents(), b"hello world");
+    assert_eq!(output.to_string_lossy(), "hello world");
+}
+
+#[test]
+fn test_io_console_poll_char() {
+    let mut console = IoConsole::new("a".as_bytes(), OutputBuffer::new());
+    assert_eq!(console.poll_char().unwrap(), Some(b'a'));
+    assert_eq!(console.poll_char().unwrap(), None);
+}
+
+/// A reader that blocks until data is sent to it through a channel
+struct ChannelReader(mpsc::Receiver<u8>);
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.recv() {
+            Ok(byte) => {
+                buf[0] = byte;
+                Ok(1)
+            }
+            Err(_) => Ok(0),
+        }
+    }
+}
+
+#[test]
+fn test_input_thread() {
+    let (sender, receiver) = mpsc::channel();
+    let mut input = InputThread::spawn(ChannelReader(receiver));
+
+    // Polling doesn't block while the reader is waiting for input
+    assert_eq!(input.poll_char(), None);
+
+    sender.send(b'k').unwrap();
+    assert_eq!(input.read_char().unwrap(), b'k');
+
+    sender.send(b'j').unwrap();
+    let polled = loop {
+        if let Some(byte) = input.poll_char() {
+            break byte;
+        }
+        thread::yield_now();
+    };
+    assert_eq!(polled, b'j');
+
+    // Once the input ends, reading fails instead of blocking forever
+    drop(sender);
+    assert!(input.read_char().is_err());
+    assert_eq!(input.poll_char(), None);
+}
@@ -0,0 +1,236 @@
+use std::{error::Error, fmt, io, path::PathBuf};
+
+use super::limits::Limit;
+use crate::symbols::SymbolTable;
+
+/// Errors that can occur while loading a program into the VM
+#[derive(Debug)]
+pub enum LoadError {
+    /// The program file could not be read
+    Io(io::Error),
+    /// The program does not contain any words after its origin
+    Empty,
+    /// The program has an odd number of bytes, so it is not made of 16 bit words
+    OddLength { len: usize },
+    /// A line of a program in a text format does not contain a valid word. `line` is
+    /// the 1-based line number
+    InvalidWord { line: usize, word: String },
+    /// The program is too large to fit into memory when loaded at `origin`.
+    /// `len` is the number of words in the program, apart from the origin
+    TooLarge { origin: u16, len: usize },
+    /// Two programs would be loaded into the same memory locations, from `start` to
+    /// `end`. `second` is the program that was loaded after `first`
+    Overlap {
+        first: PathBuf,
+        second: PathBuf,
+        start: u16,
+        end: u16,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to read program: {e}"),
+            Self::Empty => write!(f, "program is empty"),
+            Self::OddLength { len } => {
+                write!(f, "program has an odd number of bytes ({len})")
+            }
+            Self::InvalidWord { line, word } => {
+                write!(f, "invalid word \"{word}\" on line {line}")
+            }
+            Self::TooLarge { origin, len } => write!(
+                f,
+                "program of {len} words is too large to be loaded at x{origin:04X}"
+            ),
+            Self::Overlap {
+                first,
+                second,
+                start,
+                end,
+            } => write!(
+                f,
+                "{} overlaps {} from x{start:04X} to x{end:04X}",
+                second.display(),
+                first.display()
+            ),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Errors returned by the VM. Errors that happen while executing an instruction carry
+/// the address of the faulting instruction (`pc`) and the instruction itself (`instr`)
+#[derive(Debug)]
+pub enum VmError {
+    /// The instruction contains an opcode that is not part of the ISA. This and the
+    /// other exceptions are only reported when the interrupt vector table has no
+    /// service routine for them
+    IllegalOpcode { pc: u16, instr: u16 },
+    /// A privileged instruction (`RTI`) was executed in user mode
+    PrivilegeViolation { pc: u16, instr: u16 },
+    /// A program running in user mode accessed `address` in system space or the
+    /// device register space
+    AccessViolation { pc: u16, instr: u16, address: u16 },
+    /// The `TRAP` instruction refers to a trap vector without a service routine in the
+    /// trap vector table
+    BadTrapVector { pc: u16, instr: u16 },
+    /// A value that is not a valid ASCII character was read from, or written to
+    /// the console
+    InvalidCharacter { pc: u16, instr: u16, value: u16 },
+    /// Reading from or writing to the console failed
+    Io {
+        pc: u16,
+        instr: u16,
+        source: io::Error,
+    },
+    /// The program could not be loaded
+    Load(LoadError),
+    /// Execution was stopped by a `RunLimits` limit. `pc` is the address of the next
+    /// instruction that would have been executed
+    LimitExceeded { pc: u16, limit: Limit },
+    /// Writing a record to the execution trace failed
+    Trace(io::Error),
+}
+
+impl VmError {
+    /// Returns a value that displays the error like its `Display` implementation, with
+    /// addresses also rendered as `LABEL+OFFSET` using `symbols`
+    pub fn with_symbols<'a>(&'a self, symbols: &'a SymbolTable) -> WithSymbols<'a> {
+        WithSymbols {
+            error: self,
+            symbols,
+        }
+    }
+
+    fn fmt_with(&self, f: &mut fmt::Formatter<'_>, symbols: Option<&SymbolTable>) -> fmt::Result {
+        let addr = |address: u16| match symbols {
+            Some(symbols) => symbols.describe(address),
+            None => format!("x{address:04X}"),
+        };
+        match self {
+            Self::IllegalOpcode { pc, instr } => {
+                write!(
+                    f,
+                    "illegal opcode in instruction x{instr:04X} at {}",
+                    addr(*pc)
+                )
+            }
+            Self::PrivilegeViolation { pc, instr } => write!(
+                f,
+                "privileged instruction x{instr:04X} executed in user mode at {}",
+                addr(*pc)
+            ),
+            Self::AccessViolation { pc, instr, address } => write!(
+                f,
+                "access violation at {} in user mode while executing x{instr:04X} at {}",
+                addr(*address),
+                addr(*pc)
+            ),
+            Self::BadTrapVector { pc, instr } => write!(
+                f,
+                "unknown trap vector x{:02X} in instruction x{instr:04X} at {}",
+                instr & 0xff,
+                addr(*pc)
+            ),
+            Self::InvalidCharacter { pc, instr, value } => write!(
+                f,
+                "invalid ASCII character x{value:04X} while executing x{instr:04X} at {}",
+                addr(*pc)
+            ),
+            Self::Io { pc, instr, source } => write!(
+                f,
+                "console I/O failed while executing x{instr:04X} at {}: {source}",
+                addr(*pc)
+            ),
+            Self::Load(e) => write!(f, "failed to load program: {e}"),
+            Self::LimitExceeded { pc, limit } => match limit {
+                Limit::Instructions(count) => {
+                    write!(
+                        f,
+                        "stopped at {} after executing {count} instructions",
+                        addr(*pc)
+                    )
+                }
+                Limit::Duration(duration) => {
+                    write!(f, "stopped at {} after running for {duration:?}", addr(*pc))
+                }
+            },
+            Self::Trace(e) => write!(f, "failed to write the execution trace: {e}"),
+        }
+    }
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with(f, None)
+    }
+}
+
+/// Displays a `VmError` with the labels of a symbol table. See `VmError::with_symbols`
+pub struct WithSymbols<'a> {
+    error: &'a VmError,
+    symbols: &'a SymbolTable,
+}
+
+impl fmt::Display for WithSymbols<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt_with(f, Some(self.symbols))
+    }
+}
+
+impl Error for VmError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Load(e) => Some(e),
+            Self::Trace(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<LoadError> for VmError {
+    fn from(value: LoadError) -> Self {
+        Self::Load(value)
+    }
+}
+
+/// An error raised while executing an instruction, before the faulting instruction is
+/// known. `Lc3Vm::run_op` converts it into a `VmError` using `Fault::at`
+#[derive(Debug)]
+pub(crate) enum Fault {
+    IllegalOpcode,
+    PrivilegeViolation,
+    AccessViolation(u16),
+    BadTrapVector,
+    InvalidCharacter(u16),
+    Io(io::Error),
+}
+
+impl Fault {
+    pub(crate) fn at(self, pc: u16, instr: u16) -> VmError {
+        match self {
+            Self::IllegalOpcode => VmError::IllegalOpcode { pc, instr },
+            Self::PrivilegeViolation => VmError::PrivilegeViolation { pc, instr },
+            Self::AccessViolation(address) => VmError::AccessViolation { pc, instr, address },
+            Self::BadTrapVector => VmError::BadTrapVector { pc, instr },
+            Self::InvalidCharacter(value) => VmError::InvalidCharacter { pc, instr, value },
+            Self::Io(source) => VmError::Io { pc, instr, source },
+        }
+    }
+}
+
+impl From<io::Error> for Fault {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
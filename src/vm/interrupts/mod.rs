@@ -0,0 +1,88 @@
+//! This module contains the interrupt controller of the VM. Device interrupts are
+//! checked between instructions, and are taken when the device's priority is higher
+//! than the priority level in the PSR. Exceptions raised by instructions are handled
+//! through the same interrupt vector table
+
+#[cfg(test)]
+mod tests;
+
+use super::{error::Fault, registers::PrivilegeMode, Lc3Vm};
+
+/// The start of the interrupt vector table. The address of the service routine for
+/// interrupt vector `v` is stored at `INTERRUPT_VECTOR_TABLE + v`
+pub const INTERRUPT_VECTOR_TABLE: u16 = 0x0100;
+/// The exception vector of privilege mode violations, raised by `RTI` in user mode
+pub const PRIVILEGE_VIOLATION_VECTOR: u8 = 0x00;
+/// The exception vector of illegal opcodes
+pub const ILLEGAL_OPCODE_VECTOR: u8 = 0x01;
+/// The exception vector of access control violations, raised when a program in user
+/// mode accesses system space or the device registers
+pub const ACCESS_VIOLATION_VECTOR: u8 = 0x02;
+/// The interrupt vector of the keyboard
+pub const KEYBOARD_VECTOR: u8 = 0x80;
+/// The priority level of keyboard interrupts
+pub const KEYBOARD_PRIORITY: u16 = 4;
+
+impl Lc3Vm {
+    /// Takes a pending device interrupt, if there is one with a higher priority than
+    /// the running program. Returns the vector of the interrupt that was taken
+    pub(crate) fn check_interrupts(&mut self) -> Option<u8> {
+        let priority = self.registers.priority_level();
+        if KEYBOARD_PRIORITY > priority && self.memory.keyboard_interrupt_requested() {
+            self.initiate_interrupt(KEYBOARD_VECTOR, Some(KEYBOARD_PRIORITY));
+            return Some(KEYBOARD_VECTOR);
+        }
+        None
+    }
+
+    /// Starts the service routine of the exception for `fault`. The PC pushed onto the
+    /// supervisor stack is the current PC. Faults that are not exceptions, and
+    /// exceptions without a service routine in the interrupt vector table, are
+    /// returned so that they can be reported as errors
+    pub(crate) fn raise_exception(&mut self, fault: Fault) -> Result<(), Fault> {
+        let vector = match fault {
+            Fault::PrivilegeViolation => PRIVILEGE_VIOLATION_VECTOR,
+            Fault::IllegalOpcode => ILLEGAL_OPCODE_VECTOR,
+            Fault::AccessViolation(_) => ACCESS_VIOLATION_VECTOR,
+            _ => return Err(fault),
+        };
+        // A service routine cannot be at x0000, which is where the trap vector table is
+        if self.peek_memory(INTERRUPT_VECTOR_TABLE + vector as u16) == 0 {
+            return Err(fault);
+        }
+        self.initiate_interrupt(vector, None);
+        self.exception = Some(vector);
+        Ok(())
+    }
+
+    /// Switches to supervisor mode, pushes the PSR and PC onto the supervisor stack,
+    /// and jumps to the service routine of `vector` in the interrupt vector table.
+    /// `priority` is the new priority level, which is left unchanged if it is `None`
+    pub(crate) fn initiate_interrupt(&mut self, vector: u8, priority: Option<u16>) {
+        self.enter_supervisor_mode(priority);
+        let service_routine = self.read_memory(INTERRUPT_VECTOR_TABLE + vector as u16);
+        self.registers.set_program_counter(service_routine);
+    }
+
+    /// Switches to supervisor mode and pushes the PSR and PC onto the supervisor
+    /// stack, so that a service routine can return to the running program with `RTI`
+    pub(crate) fn enter_supervisor_mode(&mut self, priority: Option<u16>) {
+        let psr = self.registers.psr();
+        let pc = self.registers.program_counter();
+        if self.registers.privilege_mode() == PrivilegeMode::User {
+            let user_stack_pointer = self.get_reg_val_by_id(6);
+            self.registers.set_saved_usp(user_stack_pointer);
+            let supervisor_stack_pointer = self.registers.saved_ssp();
+            self.set_reg_val_by_id(6, supervisor_stack_pointer);
+        }
+        self.registers.set_privilege_mode(PrivilegeMode::Supervisor);
+        if let Some(priority) = priority {
+            self.registers.set_priority_level(priority);
+        }
+
+        let stack_pointer = self.get_reg_val_by_id(6).wrapping_sub(2);
+        self.set_reg_val_by_id(6, stack_pointer);
+        self.write_memory(stack_pointer.wrapping_add(1), psr);
+        self.write_memory(stack_pointer, pc);
+    }
+}
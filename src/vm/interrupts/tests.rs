@@ -0,0 +1,208 @@
+use super::*;
+use crate::{
+    asm::assemble,
+    vm::{
+        console::{IoConsole, OutputBuffer},
+        error::VmError,
+        registers::Registers,
+        step::Step,
+    },
+};
+
+const KBSR: u16 = 0xfe00;
+
+/// Creates a VM which reads `input` from its console, with the given programs
+/// assembled into memory
+fn vm_with_programs(input: &'static str, sources: &[&str]) -> Lc3Vm {
+    let console = IoConsole::new(input.as_bytes(), OutputBuffer::new());
+    let mut vm = Lc3Vm::with_console(console);
+    for source in sources {
+        let assembly = assemble(source).unwrap();
+        for (address, word) in (assembly.origin..).zip(&assembly.words) {
+            vm.memory.write(address, *word);
+        }
+    }
+    vm
+}
+
+#[test]
+fn test_keyboard_interrupt() {
+    let main = "
+        .ORIG x3000
+LOOP    ADD R1, R1, #0
+        BRz LOOP
+        HALT
+        .END";
+    let handler = "
+        .ORIG x1000
+        LDI R1, KBDR
+        RTI
+KBDR    .FILL xFE02
+        .END";
+    let mut vm = vm_with_programs("a", &[main, handler]);
+    vm.memory
+        .write(INTERRUPT_VECTOR_TABLE + KEYBOARD_VECTOR as u16, 0x1000);
+    vm.set_reg_val_by_id(6, 0xfd00);
+    vm.memory.write(KBSR, 0x4000);
+
+    let step = vm.step().unwrap();
+    assert_eq!(step.interrupt, Some(KEYBOARD_VECTOR));
+    assert_eq!(step.pc, 0x1000);
+    assert_eq!(vm.registers.privilege_mode(), PrivilegeMode::Supervisor);
+    assert_eq!(vm.registers.priority_level(), KEYBOARD_PRIORITY);
+    assert_eq!(vm.get_reg_val_by_id(6), Registers::DEFAULT_SAVED_SSP - 2);
+    assert_eq!(vm.registers.saved_usp(), 0xfd00);
+
+    // Run until the handler returns with RTI
+    vm.run_until(|_, step| step.pc == 0x1001).unwrap();
+    assert_eq!(vm.get_reg_val_by_id(1), 'a' as u16);
+    assert_eq!(vm.registers.privilege_mode(), PrivilegeMode::User);
+    assert_eq!(vm.registers.priority_level(), 0);
+    assert_eq!(vm.get_reg_val_by_id(6), 0xfd00);
+    assert_eq!(vm.registers.saved_ssp(), Registers::DEFAULT_SAVED_SSP);
+    // Reading KBDR cleared the ready bit
+    assert_eq!(vm.memory.peek(KBSR), 0x4000);
+}
+
+#[test]
+fn test_keyboard_interrupt_disabled() {
+    let mut vm = vm_with_programs("a", &[]);
+    assert_eq!(vm.check_interrupts(), None);
+    // The character is still waiting to be read
+    assert_eq!(vm.memory.read(KBSR), 0x8000);
+}
+
+#[test]
+fn test_interrupt_priority() {
+    let mut vm = vm_with_programs("a", &[]);
+    vm.memory.write(KBSR, 0x4000);
+    vm.registers.set_privilege_mode(PrivilegeMode::Supervisor);
+    vm.registers.set_priority_level(KEYBOARD_PRIORITY);
+    assert_eq!(vm.check_interrupts(), None);
+    vm.registers.set_priority_level(KEYBOARD_PRIORITY - 1);
+    assert_eq!(vm.check_interrupts(), Some(KEYBOARD_VECTOR));
+}
+
+#[test]
+fn test_initiate_interrupt_in_supervisor_mode() {
+    let mut vm = Lc3Vm::new();
+    vm.memory.write(INTERRUPT_VECTOR_TABLE + 0x42, 0x0600);
+    vm.registers.set_psr(0x0104);
+    vm.registers.set_program_counter(0x0500);
+    vm.set_reg_val_by_id(6, 0x2f00);
+    vm.initiate_interrupt(0x42, None);
+
+    assert_eq!(vm.registers.program_counter(), 0x0600);
+    // The supervisor stack stays in use, and the priority is unchanged
+    assert_eq!(vm.get_reg_val_by_id(6), 0x2efe);
+    assert_eq!(vm.registers.psr(), 0x0104);
+    assert_eq!(vm.memory.read(0x2efe), 0x0500);
+    assert_eq!(vm.memory.read(0x2eff), 0x0104);
+}
+
+/// Returns a VM with `instr` at `Lc3Vm::DEFAULT_PC_START`, and a service routine at
+/// x1000 for the exception `vector`
+fn vm_with_exception_handler(instr: u16, vector: u8) -> Lc3Vm {
+    let mut vm = Lc3Vm::new();
+    vm.memory.write(Lc3Vm::DEFAULT_PC_START, instr);
+    vm.memory
+        .write(INTERRUPT_VECTOR_TABLE + vector as u16, 0x1000);
+    vm
+}
+
+/// Checks that the service routine at x1000 of the exception `vector` was started,
+/// and the user mode PC and PSR were saved on the supervisor stack
+fn assert_exception_taken(vm: &Lc3Vm, step: &Step, vector: u8, saved_pc: u16) {
+    assert_eq!(step.exception, Some(vector));
+    assert_eq!(step.next_pc, 0x1000);
+    assert_eq!(vm.registers.privilege_mode(), PrivilegeMode::Supervisor);
+    let stack_pointer = Registers::DEFAULT_SAVED_SSP - 2;
+    assert_eq!(vm.get_reg_val_by_id(6), stack_pointer);
+    assert_eq!(vm.memory.peek(stack_pointer), saved_pc);
+    assert_eq!(vm.memory.peek(stack_pointer + 1), Registers::DEFAULT_PSR);
+}
+
+#[test]
+fn test_illegal_opcode_exception() {
+    let mut vm = vm_with_exception_handler(0xd000, ILLEGAL_OPCODE_VECTOR);
+    let step = vm.step().unwrap();
+    assert_exception_taken(&vm, &step, ILLEGAL_OPCODE_VECTOR, 0x3001);
+}
+
+#[test]
+fn test_privilege_violation_exception() {
+    // RTI
+    let mut vm = vm_with_exception_handler(0x8000, PRIVILEGE_VIOLATION_VECTOR);
+    let step = vm.step().unwrap();
+    assert_exception_taken(&vm, &step, PRIVILEGE_VIOLATION_VECTOR, 0x3001);
+}
+
+#[test]
+#[allow(clippy::unusual_byte_groupings)]
+fn test_access_violation_exception() {
+    // LDR R0, R1, #0
+    let instr = 0b0110_000_001_000000;
+    let mut vm = vm_with_exception_handler(instr, ACCESS_VIOLATION_VECTOR);
+    vm.set_reg_val_by_id(1, KBSR);
+    vm.set_reg_val_by_id(0, 0x1234);
+    let step = vm.step().unwrap();
+    assert_exception_taken(&vm, &step, ACCESS_VIOLATION_VECTOR, 0x3001);
+    // The load was not completed
+    assert_eq!(vm.get_reg_val_by_id(0), 0x1234);
+}
+
+#[test]
+#[allow(clippy::unusual_byte_groupings)]
+fn test_access_violation_without_handler() {
+    let mut vm = Lc3Vm::new();
+    // STR R0, R1, #0
+    vm.memory.write(0x3000, 0b0111_000_001_000000);
+    vm.set_reg_val_by_id(0, 0xbeef);
+    vm.set_reg_val_by_id(1, 0x2fff);
+    let err = vm.step().unwrap_err();
+    assert!(matches!(
+        err,
+        VmError::AccessViolation {
+            pc: 0x3000,
+            address: 0x2fff,
+            ..
+        }
+    ));
+    assert_eq!(vm.memory.peek(0x2fff), 0);
+
+    // The access is allowed when privilege is ignored
+    vm.set_ignore_privilege(true);
+    vm.registers.set_program_counter(0x3000);
+    vm.step().unwrap();
+    assert_eq!(vm.memory.peek(0x2fff), 0xbeef);
+}
+
+#[test]
+#[allow(clippy::unusual_byte_groupings)]
+fn test_access_violation_without_os() {
+    // LDI R0, #1, which loads the address x0000 from x3002
+    let mut vm = vm_with_exception_handler(0b1010_000_000000001, ACCESS_VIOLATION_VECTOR);
+    assert_eq!(vm.registers.privilege_mode(), PrivilegeMode::User);
+    vm.memory.write(0x3002, 0x0000);
+    vm.set_reg_val_by_id(0, 0x1234);
+    let step = vm.step().unwrap();
+    assert_exception_taken(&vm, &step, ACCESS_VIOLATION_VECTOR, 0x3001);
+    assert_eq!(vm.get_reg_val_by_id(0), 0x1234);
+}
+
+#[test]
+fn test_access_violation_on_fetch() {
+    let mut vm = vm_with_exception_handler(0, ACCESS_VIOLATION_VECTOR);
+    vm.registers.set_program_counter(0x2000);
+    let step = vm.step().unwrap();
+    assert_eq!(step.pc, 0x2000);
+    assert_exception_taken(&vm, &step, ACCESS_VIOLATION_VECTOR, 0x2000);
+
+    // In supervisor mode, system space can be executed
+    let mut vm = Lc3Vm::new();
+    vm.registers.set_privilege_mode(PrivilegeMode::Supervisor);
+    vm.registers.set_program_counter(0x2000);
+    let step = vm.step().unwrap();
+    assert_eq!(step.exception, None);
+    assert_eq!(step.next_pc, 0x2001);
+}
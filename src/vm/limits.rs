@@ -0,0 +1,39 @@
+use std::time::Duration;
+
+/// Limits on how long `Lc3Vm::run` is allowed to execute a program for. Limits that
+/// are `None` are not enforced
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct RunLimits {
+    /// The maximum number of instructions to execute
+    pub max_instructions: Option<u64>,
+    /// The maximum amount of time to spend executing instructions
+    pub max_duration: Option<Duration>,
+}
+
+impl RunLimits {
+    /// Returns limits that allow a program to run forever
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum number of instructions to execute
+    pub fn with_max_instructions(mut self, max_instructions: u64) -> Self {
+        self.max_instructions = Some(max_instructions);
+        self
+    }
+
+    /// Sets the maximum amount of time to spend executing instructions
+    pub fn with_max_duration(mut self, max_duration: Duration) -> Self {
+        self.max_duration = Some(max_duration);
+        self
+    }
+}
+
+/// The limit that caused `Lc3Vm::run` to stop executing a program
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Limit {
+    /// The given number of instructions were executed
+    Instructions(u64),
+    /// The given amount of time elapsed
+    Duration(Duration),
+}
@@ -0,0 +1,239 @@
+//! This module contains the loader of compiled LC3 programs. A program in the `.obj`
+//! format starts with its origin, the address where it is loaded, followed by the
+//! words of the program. All words are stored in big endian. Programs can also be
+//! stored in the `.hex` and `.bin` text formats, see `ProgramFormat`
+
+#[cfg(test)]
+mod tests;
+mod text;
+
+use std::{
+    fs,
+    io::Read,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    error::{LoadError, VmError},
+    Lc3Vm,
+};
+
+/// The formats that compiled LC3 programs are stored in. Every format starts with the
+/// origin of the program, followed by its words
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ProgramFormat {
+    /// Words stored in big endian
+    Obj,
+    /// Text with one word per line, written as 4 hexadecimal digits
+    Hex,
+    /// Text with one word per line, written as 16 binary digits
+    Bin,
+}
+
+impl ProgramFormat {
+    /// Returns the format stored in files with the extension `extension`
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "obj" => Some(Self::Obj),
+            "hex" => Some(Self::Hex),
+            "bin" => Some(Self::Bin),
+            _ => None,
+        }
+    }
+
+    /// Returns the format of the file at `path` based on its extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?;
+        Self::from_extension(extension)
+    }
+
+    /// Guesses the format of a program from its contents. Text whose first word is
+    /// written with 16 binary digits or 4 hexadecimal digits is in a text format, and
+    /// anything else is in the `.obj` format
+    pub fn detect(bytes: &[u8]) -> Self {
+        let is_text = bytes
+            .iter()
+            .all(|byte| byte.is_ascii_graphic() || byte.is_ascii_whitespace());
+        if !is_text {
+            return Self::Obj;
+        }
+        let source = String::from_utf8_lossy(bytes);
+        match text::first_word(&source) {
+            Some(word) if text::is_word(word, Self::Bin) => Self::Bin,
+            Some(word) if text::is_word(word, Self::Hex) => Self::Hex,
+            _ => Self::Obj,
+        }
+    }
+}
+
+/// A compiled LC3 program which has been validated, and fits into memory
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ProgramImage {
+    origin: u16,
+    words: Vec<u16>,
+}
+
+impl ProgramImage {
+    /// Creates a program which is loaded at `origin`. The program must contain at
+    /// least one word, and fit into memory
+    pub fn new(origin: u16, words: Vec<u16>) -> Result<Self, LoadError> {
+        if words.is_empty() {
+            return Err(LoadError::Empty);
+        }
+        let capacity = 0x10000 - usize::from(origin);
+        if words.len() > capacity {
+            return Err(LoadError::TooLarge {
+                origin,
+                len: words.len(),
+            });
+        }
+        Ok(Self { origin, words })
+    }
+
+    /// Parses a program in the `.obj` format
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, LoadError> {
+        if !bytes.len().is_multiple_of(2) {
+            return Err(LoadError::OddLength { len: bytes.len() });
+        }
+        let mut words = bytes
+            .chunks_exact(2)
+            .map(|chunk| u16::from_be_bytes([chunk[0], chunk[1]]));
+        let origin = words.next().ok_or(LoadError::Empty)?;
+        Self::new(origin, words.collect())
+    }
+
+    /// Parses a program stored in `format`. Text formats may contain blank lines and
+    /// comments, which start with `;`
+    pub fn parse(bytes: &[u8], format: ProgramFormat) -> Result<Self, LoadError> {
+        match format {
+            ProgramFormat::Obj => Self::from_bytes(bytes),
+            ProgramFormat::Hex | ProgramFormat::Bin => {
+                let (origin, words) = text::parse(&String::from_utf8_lossy(bytes), format)?;
+                Self::new(origin, words)
+            }
+        }
+    }
+
+    /// Returns the program stored in `format`
+    pub fn to_bytes(&self, format: ProgramFormat) -> Vec<u8> {
+        match format {
+            ProgramFormat::Obj => std::iter::once(self.origin)
+                .chain(self.words.iter().copied())
+                .flat_map(u16::to_be_bytes)
+                .collect(),
+            ProgramFormat::Hex | ProgramFormat::Bin => {
+                text::write(self.origin, &self.words, format).into_bytes()
+            }
+        }
+    }
+
+    /// Reads a program in the `.obj` format from `reader`
+    pub fn from_reader(mut reader: impl Read) -> Result<Self, LoadError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).map_err(LoadError::Io)?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Reads a program from the file at `file_path`. The format of the program is
+    /// determined by the file's extension, or by its contents if the extension is not
+    /// one of the formats
+    pub fn from_path(file_path: &Path) -> Result<Self, LoadError> {
+        let bytes = fs::read(file_path).map_err(LoadError::Io)?;
+        let format =
+            ProgramFormat::from_path(file_path).unwrap_or_else(|| ProgramFormat::detect(&bytes));
+        Self::parse(&bytes, format)
+    }
+
+    /// The address where the program is loaded
+    pub fn origin(&self) -> u16 {
+        self.origin
+    }
+
+    /// The words of the program, which are loaded starting at the origin
+    pub fn words(&self) -> &[u16] {
+        &self.words
+    }
+
+    /// The memory locations that the program is loaded into
+    pub fn range(&self) -> RangeInclusive<u16> {
+        // The program is never empty, and fits into memory
+        let last_offset = (self.words.len() - 1) as u16;
+        self.origin..=self.origin + last_offset
+    }
+}
+
+impl Lc3Vm {
+    /// Load a compiled LC3 program for execution, and set the program counter to the
+    /// start of the program. Returns the memory locations the program was loaded into.
+    ///
+    /// A given LC3 program will have its first 16 bits set to the memory address
+    /// where the start of the program instructions should be loaded to. Subsequent
+    /// bytes are then the program instructions
+    pub fn load_program(&mut self, file_path: &Path) -> Result<RangeInclusive<u16>, VmError> {
+        let image = ProgramImage::from_path(file_path)?;
+        Ok(self.load_image(&image))
+    }
+
+    /// Loads a compiled LC3 program from `reader` like `Lc3Vm::load_program`
+    pub fn load_from_reader(&mut self, reader: impl Read) -> Result<RangeInclusive<u16>, VmError> {
+        let image = ProgramImage::from_reader(reader)?;
+        Ok(self.load_image(&image))
+    }
+
+    /// Loads a compiled LC3 program from `bytes` like `Lc3Vm::load_program`
+    pub fn load_bytes(&mut self, bytes: &[u8]) -> Result<RangeInclusive<u16>, VmError> {
+        let image = ProgramImage::from_bytes(bytes)?;
+        Ok(self.load_image(&image))
+    }
+
+    /// Loads a program into memory, and sets the program counter to its origin.
+    /// Returns the memory locations the program was loaded into
+    pub fn load_image(&mut self, image: &ProgramImage) -> RangeInclusive<u16> {
+        for (address, word) in image.range().zip(&image.words) {
+            self.memory.write(address, *word);
+        }
+        self.registers.set_program_counter(image.origin);
+        image.range()
+    }
+
+    /// Loads several compiled LC3 programs in order, and sets the program counter to
+    /// the start of the first one. Returns the memory locations each program was
+    /// loaded into. Nothing is loaded if one of the programs cannot be read, or if two
+    /// programs would be loaded into the same memory locations
+    pub fn load_programs<P: AsRef<Path>>(
+        &mut self,
+        file_paths: &[P],
+    ) -> Result<Vec<RangeInclusive<u16>>, VmError> {
+        let mut images: Vec<(PathBuf, ProgramImage)> = Vec::new();
+        for file_path in file_paths {
+            let file_path = file_path.as_ref();
+            let image = ProgramImage::from_path(file_path)?;
+            let range = image.range();
+            for (other_path, other_image) in &images {
+                let other_range = other_image.range();
+                let start = *range.start().max(other_range.start());
+                let end = *range.end().min(other_range.end());
+                if start <= end {
+                    return Err(LoadError::Overlap {
+                        first: other_path.clone(),
+                        second: file_path.to_path_buf(),
+                        start,
+                        end,
+                    }
+                    .into());
+                }
+            }
+            images.push((file_path.to_path_buf(), image));
+        }
+
+        let ranges = images
+            .iter()
+            .map(|(_, image)| self.load_image(image))
+            .collect();
+        if let Some((_, image)) = images.first() {
+            self.registers.set_program_counter(image.origin);
+        }
+        Ok(ranges)
+    }
+}
@@ -0,0 +1,161 @@
+use std::io::Write;
+
+use super::*;
+
+/// Converts words into the bytes of an object file
+fn obj_bytes(words: &[u16]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_be_bytes()).collect()
+}
+
+#[test]
+fn test_from_bytes() {
+    let image = ProgramImage::from_bytes(&[0x30, 0x00, 0x2b, 0x2e, 0x12, 0x34]).unwrap();
+    assert_eq!(image.origin(), 0x3000);
+    assert_eq!(image.words(), &[0x2b2e, 0x1234]);
+    assert_eq!(image.range(), 0x3000..=0x3001);
+}
+
+#[test]
+fn test_from_bytes_empty() {
+    assert!(matches!(
+        ProgramImage::from_bytes(&[]),
+        Err(LoadError::Empty)
+    ));
+    // An origin without any words
+    assert!(matches!(
+        ProgramImage::from_bytes(&[0x30, 0x00]),
+        Err(LoadError::Empty)
+    ));
+}
+
+#[test]
+fn test_from_bytes_odd_length() {
+    assert!(matches!(
+        ProgramImage::from_bytes(&[0x30, 0x00, 0x12]),
+        Err(LoadError::OddLength { len: 3 })
+    ));
+}
+
+#[test]
+fn test_from_bytes_too_large() {
+    let mut words = vec![0x3000];
+    words.extend(vec![0; 0xd001]);
+    assert!(matches!(
+        ProgramImage::from_bytes(&obj_bytes(&words)),
+        Err(LoadError::TooLarge {
+            origin: 0x3000,
+            len: 0xd001
+        })
+    ));
+
+    // A program that fills the rest of memory fits
+    words.pop();
+    let image = ProgramImage::from_bytes(&obj_bytes(&words)).unwrap();
+    assert_eq!(image.range(), 0x3000..=0xffff);
+}
+
+#[test]
+fn test_from_reader() {
+    let bytes = obj_bytes(&[0x4000, 0xbeef]);
+    let image = ProgramImage::from_reader(bytes.as_slice()).unwrap();
+    assert_eq!(image.range(), 0x4000..=0x4000);
+    assert_eq!(image.words(), &[0xbeef]);
+}
+
+#[test]
+fn test_load_bytes() {
+    let mut vm = Lc3Vm::new();
+    let range = vm
+        .load_bytes(&obj_bytes(&[0x4000, 0x1111, 0x2222]))
+        .unwrap();
+    assert_eq!(range, 0x4000..=0x4001);
+    assert_eq!(vm.memory.read(0x4000), 0x1111);
+    assert_eq!(vm.memory.read(0x4001), 0x2222);
+    assert_eq!(vm.registers.program_counter(), 0x4000);
+
+    let err = vm.load_bytes(&[0x40]).unwrap_err();
+    assert!(matches!(
+        err,
+        VmError::Load(LoadError::OddLength { len: 1 })
+    ));
+}
+
+#[test]
+fn test_load_top_of_memory() {
+    let mut vm = Lc3Vm::new();
+    let range = vm
+        .load_from_reader(obj_bytes(&[0xfdfe, 0x1111, 0x2222]).as_slice())
+        .unwrap();
+    assert_eq!(range, 0xfdfe..=0xfdff);
+    assert_eq!(vm.memory.read(0xfdff), 0x2222);
+
+    // Loading a word at xFFFF does not overflow the address
+    let range = vm.load_bytes(&obj_bytes(&[0xffff, 0x8000])).unwrap();
+    assert_eq!(range, 0xffff..=0xffff);
+}
+
+#[test]
+fn test_parse_hex() {
+    let source = "; A program that halts
+3000    ; origin
+
+5020
+f025 ; HALT
+";
+    let image = ProgramImage::parse(source.as_bytes(), ProgramFormat::Hex).unwrap();
+    assert_eq!(image.origin(), 0x3000);
+    assert_eq!(image.words(), &[0x5020, 0xf025]);
+}
+
+#[test]
+fn test_parse_bin() {
+    let source = "0011000000000000\n0101000000100000 ; AND R0, R0, #0\n1111000000100101\n";
+    let image = ProgramImage::parse(source.as_bytes(), ProgramFormat::Bin).unwrap();
+    assert_eq!(image.origin(), 0x3000);
+    assert_eq!(image.words(), &[0x5020, 0xf025]);
+}
+
+#[test]
+fn test_parse_text_errors() {
+    let err = ProgramImage::parse(b"3000\n5020\n12G4\n", ProgramFormat::Hex).unwrap_err();
+    assert!(matches!(err, LoadError::InvalidWord { line: 3, ref word } if word == "12G4"));
+    assert_eq!(err.to_string(), "invalid word \"12G4\" on line 3");
+
+    let err = ProgramImage::parse(b"0011000000000000\n0101\n", ProgramFormat::Bin).unwrap_err();
+    assert!(matches!(err, LoadError::InvalidWord { line: 2, .. }));
+    assert!(matches!(
+        ProgramImage::parse(b"; only a comment\n3000\n", ProgramFormat::Hex),
+        Err(LoadError::Empty)
+    ));
+}
+
+#[test]
+fn test_convert_formats() {
+    let image = ProgramImage::new(0x3000, vec![0x5020, 0xf025]).unwrap();
+    assert_eq!(
+        image.to_bytes(ProgramFormat::Obj),
+        vec![0x30, 0x00, 0x50, 0x20, 0xf0, 0x25]
+    );
+    assert_eq!(image.to_bytes(ProgramFormat::Hex), b"3000\n5020\nF025\n");
+    for format in [ProgramFormat::Obj, ProgramFormat::Hex, ProgramFormat::Bin] {
+        let bytes = image.to_bytes(format);
+        assert_eq!(ProgramFormat::detect(&bytes), format);
+        assert_eq!(ProgramImage::parse(&bytes, format).unwrap(), image);
+    }
+}
+
+#[test]
+fn test_from_path_format() {
+    let mut hex_file = tempfile::Builder::new().suffix(".HEX").tempfile().unwrap();
+    hex_file.write_all(b"4000\n1234\n").unwrap();
+    let image = ProgramImage::from_path(hex_file.path()).unwrap();
+    assert_eq!(image.range(), 0x4000..=0x4000);
+
+    // The format of files with other extensions is detected from their contents
+    let mut txt_file = tempfile::Builder::new().suffix(".txt").tempfile().unwrap();
+    txt_file
+        .write_all(b"0100000000000000\n0001001000110100\n")
+        .unwrap();
+    let image = ProgramImage::from_path(txt_file.path()).unwrap();
+    assert_eq!(image.words(), &[0x1234]);
+}
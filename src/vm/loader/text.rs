@@ -0,0 +1,64 @@
+//! Parses and writes the `.hex` and `.bin` text formats, which contain one word per
+//! line. Comments start with `;`, and blank lines are ignored
+
+use super::ProgramFormat;
+use crate::vm::error::LoadError;
+
+/// Parses a program in a text format. Returns the origin and the words of the program
+pub(super) fn parse(source: &str, format: ProgramFormat) -> Result<(u16, Vec<u16>), LoadError> {
+    let mut words = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let word = strip_comment(line);
+        if word.is_empty() {
+            continue;
+        }
+        if !is_word(word, format) {
+            return Err(LoadError::InvalidWord {
+                line: index + 1,
+                word: word.to_string(),
+            });
+        }
+        // The digits have been checked, so the word can always be parsed
+        let radix = if format == ProgramFormat::Bin { 2 } else { 16 };
+        words.push(u16::from_str_radix(word, radix).unwrap());
+    }
+    if words.is_empty() {
+        return Err(LoadError::Empty);
+    }
+    let origin = words.remove(0);
+    Ok((origin, words))
+}
+
+/// Writes a program in a text format, starting with its origin
+pub(super) fn write(origin: u16, words: &[u16], format: ProgramFormat) -> String {
+    std::iter::once(&origin)
+        .chain(words)
+        .map(|word| match format {
+            ProgramFormat::Bin => format!("{word:016b}\n"),
+            _ => format!("{word:04X}\n"),
+        })
+        .collect()
+}
+
+/// Returns the first word of a program in a text format
+pub(super) fn first_word(source: &str) -> Option<&str> {
+    source
+        .lines()
+        .map(strip_comment)
+        .find(|word| !word.is_empty())
+}
+
+/// Returns `true` if `word` is a word written in the text format `format`
+pub(super) fn is_word(word: &str, format: ProgramFormat) -> bool {
+    match format {
+        ProgramFormat::Bin => word.len() == 16 && word.chars().all(|ch| matches!(ch, '0' | '1')),
+        ProgramFormat::Hex => word.len() == 4 && word.chars().all(|ch| ch.is_ascii_hexdigit()),
+        ProgramFormat::Obj => false,
+    }
+}
+
+/// Removes the comment and surrounding whitespace from a line
+fn strip_comment(line: &str) -> &str {
+    let code = line.split_once(';').map_or(line, |(code, _)| code);
+    code.trim()
+}
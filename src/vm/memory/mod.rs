@@ -1,10 +1,15 @@
 #[cfg(test)]
 mod tests;
 
-use std::io::{stdin, stdout, Read, Write};
+use std::fmt;
 
 use ascii::AsciiChar;
 
+use super::{
+    console::{Console, StdConsole},
+    error::Fault,
+};
+
 /// Maximum size a `u16` can hold
 const MEMORY_MAX: usize = 1 << 16;
 
@@ -13,7 +18,9 @@ const MEMORY_MAX: usize = 1 << 16;
 /// specific memory address specified by each of these registers.
 pub enum DeviceRegister {
     /// Keyboard status register. The ready bit (bit [15]) indicates if the keyboard has
-    /// received a new character. Mapped to `0xFE00`.
+    /// received a new character, and stays set until KBDR is read. When the interrupt
+    /// enable bit (bit [14]) is set, the keyboard requests an interrupt when a new
+    /// character is received. Mapped to `0xFE00`.
     Kbsr = 0,
     /// Keyboard data register. Bits [7:0] contain the last character typed on the
     /// keyboard. Mapped to `0xFE02`.
@@ -47,6 +54,30 @@ impl DeviceRegister {
             _ => None,
         }
     }
+
+    /// Returns the memory mapped address of the register
+    pub fn address(self) -> u16 {
+        match self {
+            Self::Kbsr => Self::KBSR_ADDR,
+            Self::Kbdr => Self::KBDR_ADDR,
+            Self::Dsr => Self::DSR_ADDR,
+            Self::Ddr => Self::DDR_ADDR,
+            Self::Mcr => Self::MCR_ADDR,
+        }
+    }
+}
+
+impl fmt::Display for DeviceRegister {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Kbsr => "KBSR",
+            Self::Kbdr => "KBDR",
+            Self::Dsr => "DSR",
+            Self::Ddr => "DDR",
+            Self::Mcr => "MCR",
+        };
+        write!(f, "{name}")
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -64,35 +95,74 @@ impl MemorySlice {
 
 /// A struct containing the values of certain device registers where the value has to be saved
 struct MmapRegisters {
+    kbsr: MemorySlice,
     kbdr: MemorySlice,
     mcr: MemorySlice,
 }
 
 impl MmapRegisters {
     const MCR_DEFAULT_VALUE: u16 = 0x8000;
+    const KBSR_READY_BIT: u16 = 1 << 15;
+    const KBSR_INTERRUPT_ENABLE_BIT: u16 = 1 << 14;
+
     fn new() -> Self {
         Self {
+            kbsr: MemorySlice(0),
             kbdr: MemorySlice(0),
             mcr: MemorySlice(Self::MCR_DEFAULT_VALUE),
         }
     }
 }
 
+/// The memory of the LC3 VM, which contains `MEMORY_MAX` addressable 16 bit words.
+/// Some of the addresses are mapped to device registers, see `DeviceRegister`.
 pub struct Memory {
     mem_arr: [MemorySlice; MEMORY_MAX],
     mmap_registers: MmapRegisters,
+    /// The console backing the keyboard and display device registers
+    console: Box<dyn Console>,
+    /// The error raised by the most recent failed device register access, which is
+    /// reported by the VM once the current instruction completes
+    device_fault: Option<Fault>,
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Memory {
+    /// Creates a zeroed memory, with the machine control register set so that
+    /// the clock is enabled. The keyboard and display use the standard input and
+    /// output of the process
     pub fn new() -> Self {
+        Self::with_console(StdConsole::new())
+    }
+
+    /// Creates a zeroed memory like `Memory::new`, where the keyboard and display
+    /// use the given console
+    pub fn with_console(console: impl Console + 'static) -> Self {
         let mem_arr: [MemorySlice; MEMORY_MAX] = [MemorySlice(0); MEMORY_MAX];
         let mmap_registers = MmapRegisters::new();
         Self {
             mem_arr,
             mmap_registers,
+            console: Box::new(console),
+            device_fault: None,
         }
     }
 
+    /// Returns the console backing the keyboard and display
+    pub fn console_mut(&mut self) -> &mut dyn Console {
+        self.console.as_mut()
+    }
+
+    /// Replaces the console backing the keyboard and display
+    pub fn set_console(&mut self, console: impl Console + 'static) {
+        self.console = Box::new(console);
+    }
+
     /// Reads the value at the given memory address. If the address corresponds to
     /// a memory mapped device register, the read action of that specific register
     /// will be performed
@@ -107,13 +177,26 @@ impl Memory {
         }
     }
 
+    /// Returns the value at the given memory address without performing the read
+    /// action of memory mapped device registers. This is used to inspect memory
+    /// without affecting the running program, for example in a debugger
+    pub fn peek(&self, address: u16) -> u16 {
+        match DeviceRegister::from_address(address) {
+            None => self.mem_arr[address as usize].read(),
+            // The keyboard is not checked for new input here, so only a character that
+            // has already been received is reported as ready
+            Some(DeviceRegister::Kbsr) => self.mmap_registers.kbsr.read(),
+            Some(DeviceRegister::Kbdr) => self.mmap_registers.kbdr.read(),
+            Some(DeviceRegister::Dsr) => self.read_dsr(),
+            Some(DeviceRegister::Ddr) => self.read_ddr(),
+            Some(DeviceRegister::Mcr) => self.read_mcr(),
+        }
+    }
+
     /// A handler function that calls the correct function to read the given `DeviceRegister`
     fn read_device_register(&mut self, device_register: DeviceRegister) -> u16 {
         match device_register {
-            DeviceRegister::Kbsr => {
-                let mut stdin = stdin().lock();
-                self.read_kbsr(&mut stdin)
-            }
+            DeviceRegister::Kbsr => self.read_kbsr(),
             DeviceRegister::Kbdr => self.read_kbdr(),
             DeviceRegister::Dsr => self.read_dsr(),
             DeviceRegister::Ddr => self.read_ddr(),
@@ -133,12 +216,27 @@ impl Memory {
         }
     }
 
+    /// Sets the value at the given memory address without performing the write action
+    /// of memory mapped device registers, so nothing is printed and the clock is not
+    /// stopped. DSR and DDR hold no value, so writes to them are ignored
+    pub fn poke(&mut self, address: u16, value: u16) {
+        match DeviceRegister::from_address(address) {
+            None => self.mem_arr[address as usize].write(value),
+            Some(DeviceRegister::Kbsr) => self.mmap_registers.kbsr.write(value),
+            Some(DeviceRegister::Kbdr) => self.mmap_registers.kbdr.write(value),
+            Some(DeviceRegister::Mcr) => self.mmap_registers.mcr.write(value),
+            Some(DeviceRegister::Dsr | DeviceRegister::Ddr) => (),
+        }
+    }
+
     fn write_device_register(&mut self, device_register: DeviceRegister, value: u16) {
         match device_register {
             DeviceRegister::Ddr => {
-                let mut stdout = stdout().lock();
-                self.write_ddr(value, &mut stdout);
+                if let Err(fault) = self.write_ddr(value) {
+                    self.device_fault = Some(fault);
+                }
             }
+            DeviceRegister::Kbsr => self.write_kbsr(value),
             DeviceRegister::Mcr => self.write_mcr(value),
             // Other registers don't have any specified write behaviour, so nothing
             // will happen in our implementation
@@ -146,34 +244,84 @@ impl Memory {
         }
     }
 
+    /// Returns `true` if the clock enable bit of the machine control register is cleared
     pub fn mcr_is_cleared(&self) -> bool {
         let mcr_value = self.mmap_registers.mcr.read();
         let clock_bit = mcr_value >> 15;
         clock_bit == 0
     }
 
+    /// Clears the machine control register, which stops instruction processing
     pub fn clear_mcr(&mut self) {
         self.mmap_registers.mcr.write(0);
     }
 
-    fn read_kbsr(&mut self, input_reader: &mut impl Read) -> u16 {
-        // We need to check if the input has any new character
-        let mut buf: [u8; 1] = [0];
-        // If read_exact returns Err, then it means there's nothing
-        match input_reader.read_exact(&mut buf) {
-            Err(_) => 0,
-            Ok(_) => {
-                // If the read was successfuly, then we must save the character we
-                // read to KBDR because it's no longer available in the input.
-                // We reuse the actual memory address reserved for Kbdr since it's
-                // reserved and nothing else can read it (normally)
-                self.mmap_registers.kbdr.write(buf[0] as u16);
-                0x8000
-            }
+    /// Returns the error raised by the most recent failed device register access, if
+    /// there is one, and clears it
+    pub(crate) fn take_fault(&mut self) -> Option<Fault> {
+        self.device_fault.take()
+    }
+
+    /// Returns `true` if the keyboard is requesting an interrupt, which happens when
+    /// interrupts are enabled in KBSR and a character has been received
+    pub(crate) fn keyboard_interrupt_requested(&mut self) -> bool {
+        let kbsr = self.mmap_registers.kbsr.read();
+        if kbsr & MmapRegisters::KBSR_INTERRUPT_ENABLE_BIT == 0 {
+            return false;
         }
+        self.poll_keyboard();
+        self.mmap_registers.kbsr.read() & MmapRegisters::KBSR_READY_BIT != 0
+    }
+
+    /// Reads a character from the keyboard, blocking until one is typed. A character
+    /// that has already been received by KBDR is returned first
+    pub(crate) fn read_key(&mut self) -> Result<u8, Fault> {
+        let kbsr = self.mmap_registers.kbsr.read();
+        if kbsr & MmapRegisters::KBSR_READY_BIT != 0 {
+            return Ok(self.read_kbdr() as u8);
+        }
+        Ok(self.console.read_char()?)
+    }
+
+    /// Checks the keyboard for a new character without waiting for one to be typed.
+    /// A new character is only received once the previous one has been read from KBDR
+    fn poll_keyboard(&mut self) {
+        let kbsr = self.mmap_registers.kbsr.read();
+        if kbsr & MmapRegisters::KBSR_READY_BIT != 0 {
+            return;
+        }
+        // Errors are treated like no character being available
+        if let Ok(Some(char_byte)) = self.console.poll_char() {
+            // The character is no longer available from the console, so it is kept
+            // in KBDR until the program reads it
+            self.mmap_registers.kbdr.write(char_byte as u16);
+            self.mmap_registers
+                .kbsr
+                .write(kbsr | MmapRegisters::KBSR_READY_BIT);
+        }
+    }
+
+    fn read_kbsr(&mut self) -> u16 {
+        self.poll_keyboard();
+        self.mmap_registers.kbsr.read()
+    }
+
+    /// Only the interrupt enable bit of KBSR can be written
+    fn write_kbsr(&mut self, value: u16) {
+        let kbsr = self.mmap_registers.kbsr.read();
+        let enable_bit = MmapRegisters::KBSR_INTERRUPT_ENABLE_BIT;
+        self.mmap_registers
+            .kbsr
+            .write((kbsr & !enable_bit) | (value & enable_bit));
     }
 
-    fn read_kbdr(&self) -> u16 {
+    /// Reading KBDR clears the ready bit of KBSR, so that the next character can be
+    /// received
+    fn read_kbdr(&mut self) -> u16 {
+        let kbsr = self.mmap_registers.kbsr.read();
+        self.mmap_registers
+            .kbsr
+            .write(kbsr & !MmapRegisters::KBSR_READY_BIT);
         self.mmap_registers.kbdr.read()
     }
 
@@ -204,9 +352,11 @@ impl Memory {
         self.mmap_registers.mcr.write(value);
     }
 
-    fn write_ddr(&mut self, value: u16, output_writer: &mut impl Write) {
+    fn write_ddr(&mut self, value: u16) -> Result<(), Fault> {
         let byte_slice = value.to_be_bytes();
-        let ascii_char = AsciiChar::from_ascii(byte_slice[1]).unwrap();
-        write!(output_writer, "{}", ascii_char).unwrap();
+        let ascii_char =
+            AsciiChar::from_ascii(byte_slice[1]).map_err(|_| Fault::InvalidCharacter(value))?;
+        self.console.write_chars(&[ascii_char.as_byte()])?;
+        Ok(())
     }
 }
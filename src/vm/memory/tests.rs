@@ -1,14 +1,16 @@
 use ascii::AsciiChar;
 
-use crate::vm::Lc3Vm;
+use crate::vm::{
+    console::{IoConsole, OutputBuffer},
+    Lc3Vm,
+};
 
 use super::MmapRegisters;
 
 #[test]
 fn test_read_kbsr() {
-    let mut vm = Lc3Vm::new();
-    let mut input = "y".as_bytes();
-    let value = vm.memory.read_kbsr(&mut input);
+    let mut vm = Lc3Vm::with_console(IoConsole::new("y".as_bytes(), OutputBuffer::new()));
+    let value = vm.memory.read_kbsr();
     assert_eq!(value, 0x8000);
 
     // Test that kbdr has been updated
@@ -16,8 +18,13 @@ fn test_read_kbsr() {
     let expected_val = AsciiChar::new('y') as u16;
     assert_eq!(kbdr_val, expected_val);
 
-    let mut input = "".as_bytes();
-    let value = vm.memory.read_kbsr(&mut input);
+    // The ready bit stays set until KBDR is read
+    let value = vm.memory.read_kbsr();
+    assert_eq!(value, 0x8000);
+    assert_eq!(vm.memory.read_kbdr(), expected_val);
+
+    // All of the input has been read
+    let value = vm.memory.read_kbsr();
     assert_eq!(value, 0);
 
     // Kbdr value should still be there
@@ -25,6 +32,18 @@ fn test_read_kbsr() {
     assert_eq!(kbdr_val, expected_val);
 }
 
+#[test]
+fn test_write_kbsr() {
+    let mut vm = Lc3Vm::with_console(IoConsole::new("y".as_bytes(), OutputBuffer::new()));
+    // Only the interrupt enable bit can be written
+    vm.memory.write(0xfe00, 0xffff);
+    assert_eq!(vm.memory.peek(0xfe00), 0x4000);
+    assert!(vm.memory.keyboard_interrupt_requested());
+    assert_eq!(vm.memory.read(0xfe00), 0xc000);
+    vm.memory.write(0xfe00, 0);
+    assert!(!vm.memory.keyboard_interrupt_requested());
+}
+
 #[test]
 fn test_read_kbdr() {
     let mut vm = Lc3Vm::new();
@@ -34,6 +53,17 @@ fn test_read_kbdr() {
     assert_eq!(read_value, ascii_char);
 }
 
+#[test]
+fn test_read_key() {
+    let mut vm = Lc3Vm::with_console(IoConsole::new("ab".as_bytes(), OutputBuffer::new()));
+    // A character received by KBDR is read before the rest of the input
+    assert_eq!(vm.memory.read_kbsr(), 0x8000);
+    assert_eq!(vm.memory.read_key().unwrap(), b'a');
+    assert_eq!(vm.memory.read_kbsr(), 0x8000);
+    assert_eq!(vm.memory.read_key().unwrap(), b'b');
+    assert_eq!(vm.memory.read_kbsr(), 0);
+}
+
 #[test]
 fn test_read_dsr() {
     let vm = Lc3Vm::new();
@@ -57,10 +87,11 @@ fn test_write_mcr() {
 
 #[test]
 fn test_write_ddr() {
-    let mut vm = Lc3Vm::new();
+    let output = OutputBuffer::new();
+    let mut vm = Lc3Vm::with_console(IoConsole::new("".as_bytes(), output.clone()));
     let print_char = AsciiChar::S;
-    let mut output_writer: Vec<u8> = Vec::new();
-    vm.memory.write_ddr(print_char as u16, &mut output_writer);
+    vm.memory.write_ddr(print_char as u16).unwrap();
+    let output_writer = output.contents();
     assert_eq!(output_writer.len(), 1);
     assert_eq!(output_writer[0], print_char as u8);
 }
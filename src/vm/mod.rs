@@ -1,117 +1,288 @@
-mod memory;
-mod ops;
-mod registers;
+pub mod console;
+pub mod error;
+pub mod interrupts;
+pub mod limits;
+pub mod loader;
+pub mod memory;
+pub mod ops;
+pub mod os;
+pub mod registers;
+pub mod step;
 #[cfg(test)]
 mod tests;
-mod trap_vecs;
+pub mod trace;
+pub mod trap_vecs;
 
-use std::{
-    fs::File,
-    io::{self, Read},
-    path::Path,
-};
+use std::time::Instant;
 
+use console::Console;
+use error::VmError;
+use limits::{Limit, RunLimits};
 use memory::Memory;
 use registers::Registers;
+use trace::Trace;
+
+use crate::symbols::SymbolTable;
+
+use self::{
+    error::Fault,
+    registers::{ConditionFlag, PrivilegeMode, RegisterName},
+    step::{MemoryRead, MemoryWrite},
+};
 
-use self::registers::{ConditionFlag, RegisterName};
+/// The first address of user space. Addresses below it are system space
+const USER_SPACE_START: u16 = 0x3000;
+/// The first address of the device register space
+const DEVICE_SPACE_START: u16 = 0xfe00;
 
+/// The LC3 virtual machine, containing the registers and memory of the machine
 pub struct Lc3Vm {
     registers: Registers,
     memory: Memory,
+    /// Memory reads made by the instruction currently being executed
+    memory_reads: Vec<MemoryRead>,
+    /// Memory writes made by the instruction currently being executed
+    memory_writes: Vec<MemoryWrite>,
+    limits: RunLimits,
+    /// Allows programs running in user mode to access system space and the device
+    /// registers
+    ignore_privilege: bool,
+    /// The vector of the exception raised by the instruction currently being executed
+    exception: Option<u8>,
+    /// Whether the native trap routines run, which is not the case once an operating
+    /// system has been booted
+    native_troutines: bool,
+    /// The labels of the bundled operating system, once `load_os` has loaded it
+    os_symbols: Option<SymbolTable>,
+    /// The labels of the loaded programs
+    symbols: SymbolTable,
+    /// Where the executed instructions are recorded, if they are
+    trace: Option<Trace>,
+}
+
+impl Default for Lc3Vm {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Lc3Vm {
-    const DEFAULT_PC_START: u16 = 0x3000;
+    /// The address the program counter is set to when the VM is created
+    pub const DEFAULT_PC_START: u16 = 0x3000;
 
+    /// Creates a VM with zeroed registers and memory, and the program counter set
+    /// to `DEFAULT_PC_START`. The VM uses the standard input and output of the
+    /// process as its console
     pub fn new() -> Self {
+        Self::with_memory(Memory::new())
+    }
+
+    /// Creates a VM like `Lc3Vm::new`, which uses the given console for keyboard
+    /// input and display output
+    pub fn with_console(console: impl Console + 'static) -> Self {
+        Self::with_memory(Memory::with_console(console))
+    }
+
+    fn with_memory(memory: Memory) -> Self {
         let registers = Registers::new();
-        let memory = Memory::new();
-        let mut vm = Self { registers, memory };
+        let mut vm = Self {
+            registers,
+            memory,
+            memory_reads: Vec::new(),
+            memory_writes: Vec::new(),
+            limits: RunLimits::none(),
+            ignore_privilege: false,
+            exception: None,
+            native_troutines: false,
+            os_symbols: None,
+            symbols: SymbolTable::new(),
+            trace: None,
+        };
         vm.registers.set_program_counter(Self::DEFAULT_PC_START);
+        vm.install_native_troutines();
         vm
     }
 
-    /// Load a compiled LC3 program for execution.
+    /// Returns the registers of the VM
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    /// Returns the registers of the VM for modification
+    pub fn registers_mut(&mut self) -> &mut Registers {
+        &mut self.registers
+    }
+
+    /// Returns the labels of the loaded programs, which are used to describe addresses
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    /// Returns the labels of the loaded programs for modification, such as to add the
+    /// labels of another program
+    pub fn symbols_mut(&mut self) -> &mut SymbolTable {
+        &mut self.symbols
+    }
+
+    /// Replaces the labels of the loaded programs, which are used to show addresses in
+    /// error messages, the trace and the debugger
+    pub fn set_symbols(&mut self, symbols: SymbolTable) {
+        self.symbols = symbols;
+    }
+
+    /// Returns the memory of the VM
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    /// Returns the memory of the VM for modification
+    pub fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    /// Runs the loaded program until the clock enable bit of the machine control
+    /// register is cleared, which is normally done by the `HALT` trap routine
     ///
-    /// A given LC3 program will have its first 16 bits set to the memory address
-    /// where the start of the program instructions should be loaded to. Subsequent
-    /// bytes are then the program instructions
-    pub fn load_program(&mut self, file_path: &Path) -> io::Result<()> {
-        let mut program_file = File::open(file_path)?;
-        let mut file_contents: Vec<u8> = Vec::new();
-        program_file.read_to_end(&mut file_contents).unwrap();
-
-        // Read the origin first
-        let mut chunked = file_contents.as_slice().chunks(2);
-
-        let chunk = chunked.next().unwrap();
-        let origin = Self::read_u16(chunk);
-        let file_len = program_file.metadata().unwrap().len();
-        if let Err(e) = Self::validate_file_len(file_len, origin) {
-            panic!(
-                "The length of the file ({}) will be too large to fit into VM memory!",
-                e
-            );
-        }
+    /// Execution also stops if an instruction cannot be executed, or if one of the
+    /// limits set with `set_run_limits` is exceeded, in which case the error is returned.
+    /// The trace set with `set_trace` is flushed when execution stops
+    pub fn run(&mut self) -> Result<(), VmError> {
+        let result = self.run_until_halted();
+        let flushed = self.flush_trace();
+        result.and(flushed)
+    }
 
-        let mut current_address = origin;
-        for chunk in chunked {
-            let mem_data = Self::read_u16(chunk);
-            self.memory.write(current_address, mem_data);
-            current_address += 1;
+    fn run_until_halted(&mut self) -> Result<(), VmError> {
+        let started = Instant::now();
+        let mut executed: u64 = 0;
+        while self.running() {
+            if let Some(limit) = self.exceeded_limit(executed, started) {
+                let pc = self.registers.program_counter();
+                return Err(VmError::LimitExceeded { pc, limit });
+            }
+            self.step()?;
+            executed += 1;
         }
         Ok(())
     }
 
-    /// Reads two bytes from file data that has been converted into a `Chunks<u8>`,
-    /// and then parse it into a `u16` and return it
-    fn read_u16(program_data_slice: &[u8]) -> u16 {
-        // Program file is in little endian, so convert it to big endian, which
-        // is required in LC3
-        let buf: [u8; 2] = [program_data_slice[0], program_data_slice[1]];
-        u16::from_be_bytes(buf)
+    /// Sets the limits that `run` enforces on programs. By default, programs are
+    /// allowed to run forever
+    pub fn set_run_limits(&mut self, limits: RunLimits) {
+        self.limits = limits;
+    }
+
+    /// Returns the limits that `run` enforces on programs
+    pub fn run_limits(&self) -> RunLimits {
+        self.limits
     }
 
-    /// Validate that the program file is small enough to fit in the memory of
-    /// the LC3 VM. The `file_len` argument should be the size of the files generated
-    /// by the file's `std::fs::Metadata::len()` function
-    fn validate_file_len(file_len: u64, origin: u16) -> Result<(), u64> {
-        if file_len > (u16::MAX - origin) as u64 {
-            Err(file_len)
+    /// Checks if running another instruction would exceed the configured limits
+    fn exceeded_limit(&self, executed: u64, started: Instant) -> Option<Limit> {
+        if let Some(max_instructions) = self.limits.max_instructions {
+            if executed >= max_instructions {
+                return Some(Limit::Instructions(max_instructions));
+            }
+        }
+        if let Some(max_duration) = self.limits.max_duration {
+            if started.elapsed() >= max_duration {
+                return Some(Limit::Duration(max_duration));
+            }
+        }
+        None
+    }
+
+    /// Allows programs running in user mode to access system space (x0000 to x2FFF)
+    /// and the device registers (xFE00 to xFFFF). By default, such accesses raise an
+    /// access control violation exception. Trap routines run in supervisor mode, so
+    /// they can always access them
+    pub fn set_ignore_privilege(&mut self, ignore_privilege: bool) {
+        self.ignore_privilege = ignore_privilege;
+    }
+
+    /// Returns an `AccessViolation` fault if the running program is not allowed to
+    /// access `address`
+    pub(crate) fn check_access(&self, address: u16) -> Result<(), Fault> {
+        let is_protected = !(USER_SPACE_START..DEVICE_SPACE_START).contains(&address);
+        let is_user = self.registers.privilege_mode() == PrivilegeMode::User;
+        if is_protected && is_user && !self.ignore_privilege {
+            Err(Fault::AccessViolation(address))
         } else {
             Ok(())
         }
     }
 
-    pub fn run(&mut self) {
-        while self.running() {
-            let instr = self.memory.read(self.registers.program_counter());
-            self.registers.increment_program_counter();
-            // First 4 bits of an instruction are the opcodes
-            self.run_op(instr);
+    /// Reads memory on behalf of an instruction, which is only allowed if the running
+    /// program can access the address
+    pub(crate) fn load(&mut self, address: u16) -> Result<u16, Fault> {
+        self.check_access(address)?;
+        Ok(self.read_memory(address))
+    }
+
+    /// Writes memory on behalf of an instruction, which is only allowed if the running
+    /// program can access the address
+    pub(crate) fn store(&mut self, address: u16, value: u16) -> Result<(), Fault> {
+        self.check_access(address)?;
+        self.write_memory(address, value);
+        Ok(())
+    }
+
+    /// Reads memory like `Memory::read`, except that the memory mapped PSR is read from
+    /// the registers. Unlike `read_memory`, the read is not recorded, which is used to
+    /// fetch instructions
+    pub(crate) fn fetch_memory(&mut self, address: u16) -> u16 {
+        if address == Registers::PSR_ADDRESS {
+            self.registers.psr()
+        } else {
+            self.memory.read(address)
+        }
+    }
+
+    /// Returns the value at the given memory address like `Memory::peek`, including
+    /// the memory mapped PSR. Device registers are not affected
+    pub fn peek_memory(&self, address: u16) -> u16 {
+        if address == Registers::PSR_ADDRESS {
+            self.registers.psr()
+        } else {
+            self.memory.peek(address)
+        }
+    }
+
+    /// Sets the value at the given memory address like `Memory::poke`, including the
+    /// memory mapped PSR. Device registers are not affected
+    pub fn poke_memory(&mut self, address: u16, value: u16) {
+        if address == Registers::PSR_ADDRESS {
+            self.registers.set_psr(value);
+        } else {
+            self.memory.poke(address, value);
         }
     }
 
-    /// TODO: Implement error handling
+    /// Returns the value of the general purpose register with the given id. Only the
+    /// lowest 3 bits of `reg_id` are used, as that is how registers are encoded in
+    /// instructions
     pub fn get_reg_val_by_id(&self, reg_id: u16) -> u16 {
-        // Will panic if id is invalid!
-        let register = RegisterName::from(reg_id);
+        let register = RegisterName::general(reg_id);
         self.registers.get_reg_value(register)
     }
 
-    /// TODO: Implement error handling
+    /// Sets the value of the general purpose register with the given id. Only the
+    /// lowest 3 bits of `reg_id` are used, as that is how registers are encoded in
+    /// instructions
     pub fn set_reg_val_by_id(&mut self, reg_id: u16, value: u16) {
-        // Will panic if id is invalid!
-        let register = RegisterName::from(reg_id);
+        let register = RegisterName::general(reg_id);
         self.registers.set_reg_value(register, value);
     }
 
+    /// Returns the condition flag set by the most recently executed instruction
     pub fn get_cond_flag(&self) -> ConditionFlag {
         let flag_reg_val = self.registers.cond_reg();
-        ConditionFlag::from(flag_reg_val)
+        // The condition register can only be set through `Registers::set_cond_reg`
+        // and starts out as `Zro`, so it always holds a valid flag
+        ConditionFlag::try_from(flag_reg_val).unwrap_or(ConditionFlag::Zro)
     }
 
+    /// Returns `true` while the clock enable bit of the machine control register is set
     pub fn running(&self) -> bool {
         !self.memory.mcr_is_cleared()
     }
@@ -6,25 +6,31 @@ mod tests;
 
 use std::num::Wrapping;
 
-use super::{registers::ConditionFlag, trap_vecs::TrapVector, Lc3Vm};
+use super::{
+    error::{Fault, VmError},
+    registers::{ConditionFlag, PrivilegeMode},
+    Lc3Vm,
+};
 use crate::bitwise_utils::sign_extend;
 
-enum OpCode {
-    Add,
-    And,
-    Br,
-    Jmp,
-    Jsr,
-    Ld,
-    Ldi,
-    Ldr,
-    Lea,
-    Not,
-    Rti,
-    St,
-    Sti,
-    Str,
-    Trap,
+/// The opcodes of the LC3 ISA, which are stored in bits [15:12] of an instruction
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OpCode {
+    Add = 0b0001,
+    And = 0b0101,
+    Br = 0b0000,
+    Jmp = 0b1100,
+    Jsr = 0b0100,
+    Ld = 0b0010,
+    Ldi = 0b1010,
+    Ldr = 0b0110,
+    Lea = 0b1110,
+    Not = 0b1001,
+    Rti = 0b1000,
+    St = 0b0011,
+    Sti = 0b1011,
+    Str = 0b0111,
+    Trap = 0b1111,
 }
 
 impl TryFrom<u16> for OpCode {
@@ -55,27 +61,48 @@ impl TryFrom<u16> for OpCode {
 
 // https://www.jmeiners.com/lc3-vm/supplies/lc3-isa.pdf
 impl Lc3Vm {
-    /// Determine the correct operation to run, and run it
-    pub fn run_op(&mut self, instr: u16) {
+    /// Determine the correct operation to run, and run it. The program counter is
+    /// expected to have been incremented past `instr` already
+    pub fn run_op(&mut self, instr: u16) -> Result<(), VmError> {
+        let pc = self.registers.program_counter().wrapping_sub(1);
         let opcode_raw = instr >> 12;
-        let opcode = OpCode::try_from(opcode_raw).expect("Invalid opcode detected");
+        let result = match OpCode::try_from(opcode_raw) {
+            Ok(opcode) => self.execute(opcode, instr),
+            Err(()) => Err(Fault::IllegalOpcode),
+        };
+        // Exceptions are handled by their service routines when there is one
+        if let Err(fault) = result {
+            self.raise_exception(fault)
+                .map_err(|fault| fault.at(pc, instr))?;
+        }
+        // Errors from device registers are only reported after the instruction that
+        // accessed them completes
+        match self.memory.take_fault() {
+            Some(fault) => Err(fault.at(pc, instr)),
+            None => Ok(()),
+        }
+    }
+
+    /// Runs the operation of `opcode`
+    fn execute(&mut self, opcode: OpCode, instr: u16) -> Result<(), Fault> {
         match opcode {
             OpCode::Add => self.add_op(instr),
             OpCode::And => self.and_op(instr),
             OpCode::Br => self.br_op(instr),
             OpCode::Jmp => self.jmp_op(instr),
             OpCode::Jsr => self.jsr_op(instr),
-            OpCode::Ld => self.ld_op(instr),
-            OpCode::Ldi => self.ldi_op(instr),
-            OpCode::Ldr => self.ldr_op(instr),
+            OpCode::Ld => self.ld_op(instr)?,
+            OpCode::Ldi => self.ldi_op(instr)?,
+            OpCode::Ldr => self.ldr_op(instr)?,
             OpCode::Lea => self.lea_op(instr),
             OpCode::Not => self.not_op(instr),
-            OpCode::Rti => self.rti_op(instr),
-            OpCode::St => self.st_op(instr),
-            OpCode::Sti => self.sti_op(instr),
-            OpCode::Str => self.str_op(instr),
-            OpCode::Trap => self.trap_op(instr),
+            OpCode::Rti => self.rti_op(instr)?,
+            OpCode::St => self.st_op(instr)?,
+            OpCode::Sti => self.sti_op(instr)?,
+            OpCode::Str => self.str_op(instr)?,
+            OpCode::Trap => self.trap_op(instr)?,
         };
+        Ok(())
     }
 
     /// Performs the `ADD` operation
@@ -145,9 +172,9 @@ impl Lc3Vm {
         let test_zro = ((flag_bits >> 1) & 1) == 1;
         let test_pos = (flag_bits & 1) == 1;
 
-        let offset = instr & 0b111111111;
-        let current_pc = self.registers.program_counter();
-        let br_address = offset + current_pc;
+        let offset = Wrapping(sign_extend(instr & 0x1ff, 9));
+        let current_pc = Wrapping(self.registers.program_counter());
+        let br_address = (current_pc + offset).0;
 
         let flag = self.get_cond_flag();
         let will_br = match (test_neg, test_zro, test_pos) {
@@ -178,47 +205,50 @@ impl Lc3Vm {
         // Save PC into R7, PC should have been incremented already before
         // calling this op
         let current_pc = Wrapping(self.registers.program_counter());
-        self.set_reg_val_by_id(7, current_pc.0);
         // Implement both the JSR and JSRR operation
         let jsr_mode = ((instr >> 11) & 1) == 1;
         let new_pc_addr = if jsr_mode {
             let offset = Wrapping(sign_extend(instr & 0x7ff, 11));
             (current_pc + offset).0
         } else {
-            let base_reg = (instr >> 8) & 0b111;
+            let base_reg = (instr >> 6) & 0b111;
             self.get_reg_val_by_id(base_reg)
         };
+        // R7 is set after reading the base register, so that `JSRR R7` works
+        self.set_reg_val_by_id(7, current_pc.0);
         self.registers.set_program_counter(new_pc_addr);
     }
 
     /// Performs the `LD` operation
-    fn ld_op(&mut self, instr: u16) {
+    fn ld_op(&mut self, instr: u16) -> Result<(), Fault> {
         let offset = Wrapping(sign_extend(instr & 0x1ff, 9));
         let dest_reg = (instr >> 9) & 0x7;
         let current_pc = Wrapping(self.registers.program_counter());
         let load_addr = current_pc + offset;
-        let value = self.memory.read(load_addr.0);
+        let value = self.load(load_addr.0)?;
         self.set_reg_val_by_id(dest_reg, value);
         let flag = ConditionFlag::parse_u16(value);
         self.registers.set_cond_reg(flag);
+        Ok(())
     }
 
     /// Performs the `LDI` operation
-    fn ldi_op(&mut self, instr: u16) {
+    fn ldi_op(&mut self, instr: u16) -> Result<(), Fault> {
         let dest_reg = (instr >> 9) & 0b111;
         let pc_offset = Wrapping(sign_extend(instr & 0x1ff, 9));
         let current_pc = Wrapping(self.registers.program_counter());
         let pointer_address = pc_offset + current_pc;
-        let final_address = self.memory.read(pointer_address.0);
-        let value = self.memory.read(final_address);
+        let final_address = self.load(pointer_address.0)?;
+        let value = self.load(final_address)?;
         self.set_reg_val_by_id(dest_reg, value);
         // Check if value is positive or negative to set the flags
         let flag = ConditionFlag::parse_u16(value);
         self.registers.set_cond_reg(flag);
+        Ok(())
     }
 
     /// Performs the `LDR` operation
-    fn ldr_op(&mut self, instr: u16) {
+    fn ldr_op(&mut self, instr: u16) -> Result<(), Fault> {
         let offset = instr & 0x3F;
         let offset = Wrapping(sign_extend(offset, 6));
         let base_reg = (instr >> 6) & 0x7;
@@ -226,10 +256,11 @@ impl Lc3Vm {
 
         let br_val = Wrapping(self.get_reg_val_by_id(base_reg));
         let address = br_val + offset;
-        let value = self.memory.read(address.0);
+        let value = self.load(address.0)?;
         self.set_reg_val_by_id(dest_reg, value);
         let flag = ConditionFlag::parse_u16(value);
         self.registers.set_cond_reg(flag);
+        Ok(())
     }
 
     /// Performs the `LEA` operation
@@ -239,9 +270,9 @@ impl Lc3Vm {
         let current_pc = Wrapping(self.registers.program_counter());
         let address = pc_offset + current_pc;
 
-        let value = self.memory.read(address.0);
-        self.set_reg_val_by_id(dest_reg, value);
-        let flag = ConditionFlag::parse_u16(value);
+        // LEA loads the address itself, memory is not read
+        self.set_reg_val_by_id(dest_reg, address.0);
+        let flag = ConditionFlag::parse_u16(address.0);
         self.registers.set_cond_reg(flag);
     }
 
@@ -256,39 +287,55 @@ impl Lc3Vm {
         self.registers.set_cond_reg(flag);
     }
 
-    /// Performs the `RTI` operation
-    fn rti_op(&mut self, _instr: u16) {
-        // This operation is not actually implemented yet, but it will return so that it is
-        // not blocking programs from running. The RTI operation simply returns control from
-        // a privileged execution (i.e trap routines) to the user level execution
-        ()
+    /// Performs the `RTI` operation, which returns from an interrupt or trap routine by
+    /// popping the PC and PSR from the supervisor stack. When returning to user mode,
+    /// the user stack pointer is restored into R6
+    fn rti_op(&mut self, _instr: u16) -> Result<(), Fault> {
+        if self.registers.privilege_mode() == PrivilegeMode::User {
+            return Err(Fault::PrivilegeViolation);
+        }
+        let stack_pointer = self.get_reg_val_by_id(6);
+        let pc = self.read_memory(stack_pointer);
+        let psr = self.read_memory(stack_pointer.wrapping_add(1));
+        let stack_pointer = stack_pointer.wrapping_add(2);
+        self.registers.set_program_counter(pc);
+        self.registers.set_psr(psr);
+
+        if self.registers.privilege_mode() == PrivilegeMode::User {
+            self.registers.set_saved_ssp(stack_pointer);
+            let user_stack_pointer = self.registers.saved_usp();
+            self.set_reg_val_by_id(6, user_stack_pointer);
+        } else {
+            self.set_reg_val_by_id(6, stack_pointer);
+        }
+        Ok(())
     }
 
     /// Performs the `ST` operation
-    fn st_op(&mut self, instr: u16) {
+    fn st_op(&mut self, instr: u16) -> Result<(), Fault> {
         let pc_offset = Wrapping(sign_extend(instr & 0x1ff, 9));
         let sr = (instr >> 9) & 0x7;
         let sr_val = self.get_reg_val_by_id(sr);
 
         let current_pc = Wrapping(self.registers.program_counter());
         let address = current_pc + pc_offset;
-        self.memory.write(address.0, sr_val);
+        self.store(address.0, sr_val)
     }
 
     /// Performs the `STI` operation
-    fn sti_op(&mut self, instr: u16) {
+    fn sti_op(&mut self, instr: u16) -> Result<(), Fault> {
         let pc_offset = Wrapping(sign_extend(instr & 0x1ff, 9));
         let sr = (instr >> 9) & 0x7;
         let sr_val = self.get_reg_val_by_id(sr);
 
         let current_pc = Wrapping(self.registers.program_counter());
         let pointer_address = current_pc + pc_offset;
-        let final_address = self.memory.read(pointer_address.0);
-        self.memory.write(final_address, sr_val);
+        let final_address = self.load(pointer_address.0)?;
+        self.store(final_address, sr_val)
     }
 
     /// Performs the `STR` operation
-    fn str_op(&mut self, instr: u16) {
+    fn str_op(&mut self, instr: u16) -> Result<(), Fault> {
         let offset = Wrapping(sign_extend(instr & 0x3f, 6));
         let base_reg = (instr >> 6) & 0x7;
         let sr = (instr >> 9) & 0x7;
@@ -296,24 +343,25 @@ impl Lc3Vm {
         let sr_val = self.get_reg_val_by_id(sr);
         let base_reg_val = Wrapping(self.get_reg_val_by_id(base_reg));
         let address = base_reg_val + offset;
-        self.memory.write(address.0, sr_val);
+        self.store(address.0, sr_val)
     }
 
-    /// Performs the `TRAP` operation
-    fn trap_op(&mut self, instr: u16) {
-        let current_pc = self.registers.program_counter();
-        self.set_reg_val_by_id(7, current_pc);
-
-        let trap_vec_raw = instr & 0xff;
-        // Use the enum to parse the raw trap vector code, to make sure it is a valid
-        // trap vector code
-        let trap_vec = TrapVector::try_from(trap_vec_raw).expect("Invalid trap vector");
-        self.registers.set_program_counter(trap_vec as u16);
-
-        // Run trap routine
-        self.run_troutine(trap_vec);
-
-        // Reset the program counter after returning from the trap routine
-        self.registers.set_program_counter(current_pc);
+    /// Performs the `TRAP` operation. The PSR and PC are pushed onto the supervisor
+    /// stack, and execution continues in supervisor mode at the trap service routine
+    /// whose address is in the trap vector table. Service routines return with `RTI`
+    fn trap_op(&mut self, instr: u16) -> Result<(), Fault> {
+        let trap_vec = instr & 0xff;
+        let service_routine = self.read_memory(trap_vec);
+        // A service routine cannot be at x0000, which is where the trap vector table is
+        if service_routine == 0 {
+            return Err(Fault::BadTrapVector);
+        }
+        self.enter_supervisor_mode(None);
+        // R7 is also set to the return address like in the second edition of the LC3,
+        // so that service routines written for it can return with `RET`
+        let return_address = self.registers.program_counter();
+        self.set_reg_val_by_id(7, return_address);
+        self.registers.set_program_counter(service_routine);
+        Ok(())
     }
 }
@@ -1,4 +1,5 @@
 use super::*;
+use crate::vm::registers::Registers;
 
 #[test]
 #[allow(clippy::unusual_byte_groupings)]
@@ -54,7 +55,7 @@ fn test_ldi_op() {
     let mut vm = Lc3Vm::new();
     vm.memory.write(pointer_address, final_address);
     vm.memory.write(final_address, data);
-    vm.ldi_op(instr);
+    vm.ldi_op(instr).unwrap();
     let reg_val = vm.get_reg_val_by_id(2);
     assert_eq!(reg_val, data);
     // Test flag
@@ -77,7 +78,7 @@ fn test_and_op_register_mode() {
     vm.and_op(instr);
     let result = vm.get_reg_val_by_id(2);
     assert_eq!(result, val1 & val2);
-    let flag = ConditionFlag::from(vm.registers.cond_reg());
+    let flag = ConditionFlag::try_from(vm.registers.cond_reg()).unwrap();
     let correct_flag: ConditionFlag = ConditionFlag::parse_u16(result);
     assert_eq!(flag, correct_flag);
 }
@@ -311,7 +312,7 @@ fn test_jsrr_op() {
     let mut vm = Lc3Vm::new();
     let desired_address = 0x3085;
     // JSRR R3
-    let instr: u16 = 0b0100_0_011_00000000;
+    let instr: u16 = 0b0100_0_00_011_000000;
 
     vm.set_reg_val_by_id(3, desired_address);
     vm.jsr_op(instr);
@@ -329,10 +330,10 @@ fn test_ld_op() {
 
     let stored_value = 0xF1FA;
     vm.memory.write(desired_address, stored_value);
-    vm.ld_op(instr);
+    vm.ld_op(instr).unwrap();
     let value = vm.get_reg_val_by_id(4);
     assert_eq!(value, stored_value);
-    let flag = ConditionFlag::from(vm.registers.cond_reg());
+    let flag = ConditionFlag::try_from(vm.registers.cond_reg()).unwrap();
     let correct_flag: ConditionFlag = ConditionFlag::parse_u16(value);
     assert_eq!(flag, correct_flag);
 }
@@ -349,10 +350,10 @@ fn test_ldr_op() {
     // LDR R4, R2, offset
     let instr: u16 = 0b0110_100_010_000100;
     vm.set_reg_val_by_id(2, br_val);
-    vm.ldr_op(instr);
+    vm.ldr_op(instr).unwrap();
     let value = vm.get_reg_val_by_id(4);
     assert_eq!(value, stored_value);
-    let flag = ConditionFlag::from(vm.registers.cond_reg());
+    let flag = ConditionFlag::try_from(vm.registers.cond_reg()).unwrap();
     let correct_flag: ConditionFlag = ConditionFlag::parse_u16(value);
     assert_eq!(flag, correct_flag);
 }
@@ -364,13 +365,14 @@ fn test_lea_op() {
     let address: u16 = 0x3050;
     // let offset = desired_address - Lc3Vm::DEFAULT_PC_START;
     // LEA R2, ${offset}
-    let instr: u16 = 0b1010_010_001010000;
+    let instr: u16 = 0b1110_010_001010000;
 
     let mut vm = Lc3Vm::new();
     vm.memory.write(address, data);
     vm.lea_op(instr);
+    // The address is loaded, not the data stored at the address
     let reg_val = vm.get_reg_val_by_id(2);
-    assert_eq!(reg_val, data);
+    assert_eq!(reg_val, address);
     // Test flag
     let flag = vm.get_cond_flag();
     assert_eq!(flag, ConditionFlag::Pos);
@@ -398,7 +400,7 @@ fn test_st_op() {
     let address: u16 = 0x3050;
     // ST R4,
     let instr: u16 = 0b0011_100_001010000;
-    vm.st_op(instr);
+    vm.st_op(instr).unwrap();
     let value = vm.memory.read(address);
     assert_eq!(data, value);
 }
@@ -415,7 +417,7 @@ fn test_sti_op() {
     let instr: u16 = 0b1011_100_001010000;
     vm.set_reg_val_by_id(4, data);
     vm.memory.write(pointer_address, final_address);
-    vm.sti_op(instr);
+    vm.sti_op(instr).unwrap();
     let value = vm.memory.read(final_address);
     assert_eq!(data, value);
 }
@@ -436,7 +438,144 @@ fn test_str_op() {
 
     // STR R4, R2
     let instr: u16 = 0b0111_100_010_010101;
-    vm.str_op(instr);
+    vm.str_op(instr).unwrap();
     let value = vm.memory.read(desired_address);
     assert_eq!(data, value);
 }
+
+#[test]
+#[allow(clippy::unusual_byte_groupings)]
+fn test_run_op_illegal_opcode() {
+    let mut vm = Lc3Vm::new();
+    // Opcode 1101 is reserved
+    let instr: u16 = 0b1101_000_000_000000;
+    vm.registers.increment_program_counter();
+    let err = vm.run_op(instr).unwrap_err();
+    assert!(matches!(
+        err,
+        VmError::IllegalOpcode {
+            pc: Lc3Vm::DEFAULT_PC_START,
+            instr: 0b1101_000_000_000000
+        }
+    ));
+}
+
+#[test]
+fn test_run_op_bad_trap_vector() {
+    let mut vm = Lc3Vm::new();
+    // TRAP x30
+    let instr: u16 = 0xf030;
+    vm.registers.increment_program_counter();
+    let err = vm.run_op(instr).unwrap_err();
+    assert!(matches!(
+        err,
+        VmError::BadTrapVector {
+            pc: Lc3Vm::DEFAULT_PC_START,
+            instr: 0xf030
+        }
+    ));
+}
+
+#[test]
+#[allow(clippy::unusual_byte_groupings)]
+fn test_br_op_negative_offset() {
+    let mut vm = Lc3Vm::new();
+    // BRnzp #-16
+    let instr: u16 = 0b0000_111_111110000;
+    vm.br_op(instr);
+    let current_pc = vm.registers.program_counter();
+    assert_eq!(current_pc, Lc3Vm::DEFAULT_PC_START - 16);
+}
+
+#[test]
+fn test_rti_op_to_user_mode() {
+    let mut vm = Lc3Vm::new();
+    vm.registers.set_psr(0x0101);
+    vm.registers.set_saved_usp(0xfdff);
+    vm.set_reg_val_by_id(6, 0x2ffe);
+    // The PC and PSR pushed by an interrupt
+    vm.memory.write(0x2ffe, 0x3005);
+    vm.memory.write(0x2fff, 0x8004);
+    vm.rti_op(0x8000).unwrap();
+
+    assert_eq!(vm.registers.program_counter(), 0x3005);
+    assert_eq!(vm.registers.psr(), 0x8004);
+    assert_eq!(vm.get_reg_val_by_id(6), 0xfdff);
+    assert_eq!(vm.registers.saved_ssp(), 0x3000);
+}
+
+#[test]
+fn test_rti_op_to_supervisor_mode() {
+    let mut vm = Lc3Vm::new();
+    vm.registers.set_psr(0x0401);
+    vm.registers.set_saved_ssp(0x1234);
+    vm.set_reg_val_by_id(6, 0x2ff0);
+    vm.memory.write(0x2ff0, 0x0520);
+    vm.memory.write(0x2ff1, 0x0202);
+    vm.rti_op(0x8000).unwrap();
+
+    assert_eq!(vm.registers.program_counter(), 0x0520);
+    assert_eq!(vm.registers.psr(), 0x0202);
+    // The supervisor stack is still in use
+    assert_eq!(vm.get_reg_val_by_id(6), 0x2ff2);
+    assert_eq!(vm.registers.saved_ssp(), 0x1234);
+}
+
+#[test]
+fn test_rti_op_in_user_mode() {
+    let mut vm = Lc3Vm::new();
+    vm.registers.increment_program_counter();
+    let err = vm.run_op(0x8000).unwrap_err();
+    assert!(matches!(
+        err,
+        VmError::PrivilegeViolation {
+            pc: Lc3Vm::DEFAULT_PC_START,
+            instr: 0x8000
+        }
+    ));
+    assert_eq!(vm.registers.program_counter(), Lc3Vm::DEFAULT_PC_START + 1);
+}
+
+#[test]
+fn test_trap_op() {
+    let mut vm = Lc3Vm::new();
+    vm.memory.write(0x30, 0x4000);
+    vm.set_reg_val_by_id(6, 0xfd00);
+    vm.registers.increment_program_counter();
+    // TRAP x30
+    vm.trap_op(0xf030).unwrap();
+    assert_eq!(vm.registers.program_counter(), 0x4000);
+    assert_eq!(vm.registers.privilege_mode(), PrivilegeMode::Supervisor);
+    assert_eq!(vm.registers.saved_usp(), 0xfd00);
+    // The PC and PSR are pushed onto the supervisor stack
+    let stack_pointer = vm.get_reg_val_by_id(6);
+    assert_eq!(stack_pointer, Registers::DEFAULT_SAVED_SSP - 2);
+    assert_eq!(vm.memory.read(stack_pointer), Lc3Vm::DEFAULT_PC_START + 1);
+    assert_eq!(vm.memory.read(stack_pointer + 1), Registers::DEFAULT_PSR);
+    assert_eq!(vm.get_reg_val_by_id(7), Lc3Vm::DEFAULT_PC_START + 1);
+}
+
+#[test]
+fn test_trap_routine_return() {
+    let mut vm = Lc3Vm::new();
+    vm.set_reg_val_by_id(6, 0xfd00);
+    // TRAP x31, TRAP x30
+    vm.memory.write(0x3000, 0xf031);
+    vm.memory.write(0x3001, 0xf030);
+    // RTI returns to user mode
+    vm.memory.write(0x31, 0x4010);
+    vm.memory.write(0x4010, 0x8000);
+    vm.step().unwrap();
+    vm.step().unwrap();
+    assert_eq!(vm.registers.program_counter(), 0x3001);
+    assert_eq!(vm.registers.privilege_mode(), PrivilegeMode::User);
+    assert_eq!(vm.get_reg_val_by_id(6), 0xfd00);
+    // RET returns to the caller in supervisor mode, like routines written for the
+    // second edition of the LC3
+    vm.memory.write(0x30, 0x4000);
+    vm.memory.write(0x4000, 0xc1c0);
+    vm.step().unwrap();
+    vm.step().unwrap();
+    assert_eq!(vm.registers.program_counter(), 0x3002);
+    assert_eq!(vm.registers.privilege_mode(), PrivilegeMode::Supervisor);
+}
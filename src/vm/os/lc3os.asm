@@ -0,0 +1,728 @@
+; The LC-3 operating system which can be booted by the VM in place of the native trap
+; routines. It follows the operating system of the reference simulator: the trap
+; routines are written in LC-3 assembly and use the device registers, and the machine
+; starts in supervisor mode at OS_START, which drops to the user program with RTI.
+;
+; Trap routines return with RTI, and save the registers they use apart from R0
+
+        .ORIG x0000
+
+; The trap vector table, x0000 to x00FF
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL TRAP_GETC
+        .FILL TRAP_OUT
+        .FILL TRAP_PUTS
+        .FILL TRAP_IN
+        .FILL TRAP_PUTSP
+        .FILL TRAP_HALT
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+        .FILL BAD_TRAP
+
+; The interrupt vector table, x0100 to x01FF
+        .FILL EX_PRIV
+        .FILL EX_ILL
+        .FILL EX_ACV
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+        .FILL BAD_INT
+
+; The operating system starts here, at x0200. The PSR and PC of the user program are
+; pushed onto the supervisor stack, and RTI starts the program in user mode. The VM
+; patches USER_PC with the program's entry point before booting
+OS_START
+        LD R6, OS_SSP
+        LD R0, USER_PSR
+        ADD R6, R6, #-1
+        STR R0, R6, #0
+        LD R0, USER_PC
+        ADD R6, R6, #-1
+        STR R0, R6, #0
+        RTI
+
+OS_SSP   .FILL x3000
+USER_PSR .FILL x8002
+USER_PC  .FILL x3000
+
+OS_KBSR  .FILL xFE00
+OS_KBDR  .FILL xFE02
+OS_DSR   .FILL xFE04
+OS_DDR   .FILL xFE06
+OS_MCR   .FILL xFFFE
+MASK_HI  .FILL x7FFF
+LOW_8    .FILL x00FF
+
+OS_SAVE_R1 .BLKW 1
+OS_SAVE_R2 .BLKW 1
+OS_SAVE_R3 .BLKW 1
+
+; GETC: Reads a character from the keyboard into R0, without echoing it
+TRAP_GETC
+        LDI R0, OS_KBSR
+        BRzp TRAP_GETC
+        LDI R0, OS_KBDR
+        RTI
+
+; OUT: Writes the character in R0 to the display
+TRAP_OUT
+        ST R1, OS_SAVE_R1
+OUT_WAIT
+        LDI R1, OS_DSR
+        BRzp OUT_WAIT
+        STI R0, OS_DDR
+        LD R1, OS_SAVE_R1
+        RTI
+
+; PUTS: Writes the string starting at the address in R0 to the display, one character
+; per word, until a x0000 word
+TRAP_PUTS
+        ST R0, PUTS_SAVE_R0
+        ST R1, OS_SAVE_R1
+        ST R2, OS_SAVE_R2
+        ADD R1, R0, #0
+PUTS_LOOP
+        LDR R0, R1, #0
+        BRz PUTS_DONE
+PUTS_WAIT
+        LDI R2, OS_DSR
+        BRzp PUTS_WAIT
+        STI R0, OS_DDR
+        ADD R1, R1, #1
+        BRnzp PUTS_LOOP
+PUTS_DONE
+        LD R0, PUTS_SAVE_R0
+        LD R1, OS_SAVE_R1
+        LD R2, OS_SAVE_R2
+        RTI
+PUTS_SAVE_R0 .BLKW 1
+
+; IN: Prompts for a character, reads it into R0, and echoes it followed by a newline
+TRAP_IN
+        ST R1, OS_SAVE_R1
+        ST R2, OS_SAVE_R2
+        LEA R1, IN_PROMPT
+IN_PROMPT_LOOP
+        LDR R2, R1, #0
+        BRz IN_READ
+IN_PROMPT_WAIT
+        LDI R0, OS_DSR
+        BRzp IN_PROMPT_WAIT
+        STI R2, OS_DDR
+        ADD R1, R1, #1
+        BRnzp IN_PROMPT_LOOP
+IN_READ
+        LDI R0, OS_KBSR
+        BRzp IN_READ
+        LDI R0, OS_KBDR
+IN_ECHO_WAIT
+        LDI R1, OS_DSR
+        BRzp IN_ECHO_WAIT
+        STI R0, OS_DDR
+IN_NEWLINE_WAIT
+        LDI R1, OS_DSR
+        BRzp IN_NEWLINE_WAIT
+        AND R2, R2, #0
+        ADD R2, R2, #10
+        STI R2, OS_DDR
+        LD R1, OS_SAVE_R1
+        LD R2, OS_SAVE_R2
+        RTI
+IN_PROMPT .STRINGZ "\nInput a character> "
+
+; PUTSP: Writes the string starting at the address in R0 to the display, two
+; characters per word with the first in the low byte, until a x0000 word. A x00 high
+; byte ends a string with an odd number of characters
+TRAP_PUTSP
+        ST R0, PUTSP_SAVE_R0
+        ST R1, OS_SAVE_R1
+        ST R2, OS_SAVE_R2
+        ST R3, OS_SAVE_R3
+        ADD R1, R0, #0
+PUTSP_LOOP
+        LDR R2, R1, #0
+        BRz PUTSP_DONE
+        LD R0, LOW_8
+        AND R0, R2, R0
+PUTSP_LOW_WAIT
+        LDI R3, OS_DSR
+        BRzp PUTSP_LOW_WAIT
+        STI R0, OS_DDR
+        ; Shift the high byte into the low byte, one bit at a time
+        AND R0, R0, #0
+        AND R3, R3, #0
+        ADD R3, R3, #8
+PUTSP_SHIFT
+        ADD R0, R0, R0
+        ADD R2, R2, #0
+        BRzp PUTSP_NEXT_BIT
+        ADD R0, R0, #1
+PUTSP_NEXT_BIT
+        ADD R2, R2, R2
+        ADD R3, R3, #-1
+        BRp PUTSP_SHIFT
+        ADD R0, R0, #0
+        BRz PUTSP_DONE
+PUTSP_HIGH_WAIT
+        LDI R3, OS_DSR
+        BRzp PUTSP_HIGH_WAIT
+        STI R0, OS_DDR
+        ADD R1, R1, #1
+        BRnzp PUTSP_LOOP
+PUTSP_DONE
+        LD R0, PUTSP_SAVE_R0
+        LD R1, OS_SAVE_R1
+        LD R2, OS_SAVE_R2
+        LD R3, OS_SAVE_R3
+        RTI
+PUTSP_SAVE_R0 .BLKW 1
+
+; HALT: Prints a message and stops the machine by clearing the clock enable bit of
+; the machine control register. The registers are restored in case the clock is
+; started again, which returns to the program. PUTS uses OS_SAVE_R1 and sets R7, so
+; they are saved separately
+TRAP_HALT
+        ST R0, HALT_SAVE_R0
+        ST R1, HALT_SAVE_R1
+        ST R7, HALT_SAVE_R7
+        LEA R0, HALT_MSG
+        PUTS
+        LDI R0, OS_MCR
+        LD R1, MASK_HI
+        AND R0, R0, R1
+        STI R0, OS_MCR
+        LD R0, HALT_SAVE_R0
+        LD R1, HALT_SAVE_R1
+        LD R7, HALT_SAVE_R7
+        RTI
+HALT_SAVE_R0 .BLKW 1
+HALT_SAVE_R1 .BLKW 1
+HALT_SAVE_R7 .BLKW 1
+HALT_MSG .STRINGZ "\n--- halting the LC-3 ---\n\n"
+
+; Trap vectors without a trap routine halt the machine
+BAD_TRAP
+        LEA R0, BAD_TRAP_MSG
+        PUTS
+        HALT
+BAD_TRAP_MSG .STRINGZ "\n\n--- undefined trap executed ---\n\n"
+
+; Interrupts without a service routine are ignored
+BAD_INT
+        RTI
+
+; Exceptions print a message and halt the machine
+EX_PRIV
+        LEA R0, PRIV_MSG
+        PUTS
+        HALT
+PRIV_MSG .STRINGZ "\n\n--- privilege mode violation ---\n\n"
+
+EX_ILL
+        LEA R0, ILL_MSG
+        PUTS
+        HALT
+ILL_MSG .STRINGZ "\n\n--- illegal opcode ---\n\n"
+
+EX_ACV
+        LEA R0, ACV_MSG
+        PUTS
+        HALT
+ACV_MSG .STRINGZ "\n\n--- access control violation ---\n\n"
+
+        .END
@@ -0,0 +1,56 @@
+//! This module contains the bundled LC3 operating system, which can be booted in
+//! place of the native trap routines. Its trap routines are written in LC3 assembly
+//! and use the device registers, like the operating system of the reference simulator
+
+#[cfg(test)]
+mod tests;
+
+use super::{registers::PrivilegeMode, Lc3Vm};
+use crate::asm::{assemble, Assembly};
+
+/// The assembly source of the bundled operating system
+pub const LC3OS_SOURCE: &str = include_str!("lc3os.asm");
+/// The address where the operating system starts executing
+pub const OS_START: u16 = 0x0200;
+
+/// Assembles the bundled operating system
+pub fn lc3os() -> Assembly {
+    assemble(LC3OS_SOURCE).expect("the bundled operating system should assemble")
+}
+
+impl Lc3Vm {
+    /// Loads the bundled operating system into memory, and stops running the native
+    /// trap routines. Programs loaded afterwards can replace parts of the operating
+    /// system, such as entries of the trap vector table
+    pub fn load_os(&mut self) {
+        let os = lc3os();
+        for (address, word) in (os.origin..).zip(&os.words) {
+            self.memory.write(address, *word);
+        }
+        self.os_symbols = Some(os.symbols);
+        self.native_troutines = false;
+    }
+
+    /// Starts the machine in supervisor mode at `OS_START`, loading the bundled
+    /// operating system first unless `load_os` was called. The operating system then
+    /// starts the program at the current program counter in user mode. The labels of
+    /// the operating system are added to the VM's symbols
+    pub fn boot_os(&mut self) {
+        if self.native_troutines {
+            self.load_os();
+        }
+        let os_symbols = self
+            .os_symbols
+            .as_ref()
+            .expect("the operating system should have been loaded");
+        let user_pc_address = os_symbols
+            .address("USER_PC")
+            .expect("the operating system should define USER_PC");
+        let user_pc = self.registers.program_counter();
+        self.memory.write(user_pc_address, user_pc);
+        self.symbols.merge(os_symbols);
+
+        self.registers.set_privilege_mode(PrivilegeMode::Supervisor);
+        self.registers.set_program_counter(OS_START);
+    }
+}
@@ -0,0 +1,136 @@
+use super::*;
+use crate::vm::{
+    console::{IoConsole, OutputBuffer},
+    limits::RunLimits,
+    loader::ProgramImage,
+    memory::DeviceRegister,
+};
+
+/// Boots the operating system with `source` assembled as the user program, and runs
+/// it with `input` as the keyboard input. Returns the VM and the display output
+fn run_with_os(source: &str, input: &'static str) -> (Lc3Vm, String) {
+    let output = OutputBuffer::new();
+    let mut vm = Lc3Vm::with_console(IoConsole::new(input.as_bytes(), output.clone()));
+    let assembly = assemble(source).unwrap();
+    for (address, word) in (assembly.origin..).zip(&assembly.words) {
+        vm.memory_mut().write(address, *word);
+    }
+    vm.registers_mut().set_program_counter(assembly.origin);
+    vm.boot_os();
+    vm.set_run_limits(RunLimits::none().with_max_instructions(100_000));
+    vm.run().unwrap();
+    let output = output.to_string_lossy();
+    (vm, output)
+}
+
+#[test]
+fn test_lc3os_assembles() {
+    let os = lc3os();
+    assert_eq!(os.origin, 0);
+    assert_eq!(os.symbols.address("OS_START"), Some(OS_START));
+    assert_eq!(os.words[0x25], os.symbols.address("TRAP_HALT").unwrap());
+}
+
+#[test]
+fn test_boot_os() {
+    let mut vm = Lc3Vm::new();
+    vm.registers_mut().set_program_counter(0x4000);
+    vm.boot_os();
+    assert_eq!(vm.registers().program_counter(), OS_START);
+    assert_eq!(vm.registers().privilege_mode(), PrivilegeMode::Supervisor);
+
+    // The operating system drops to user mode at the program's entry point
+    vm.run_until(|_, step| step.instr == 0x8000).unwrap();
+    assert_eq!(vm.registers().program_counter(), 0x4000);
+    assert_eq!(vm.registers().privilege_mode(), PrivilegeMode::User);
+}
+
+#[test]
+fn test_programs_replace_os() {
+    let mut vm = Lc3Vm::with_console(IoConsole::new("".as_bytes(), OutputBuffer::new()));
+    vm.load_os();
+    // A trap routine, and the entry of the trap vector table that points to it
+    let program = assemble(".ORIG x3000\nTRAP x26\nHALT\n.END").unwrap();
+    let routine = assemble(".ORIG x4000\nADD R5, R5, #7\nRTI\n.END").unwrap();
+    let table = assemble(".ORIG x0026\n.FILL x4000\n.END").unwrap();
+    for assembly in [routine, table, program] {
+        let image = ProgramImage::new(assembly.origin, assembly.words).unwrap();
+        vm.load_image(&image);
+    }
+    vm.boot_os();
+    vm.set_run_limits(RunLimits::none().with_max_instructions(100_000));
+    vm.run().unwrap();
+    assert_eq!(vm.get_reg_val_by_id(5), 7);
+}
+
+#[test]
+fn test_os_trap_routines() {
+    let source = r#"
+        .ORIG x3000
+        LEA R0, HELLO
+        PUTS
+        LEA R0, PACKED
+        PUTSP
+        GETC
+        OUT
+        IN
+        ADD R3, R0, #0
+        HALT
+HELLO   .STRINGZ "hello "
+PACKED  .FILL x6F77
+        .FILL x6C72
+        .FILL x0064
+        .FILL x0000
+        .END"#;
+    let (vm, output) = run_with_os(source, "!?");
+    assert_eq!(vm.get_reg_val_by_id(3), '?' as u16);
+    assert_eq!(
+        output,
+        "hello world!\nInput a character> ?\n\n--- halting the LC-3 ---\n\n"
+    );
+}
+
+#[test]
+fn test_os_exception() {
+    let source = "
+        .ORIG x3000
+        LDI R0, SYSTEM
+        HALT
+SYSTEM  .FILL x0000
+        .END";
+    let (vm, output) = run_with_os(source, "");
+    assert!(!vm.running());
+    assert!(output.starts_with("\n\n--- access control violation ---\n\n"));
+}
+
+#[test]
+fn test_os_bad_trap() {
+    let source = "
+        .ORIG x3000
+        TRAP x40
+        .END";
+    let (_, output) = run_with_os(source, "");
+    assert!(output.starts_with("\n\n--- undefined trap executed ---\n\n"));
+}
+
+#[test]
+fn test_os_halt_saves_registers() {
+    let source = "
+        .ORIG x3000
+        AND R0, R0, #0
+        ADD R0, R0, #3
+        ADD R1, R0, #6
+        HALT
+        .END";
+    let (mut vm, _) = run_with_os(source, "");
+    assert!(!vm.running());
+
+    // Starting the clock again returns to the program
+    vm.memory_mut().write(DeviceRegister::Mcr.address(), 0x8000);
+    vm.run_until(|_, step| step.instr == 0x8000).unwrap();
+    assert_eq!(vm.registers().program_counter(), 0x3004);
+    assert_eq!(vm.registers().privilege_mode(), PrivilegeMode::User);
+    assert_eq!(vm.get_reg_val_by_id(0), 3);
+    assert_eq!(vm.get_reg_val_by_id(1), 9);
+    assert_eq!(vm.get_reg_val_by_id(7), 0x3004);
+}
@@ -1,4 +1,6 @@
-#[derive(Clone, Copy)]
+use std::{fmt, str::FromStr};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Register(u16);
 
 impl Register {
@@ -12,6 +14,7 @@ impl Register {
 }
 
 const GENERAL_REGISTER_COUNT: usize = 8;
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum RegisterName {
     R0 = 0,
     R1 = 1,
@@ -22,27 +25,75 @@ pub enum RegisterName {
     R6 = 6,
     R7 = 7,
     PC = 8, // Program counter
+    /// The condition codes, bits [2:0] of the PSR
     Cond = 9,
+    /// The processor status register
+    Psr = 10,
+}
+
+impl RegisterName {
+    const GENERAL: [RegisterName; GENERAL_REGISTER_COUNT] = [
+        RegisterName::R0,
+        RegisterName::R1,
+        RegisterName::R2,
+        RegisterName::R3,
+        RegisterName::R4,
+        RegisterName::R5,
+        RegisterName::R6,
+        RegisterName::R7,
+    ];
+
+    /// Returns the general purpose register encoded in the lowest 3 bits of `id`
+    pub fn general(id: u16) -> Self {
+        Self::GENERAL[(id & 0b111) as usize]
+    }
 }
 
-impl From<u16> for RegisterName {
-    fn from(value: u16) -> Self {
-        match value {
-            0 => RegisterName::R0,
-            1 => RegisterName::R1,
-            2 => RegisterName::R2,
-            3 => RegisterName::R3,
-            4 => RegisterName::R4,
-            5 => RegisterName::R5,
-            6 => RegisterName::R6,
-            7 => RegisterName::R7,
+impl TryFrom<u16> for RegisterName {
+    type Error = ();
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        let register = match value {
+            0..=7 => Self::general(value),
             8 => RegisterName::PC,
             9 => RegisterName::Cond,
-            _ => panic!("Invalid register value ({})", value),
+            10 => RegisterName::Psr,
+            _ => return Err(()),
+        };
+        Ok(register)
+    }
+}
+
+impl fmt::Display for RegisterName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PC => write!(f, "PC"),
+            Self::Cond => write!(f, "COND"),
+            Self::Psr => write!(f, "PSR"),
+            general => write!(f, "R{}", *general as u16),
         }
     }
 }
 
+impl FromStr for RegisterName {
+    type Err = ();
+    /// Parses the name of a register, as printed by `Display`. Case is ignored
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let register = match s.to_ascii_uppercase().as_str() {
+            "PC" => Self::PC,
+            "COND" => Self::Cond,
+            "PSR" => Self::Psr,
+            name => {
+                let id = name.strip_prefix('R').ok_or(())?;
+                match id.parse::<u16>() {
+                    Ok(id @ 0..=7) => Self::general(id),
+                    _ => return Err(()),
+                }
+            }
+        };
+        Ok(register)
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum ConditionFlag {
     Pos = 0b001,
@@ -71,14 +122,16 @@ impl ConditionFlag {
     }
 }
 
-impl From<u16> for ConditionFlag {
-    fn from(value: u16) -> Self {
-        match value {
+impl TryFrom<u16> for ConditionFlag {
+    type Error = ();
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        let flag = match value {
             0b001 => ConditionFlag::Pos,
             0b010 => ConditionFlag::Zro,
             0b100 => ConditionFlag::Neg,
-            _ => panic!("Invalid condition flag value ({})", value),
-        }
+            _ => return Err(()),
+        };
+        Ok(flag)
     }
 }
 
@@ -88,35 +141,86 @@ impl From<ConditionFlag> for u16 {
     }
 }
 
+/// The privilege mode of the processor, stored in bit [15] of the PSR
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PrivilegeMode {
+    Supervisor = 0,
+    User = 1,
+}
+
+/// The register file of the LC3 VM, which contains the 8 general purpose registers,
+/// the program counter and the processor status register (PSR).
+///
+/// The PSR holds the privilege mode in bit [15], the priority level in bits [10:8]
+/// and the condition codes in bits [2:0]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Registers {
     general_regs: [Register; GENERAL_REGISTER_COUNT],
     program_counter_reg: Register,
-    /// Condition register stores condition flags about most recently executed calcs.
-    /// This allows comparisons, etc
-    condition_reg: Register,
+    /// Processor status register. Its condition codes store condition flags about
+    /// most recently executed calcs. This allows comparisons, etc
+    psr: Register,
+    /// The stack pointer (R6) of the mode that is not running. `saved_usp` holds the
+    /// user stack pointer while in supervisor mode, and `saved_ssp` holds the
+    /// supervisor stack pointer while in user mode
+    saved_usp: Register,
+    saved_ssp: Register,
+}
+
+impl Default for Registers {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Registers {
+    /// The memory mapped address of the PSR
+    pub const PSR_ADDRESS: u16 = 0xfffc;
+    /// The PSR a program starts with: user mode, priority level 0 and the `Zro` flag
+    pub const DEFAULT_PSR: u16 = 0x8002;
+    /// The initial supervisor stack pointer. The supervisor stack grows downwards
+    /// from the end of system space
+    pub const DEFAULT_SAVED_SSP: u16 = 0x3000;
+
+    const PRIVILEGE_BIT: u16 = 1 << 15;
+    const PRIORITY_MASK: u16 = 0b111 << 8;
+    const COND_MASK: u16 = 0b111;
+
+    /// Creates a register file with every register set to `0`, except for the PSR
+    /// which is set to `DEFAULT_PSR`, and the saved supervisor stack pointer which is
+    /// set to `DEFAULT_SAVED_SSP`
     pub fn new() -> Self {
         let general_regs: [Register; GENERAL_REGISTER_COUNT] =
             [Register(0); GENERAL_REGISTER_COUNT];
         let program_counter_reg = Register(0);
-        let condition_reg = Register(0);
+        let psr = Register(Self::DEFAULT_PSR);
         Self {
             general_regs,
             program_counter_reg,
-            condition_reg,
+            psr,
+            saved_usp: Register(0),
+            saved_ssp: Register(Self::DEFAULT_SAVED_SSP),
         }
     }
 
     pub fn get_reg_value(&self, register: RegisterName) -> u16 {
-        let index = register as usize;
-        self.general_regs[index].value()
+        match register {
+            RegisterName::PC => self.program_counter_reg.value(),
+            RegisterName::Cond => self.cond_reg(),
+            RegisterName::Psr => self.psr(),
+            general => self.general_regs[general as usize].value(),
+        }
     }
 
+    /// Sets the value of a register. Only bits [2:0] of the value are used for the
+    /// `Cond` register
     pub fn set_reg_value(&mut self, register: RegisterName, value: u16) {
-        let index = register as usize;
-        self.general_regs[index].set(value);
+        match register {
+            RegisterName::PC => self.program_counter_reg.set(value),
+            RegisterName::Cond => self.set_psr_bits(Self::COND_MASK, value),
+            RegisterName::Psr => self.set_psr(value),
+            general => self.general_regs[general as usize].set(value),
+        }
     }
 
     pub fn program_counter(&self) -> u16 {
@@ -124,7 +228,7 @@ impl Registers {
     }
 
     pub fn increment_program_counter(&mut self) -> u16 {
-        let new_pc_val = self.program_counter_reg.value() + 1;
+        let new_pc_val = self.program_counter_reg.value().wrapping_add(1);
         self.program_counter_reg.set(new_pc_val);
         new_pc_val
     }
@@ -133,11 +237,64 @@ impl Registers {
         self.program_counter_reg.set(value);
     }
 
+    /// Returns the condition codes, bits [2:0] of the PSR
     pub fn cond_reg(&self) -> u16 {
-        self.condition_reg.value()
+        self.psr.value() & Self::COND_MASK
     }
 
     pub fn set_cond_reg(&mut self, flag: ConditionFlag) {
-        self.condition_reg.set(flag.into());
+        self.set_psr_bits(Self::COND_MASK, flag.into());
+    }
+
+    pub fn psr(&self) -> u16 {
+        self.psr.value()
+    }
+
+    pub fn set_psr(&mut self, value: u16) {
+        self.psr.set(value);
+    }
+
+    pub fn privilege_mode(&self) -> PrivilegeMode {
+        if self.psr.value() & Self::PRIVILEGE_BIT == 0 {
+            PrivilegeMode::Supervisor
+        } else {
+            PrivilegeMode::User
+        }
+    }
+
+    pub fn set_privilege_mode(&mut self, mode: PrivilegeMode) {
+        self.set_psr_bits(Self::PRIVILEGE_BIT, (mode as u16) << 15);
+    }
+
+    /// Returns the priority level, from 0 to 7, bits [10:8] of the PSR
+    pub fn priority_level(&self) -> u16 {
+        (self.psr.value() & Self::PRIORITY_MASK) >> 8
+    }
+
+    /// Sets the priority level. Only the lowest 3 bits of `level` are used
+    pub fn set_priority_level(&mut self, level: u16) {
+        self.set_psr_bits(Self::PRIORITY_MASK, level << 8);
+    }
+
+    pub fn saved_usp(&self) -> u16 {
+        self.saved_usp.value()
+    }
+
+    pub fn set_saved_usp(&mut self, value: u16) {
+        self.saved_usp.set(value);
+    }
+
+    pub fn saved_ssp(&self) -> u16 {
+        self.saved_ssp.value()
+    }
+
+    pub fn set_saved_ssp(&mut self, value: u16) {
+        self.saved_ssp.set(value);
+    }
+
+    /// Replaces the bits of the PSR selected by `mask` with those of `value`
+    fn set_psr_bits(&mut self, mask: u16, value: u16) {
+        let psr = (self.psr.value() & !mask) | (value & mask);
+        self.psr.set(psr);
     }
 }
@@ -0,0 +1,202 @@
+//! This module contains the single-step execution API of the VM, which is used to
+//! build debuggers and other tools on top of the fetch and execute loop
+
+#[cfg(test)]
+mod tests;
+
+use std::mem;
+
+use super::{
+    error::VmError,
+    registers::{RegisterName, Registers},
+    Lc3Vm,
+};
+
+/// A register whose value was changed by an executed instruction
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RegisterWrite {
+    pub register: RegisterName,
+    pub old_value: u16,
+    pub new_value: u16,
+}
+
+/// A memory location that was read by an executed instruction. Fetching the
+/// instruction itself is not a read
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MemoryRead {
+    pub address: u16,
+    pub value: u16,
+}
+
+/// A memory location that was written to by an executed instruction
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct MemoryWrite {
+    pub address: u16,
+    pub old_value: u16,
+    pub new_value: u16,
+}
+
+/// Describes what happened when a single instruction was executed by `Lc3Vm::step`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Step {
+    /// The address of the executed instruction
+    pub pc: u16,
+    /// The executed instruction
+    pub instr: u16,
+    /// The value of the program counter after the instruction was executed
+    pub next_pc: u16,
+    /// The registers, apart from the program counter, whose values were changed
+    pub registers_written: Vec<RegisterWrite>,
+    /// The memory locations read, in the order they were read
+    pub memory_read: Vec<MemoryRead>,
+    /// The memory locations written to, in the order they were written
+    pub memory_written: Vec<MemoryWrite>,
+    /// Whether the instruction stopped the machine
+    pub halted: bool,
+    /// The vector of the interrupt that was taken before the instruction was
+    /// fetched. The instruction is the first one of the interrupt's service routine
+    pub interrupt: Option<u8>,
+    /// The vector of the exception raised by the instruction. The next instruction is
+    /// the first one of the exception's service routine
+    pub exception: Option<u8>,
+}
+
+impl Lc3Vm {
+    /// Fetches, decodes and executes exactly one instruction, and returns what the
+    /// instruction did. Pending interrupts are taken before the instruction is
+    /// fetched. The instruction is executed even if the machine has been halted, so
+    /// callers should check `Lc3Vm::running` first
+    pub fn step(&mut self) -> Result<Step, VmError> {
+        let registers_before = self.registers;
+        self.memory_reads.clear();
+        self.memory_writes.clear();
+
+        self.exception = None;
+
+        let interrupt = self.check_interrupts();
+        let pc = self.registers.program_counter();
+        let result = self.fetch_and_execute(pc);
+        let instr = match result {
+            Ok(instr) => instr,
+            Err(_) => self.peek_memory(pc),
+        };
+
+        let step = Step {
+            pc,
+            instr,
+            next_pc: self.registers.program_counter(),
+            registers_written: Self::registers_written(&registers_before, &self.registers),
+            memory_read: mem::take(&mut self.memory_reads),
+            memory_written: mem::take(&mut self.memory_writes),
+            halted: !self.running(),
+            interrupt,
+            exception: self.exception,
+        };
+        match result {
+            Ok(_) => {
+                self.trace_step(&step, None).map_err(VmError::Trace)?;
+                Ok(step)
+            }
+            Err(error) => {
+                // The error that stopped the program is more important than a failure
+                // to write it to the trace
+                let _ = self.trace_step(&step, Some(&error));
+                Err(error)
+            }
+        }
+    }
+
+    /// Fetches and executes the instruction at `pc`, and returns the instruction
+    fn fetch_and_execute(&mut self, pc: u16) -> Result<u16, VmError> {
+        match self.check_access(pc) {
+            Ok(()) => {
+                let instr = self.fetch_memory(pc);
+                self.registers.increment_program_counter();
+                // Native trap routines run before their `RTI` returns to the caller.
+                // `HALT` stops the machine before it returns
+                if let Some(trap_vec) = self.native_troutine_at(pc, instr) {
+                    self.run_troutine(trap_vec)
+                        .map_err(|fault| fault.at(pc, instr))?;
+                }
+                if self.running() {
+                    self.run_op(instr)?;
+                }
+                Ok(instr)
+            }
+            // The instruction is not fetched, and the exception is raised instead
+            Err(fault) => {
+                let instr = self.peek_memory(pc);
+                self.raise_exception(fault)
+                    .map_err(|fault| fault.at(pc, instr))?;
+                Ok(instr)
+            }
+        }
+    }
+
+    /// Executes up to `count` instructions, stopping early if the machine halts.
+    /// Returns the number of instructions that were executed
+    pub fn run_for(&mut self, count: usize) -> Result<usize, VmError> {
+        let mut executed = 0;
+        while executed < count && self.running() {
+            self.step()?;
+            executed += 1;
+        }
+        Ok(executed)
+    }
+
+    /// Executes instructions until `predicate` returns `true` for an executed
+    /// instruction, and returns that instruction's `Step`. `None` is returned if the
+    /// machine halts before the predicate is satisfied
+    pub fn run_until<F>(&mut self, mut predicate: F) -> Result<Option<Step>, VmError>
+    where
+        F: FnMut(&Lc3Vm, &Step) -> bool,
+    {
+        while self.running() {
+            let step = self.step()?;
+            if predicate(self, &step) {
+                return Ok(Some(step));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Records a memory read made by the current instruction, so that it can be
+    /// reported by `step`
+    pub(crate) fn read_memory(&mut self, address: u16) -> u16 {
+        let value = self.fetch_memory(address);
+        self.memory_reads.push(MemoryRead { address, value });
+        value
+    }
+
+    /// Records a memory write made by the current instruction, so that it can be
+    /// reported by `step`
+    pub(crate) fn write_memory(&mut self, address: u16, value: u16) {
+        let old_value = self.peek_memory(address);
+        if address == Registers::PSR_ADDRESS {
+            self.registers.set_psr(value);
+        } else {
+            self.memory.write(address, value);
+        }
+        self.memory_writes.push(MemoryWrite {
+            address,
+            old_value,
+            new_value: value,
+        });
+    }
+
+    fn registers_written(before: &Registers, after: &Registers) -> Vec<RegisterWrite> {
+        (0..8)
+            .map(RegisterName::general)
+            .chain([RegisterName::Cond])
+            .filter_map(|register| {
+                let old_value = before.get_reg_value(register);
+                let new_value = after.get_reg_value(register);
+                (old_value != new_value).then_some(RegisterWrite {
+                    register,
+                    old_value,
+                    new_value,
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,135 @@
+use super::*;
+use crate::vm::registers::PrivilegeMode;
+
+/// Writes `program` into memory, starting at `Lc3Vm::DEFAULT_PC_START`
+fn load_words(vm: &mut Lc3Vm, program: &[u16]) {
+    for (address, word) in (Lc3Vm::DEFAULT_PC_START..).zip(program) {
+        vm.memory.write(address, *word);
+    }
+}
+
+#[test]
+#[allow(clippy::unusual_byte_groupings)]
+fn test_step_register_write() {
+    let mut vm = Lc3Vm::new();
+    // ADD R1, R1, #5
+    let instr: u16 = 0b0001_001_001_1_00101;
+    load_words(&mut vm, &[instr]);
+
+    let step = vm.step().unwrap();
+    assert_eq!(step.pc, Lc3Vm::DEFAULT_PC_START);
+    assert_eq!(step.instr, instr);
+    assert_eq!(step.next_pc, Lc3Vm::DEFAULT_PC_START + 1);
+    assert!(step.memory_written.is_empty());
+    assert!(!step.halted);
+    assert_eq!(
+        step.registers_written,
+        vec![
+            RegisterWrite {
+                register: RegisterName::R1,
+                old_value: 0,
+                new_value: 5,
+            },
+            RegisterWrite {
+                register: RegisterName::Cond,
+                old_value: 0b010,
+                new_value: 0b001,
+            },
+        ]
+    );
+}
+
+#[test]
+#[allow(clippy::unusual_byte_groupings)]
+fn test_step_memory_write() {
+    let mut vm = Lc3Vm::new();
+    // ST R3, #4
+    let instr: u16 = 0b0011_011_000000100;
+    load_words(&mut vm, &[instr]);
+    vm.set_reg_val_by_id(3, 0x1234);
+    vm.memory.write(0x3005, 0x0042);
+
+    let step = vm.step().unwrap();
+    assert!(step.registers_written.is_empty());
+    assert!(step.memory_read.is_empty());
+    assert_eq!(
+        step.memory_written,
+        vec![MemoryWrite {
+            address: 0x3005,
+            old_value: 0x0042,
+            new_value: 0x1234,
+        }]
+    );
+}
+
+#[test]
+#[allow(clippy::unusual_byte_groupings)]
+fn test_step_memory_read() {
+    let mut vm = Lc3Vm::new();
+    // LDI R2, #1
+    let instr: u16 = 0b1010_010_000000001;
+    load_words(&mut vm, &[instr, 0, 0x4000]);
+    vm.memory.write(0x4000, 0x0007);
+
+    // The instruction fetch is not reported
+    let step = vm.step().unwrap();
+    assert_eq!(
+        step.memory_read,
+        vec![
+            MemoryRead {
+                address: 0x3002,
+                value: 0x4000,
+            },
+            MemoryRead {
+                address: 0x4000,
+                value: 0x0007,
+            },
+        ]
+    );
+    assert!(step.memory_written.is_empty());
+}
+
+#[test]
+#[allow(clippy::unusual_byte_groupings)]
+fn test_run_for() {
+    let mut vm = Lc3Vm::new();
+    // ADD R0, R0, #1 three times, then STI R1 to the MCR to halt
+    let add: u16 = 0b0001_000_000_1_00001;
+    let sti: u16 = 0b1011_001_000000000;
+    load_words(&mut vm, &[add, add, add, sti, 0xfffe]);
+    // Only the supervisor can access the MCR
+    vm.registers.set_privilege_mode(PrivilegeMode::Supervisor);
+
+    let executed = vm.run_for(2).unwrap();
+    assert_eq!(executed, 2);
+    assert_eq!(vm.get_reg_val_by_id(0), 2);
+
+    // The program halts before the requested count is reached
+    let executed = vm.run_for(10).unwrap();
+    assert_eq!(executed, 2);
+    assert!(!vm.running());
+}
+
+#[test]
+#[allow(clippy::unusual_byte_groupings)]
+fn test_run_until() {
+    let mut vm = Lc3Vm::new();
+    let add: u16 = 0b0001_000_000_1_00001;
+    let sti: u16 = 0b1011_001_000000000;
+    load_words(&mut vm, &[add, add, add, sti, 0xfffe]);
+    // Only the supervisor can access the MCR
+    vm.registers.set_privilege_mode(PrivilegeMode::Supervisor);
+
+    let step = vm
+        .run_until(|vm, _| vm.get_reg_val_by_id(0) == 2)
+        .unwrap()
+        .unwrap();
+    assert_eq!(step.pc, Lc3Vm::DEFAULT_PC_START + 1);
+
+    let step = vm.run_until(|_, step| step.halted).unwrap().unwrap();
+    assert_eq!(step.instr, sti);
+
+    // Nothing is executed once the machine has halted
+    let step = vm.run_until(|_, _| true).unwrap();
+    assert!(step.is_none());
+}
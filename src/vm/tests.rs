@@ -1,6 +1,9 @@
 use std::io::Write;
 
 use super::*;
+use console::{IoConsole, OutputBuffer};
+use error::LoadError;
+use registers::PrivilegeMode;
 use tempfile::NamedTempFile;
 
 #[test]
@@ -42,22 +45,209 @@ fn test_load_program() {
 }
 
 #[test]
-fn test_read_u16() {
-    let le_bytes: [u8; 2] = [0x2b, 0x2e];
-    let be_u16 = Lc3Vm::read_u16(&le_bytes);
-    assert_eq!(be_u16, 0x2b2e);
+fn test_accessors() {
+    let mut vm = Lc3Vm::default();
+    assert_eq!(vm.registers().program_counter(), Lc3Vm::DEFAULT_PC_START);
+
+    vm.registers_mut().set_reg_value(RegisterName::R3, 0x1234);
+    assert_eq!(vm.get_reg_val_by_id(3), 0x1234);
+
+    vm.memory_mut().write(0x4000, 0xbeef);
+    assert_eq!(vm.memory.read(0x4000), 0xbeef);
+}
+
+#[test]
+fn test_load_program_too_large() {
+    let mut vm = Lc3Vm::new();
+    let mut temp_file = NamedTempFile::new().unwrap();
+    let mut write_data = 0xff00_u16.to_be_bytes().to_vec();
+    write_data.extend(vec![0; 0x202]);
+    temp_file.write_all(&write_data).unwrap();
+
+    let err = vm.load_program(temp_file.path()).unwrap_err();
+    assert!(matches!(
+        err,
+        VmError::Load(LoadError::TooLarge { origin: 0xff00, .. })
+    ));
+}
+
+/// Creates an object file containing `words`, where the first word is the origin
+fn obj_file(words: &[u16]) -> NamedTempFile {
+    let mut temp_file = NamedTempFile::new().unwrap();
+    let write_data = words
+        .iter()
+        .flat_map(|word| word.to_be_bytes())
+        .collect::<Vec<u8>>();
+    temp_file.write_all(&write_data).unwrap();
+    temp_file
+}
+
+#[test]
+fn test_load_programs() {
+    let mut vm = Lc3Vm::new();
+    let program = obj_file(&[0x4000, 0x1111, 0x2222]);
+    let data = obj_file(&[0x5000, 0x3333]);
+    let handler = obj_file(&[0x4002, 0x4444]);
+    let ranges = vm
+        .load_programs(&[program.path(), data.path(), handler.path()])
+        .unwrap();
+    assert_eq!(
+        ranges,
+        vec![0x4000..=0x4001, 0x5000..=0x5000, 0x4002..=0x4002]
+    );
+    assert_eq!(vm.memory.read(0x4000), 0x1111);
+    assert_eq!(vm.memory.read(0x4001), 0x2222);
+    assert_eq!(vm.memory.read(0x4002), 0x4444);
+    assert_eq!(vm.memory.read(0x5000), 0x3333);
+    // Execution starts at the first program
+    assert_eq!(vm.registers.program_counter(), 0x4000);
+}
+
+#[test]
+fn test_load_programs_overlap() {
+    let mut vm = Lc3Vm::new();
+    let program = obj_file(&[0x4000, 0x1111, 0x2222, 0x3333]);
+    let data = obj_file(&[0x4002, 0x4444, 0x5555]);
+    let err = vm
+        .load_programs(&[program.path(), data.path()])
+        .unwrap_err();
+    let VmError::Load(LoadError::Overlap {
+        first,
+        second,
+        start,
+        end,
+    }) = err
+    else {
+        panic!("expected an overlap error, found {err:?}");
+    };
+    assert_eq!(first, program.path());
+    assert_eq!(second, data.path());
+    assert_eq!((start, end), (0x4002, 0x4002));
+    // Nothing is loaded
+    assert_eq!(vm.memory.read(0x4000), 0);
+}
+
+#[test]
+fn test_run_instruction_limit() {
+    let mut vm = Lc3Vm::new();
+    // BRnzp #-1, which loops forever
+    vm.memory.write(Lc3Vm::DEFAULT_PC_START, 0x0fff);
+    vm.set_run_limits(RunLimits::none().with_max_instructions(100));
+
+    let err = vm.run().unwrap_err();
+    assert!(matches!(
+        err,
+        VmError::LimitExceeded {
+            pc: Lc3Vm::DEFAULT_PC_START,
+            limit: Limit::Instructions(100)
+        }
+    ));
+}
+
+#[test]
+fn test_run_duration_limit() {
+    let mut vm = Lc3Vm::new();
+    vm.memory.write(Lc3Vm::DEFAULT_PC_START, 0x0fff);
+    let max_duration = std::time::Duration::from_millis(20);
+    vm.set_run_limits(RunLimits::none().with_max_duration(max_duration));
+
+    let err = vm.run().unwrap_err();
+    assert!(matches!(
+        err,
+        VmError::LimitExceeded {
+            limit: Limit::Duration(_),
+            ..
+        }
+    ));
 }
 
 #[test]
-fn test_validate_file_len() {
-    let invalid_file_len: u64 = 65536;
-    let valid_file_len: u64 = 2000;
-    let origin = 0x3000;
+fn test_run_with_console() {
+    let output = OutputBuffer::new();
+    let mut vm = Lc3Vm::with_console(IoConsole::new("x".as_bytes(), output.clone()));
+    // GETC, OUT, HALT
+    let program: [u16; 3] = [0xf020, 0xf021, 0xf025];
+    for (address, instr) in (Lc3Vm::DEFAULT_PC_START..).zip(program) {
+        vm.memory.write(address, instr);
+    }
+
+    vm.run().unwrap();
+    assert!(output.to_string_lossy().starts_with('x'));
+}
 
-    let invalid_check = Lc3Vm::validate_file_len(invalid_file_len, origin);
-    let valid_check = Lc3Vm::validate_file_len(valid_file_len, origin);
-    assert!(valid_check.is_ok());
-    assert!(invalid_check.is_err());
-    let err = invalid_check.unwrap_err();
-    assert_eq!(err, invalid_file_len);
+#[test]
+fn test_register_names() {
+    for id in 0..11 {
+        let register = RegisterName::try_from(id).unwrap();
+        assert_eq!(register.to_string().parse(), Ok(register));
+    }
+    assert_eq!("r3".parse(), Ok(RegisterName::R3));
+    assert_eq!("cond".parse(), Ok(RegisterName::Cond));
+    assert_eq!("R8".parse::<RegisterName>(), Err(()));
+    assert_eq!("X1".parse::<RegisterName>(), Err(()));
+}
+
+#[test]
+fn test_psr() {
+    let mut registers = Registers::new();
+    assert_eq!(registers.psr(), Registers::DEFAULT_PSR);
+    assert_eq!(registers.privilege_mode(), PrivilegeMode::User);
+    assert_eq!(registers.priority_level(), 0);
+    assert_eq!(registers.cond_reg(), ConditionFlag::Zro as u16);
+
+    registers.set_privilege_mode(PrivilegeMode::Supervisor);
+    registers.set_priority_level(4);
+    registers.set_cond_reg(ConditionFlag::Neg);
+    assert_eq!(registers.psr(), 0x0404);
+    assert_eq!(registers.get_reg_value(RegisterName::Cond), 0b100);
+
+    // Setting the condition codes leaves the rest of the PSR alone
+    registers.set_reg_value(RegisterName::Cond, 0xfff9);
+    assert_eq!(registers.psr(), 0x0401);
+    registers.set_reg_value(RegisterName::Psr, 0x8702);
+    assert_eq!(registers.privilege_mode(), PrivilegeMode::User);
+    assert_eq!(registers.priority_level(), 7);
+}
+
+#[test]
+#[allow(clippy::unusual_byte_groupings)]
+fn test_psr_memory_mapped() {
+    let mut vm = Lc3Vm::new();
+    // Only the supervisor can access the PSR
+    vm.registers.set_privilege_mode(PrivilegeMode::Supervisor);
+    vm.registers.set_priority_level(2);
+    vm.set_reg_val_by_id(1, Registers::PSR_ADDRESS);
+    // LDR R0, R1, #0
+    vm.memory.write(0x3000, 0b0110_000_001_000000);
+    vm.step().unwrap();
+    assert_eq!(vm.get_reg_val_by_id(0), 0x0202);
+    // The load sets the condition codes in the PSR
+    assert_eq!(vm.peek_memory(Registers::PSR_ADDRESS), 0x0201);
+
+    // STR R2, R1, #0
+    vm.memory.write(0x3001, 0b0111_010_001_000000);
+    vm.set_reg_val_by_id(2, 0x8004);
+    vm.step().unwrap();
+    assert_eq!(vm.registers.psr(), 0x8004);
+    assert_eq!(vm.registers.privilege_mode(), PrivilegeMode::User);
+}
+
+#[test]
+fn test_error_with_symbols() {
+    let mut vm = Lc3Vm::new();
+    vm.symbols_mut().insert("MAIN", 0x3000);
+    // ADD R0, R0, #1, then LDI R0, #14 which reads the address xFE00 from x3010
+    vm.memory.write(0x3000, 0x1021);
+    vm.memory.write(0x3001, 0xa00e);
+    vm.memory.write(0x3010, 0xfe00);
+    vm.run_for(1).unwrap();
+    let err = vm.step().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "access violation at xFE00 in user mode while executing xA00E at x3001"
+    );
+    assert_eq!(
+        err.with_symbols(vm.symbols()).to_string(),
+        "access violation at xFE00 in user mode while executing xA00E at x3001 (MAIN+1)"
+    );
 }
@@ -0,0 +1,236 @@
+//! This module contains the execution trace of the VM, which writes a record of every
+//! executed instruction so that the runs of two programs can be compared
+
+#[cfg(test)]
+mod tests;
+
+use std::{
+    fmt::Write as _,
+    io::{self, BufWriter, Write},
+    str::FromStr,
+};
+
+use serde_json::{json, Value};
+
+use super::{
+    error::VmError,
+    registers::{ConditionFlag, RegisterName},
+    step::Step,
+    Lc3Vm,
+};
+use crate::{disasm::disassemble_instr, symbols::SymbolTable};
+
+/// The width of the location column of text records, which fits an address and a
+/// short label
+const LOCATION_WIDTH: usize = 20;
+/// The width of the disassembly column of text records
+const DISASSEMBLY_WIDTH: usize = 24;
+
+/// How the records of a trace are written
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TraceFormat {
+    /// One line of aligned columns per instruction, meant to be read and diffed
+    Text,
+    /// One JSON object per line, meant to be processed by other tools
+    JsonLines,
+}
+
+impl FromStr for TraceFormat {
+    type Err = ();
+    /// Parses `text` or `jsonl`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "jsonl" => Ok(Self::JsonLines),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Writes a record for every instruction executed by the VM it is given to with
+/// `Lc3Vm::set_trace`
+pub struct Trace {
+    writer: BufWriter<Box<dyn Write>>,
+    format: TraceFormat,
+}
+
+impl Trace {
+    pub fn new(writer: impl Write + 'static, format: TraceFormat) -> Self {
+        Self {
+            writer: BufWriter::new(Box::new(writer)),
+            format,
+        }
+    }
+
+    /// Writes the record of an executed instruction. `cond` is the condition flag after
+    /// the instruction was executed, and addresses are also shown relative to the
+    /// labels of `symbols`. `error` is the error the instruction failed with, in which
+    /// case `step` holds the changes made before it failed
+    pub fn record(
+        &mut self,
+        step: &Step,
+        cond: ConditionFlag,
+        symbols: &SymbolTable,
+        error: Option<&VmError>,
+    ) -> io::Result<()> {
+        let error = error.map(|e| e.with_symbols(symbols).to_string());
+        let record = match self.format {
+            TraceFormat::Text => text_record(step, cond, symbols, error),
+            TraceFormat::JsonLines => json_record(step, cond, symbols, error).to_string(),
+        };
+        writeln!(self.writer, "{record}")
+    }
+
+    /// Writes out the buffered records
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl Lc3Vm {
+    /// Starts writing a record of every instruction executed by `step` and `run` to
+    /// `trace`, replacing any previous trace
+    pub fn set_trace(&mut self, trace: Trace) {
+        self.trace = Some(trace);
+    }
+
+    /// Stops tracing, and returns the trace so that it can be flushed
+    pub fn take_trace(&mut self) -> Option<Trace> {
+        self.trace.take()
+    }
+
+    /// Writes the record of an executed instruction if tracing is enabled, including
+    /// the error it failed with
+    pub(crate) fn trace_step(&mut self, step: &Step, error: Option<&VmError>) -> io::Result<()> {
+        let cond = self.get_cond_flag();
+        match &mut self.trace {
+            Some(trace) => trace.record(step, cond, &self.symbols, error),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes out the buffered records of the trace, if there is one
+    pub(crate) fn flush_trace(&mut self) -> Result<(), VmError> {
+        match &mut self.trace {
+            Some(trace) => trace.flush().map_err(VmError::Trace),
+            None => Ok(()),
+        }
+    }
+}
+
+fn cond_letter(cond: ConditionFlag) -> char {
+    match cond {
+        ConditionFlag::Neg => 'n',
+        ConditionFlag::Zro => 'z',
+        ConditionFlag::Pos => 'p',
+    }
+}
+
+/// Renders a step as aligned columns of the location, the instruction word, its
+/// disassembly and the condition flag, followed by what the instruction changed,
+/// like `x3002 (LOOP)  103F  ADD R0, R0, #-1  p  R0 x0003 -> x0002`
+fn text_record(
+    step: &Step,
+    cond: ConditionFlag,
+    symbols: &SymbolTable,
+    error: Option<String>,
+) -> String {
+    let mut record = format!(
+        "{:<LOCATION_WIDTH$}  {:04X}  {:<DISASSEMBLY_WIDTH$}  {}",
+        symbols.describe(step.pc),
+        step.instr,
+        disassemble_instr(step.instr, step.pc, Some(symbols)),
+        cond_letter(cond)
+    );
+    if let Some(vector) = step.interrupt {
+        let _ = write!(record, "  interrupt x{vector:02X}");
+    }
+    // The condition flag is already shown in its own column
+    for write in &step.registers_written {
+        if write.register != RegisterName::Cond {
+            let _ = write!(
+                record,
+                "  {} x{:04X} -> x{:04X}",
+                write.register, write.old_value, write.new_value
+            );
+        }
+    }
+    for read in &step.memory_read {
+        let _ = write!(
+            record,
+            "  read {} x{:04X}",
+            symbols.describe(read.address),
+            read.value
+        );
+    }
+    for write in &step.memory_written {
+        let _ = write!(
+            record,
+            "  wrote {} x{:04X} -> x{:04X}",
+            symbols.describe(write.address),
+            write.old_value,
+            write.new_value
+        );
+    }
+    if let Some(vector) = step.exception {
+        let _ = write!(record, "  exception x{vector:02X}");
+    }
+    if step.halted {
+        record += "  halted";
+    }
+    if let Some(error) = error {
+        let _ = write!(record, "  error: {error}");
+    }
+    record
+}
+
+/// Renders a step as a JSON object. Every record has the same fields, which are
+/// `null` or empty when they do not apply
+fn json_record(
+    step: &Step,
+    cond: ConditionFlag,
+    symbols: &SymbolTable,
+    error: Option<String>,
+) -> Value {
+    let registers = step
+        .registers_written
+        .iter()
+        .map(|write| {
+            json!({
+                "register": write.register.to_string(),
+                "old": write.old_value,
+                "new": write.new_value,
+            })
+        })
+        .collect::<Vec<Value>>();
+    let reads = step
+        .memory_read
+        .iter()
+        .map(|read| json!({ "address": read.address, "value": read.value }))
+        .collect::<Vec<Value>>();
+    let writes = step
+        .memory_written
+        .iter()
+        .map(|write| {
+            json!({
+                "address": write.address,
+                "old": write.old_value,
+                "new": write.new_value,
+            })
+        })
+        .collect::<Vec<Value>>();
+    json!({
+        "pc": step.pc,
+        "label": symbols.label_offset(step.pc),
+        "instr": step.instr,
+        "disassembly": disassemble_instr(step.instr, step.pc, Some(symbols)),
+        "registers": registers,
+        "reads": reads,
+        "writes": writes,
+        "cond": cond_letter(cond).to_string(),
+        "interrupt": step.interrupt,
+        "exception": step.exception,
+        "halted": step.halted,
+        "error": error,
+    })
+}
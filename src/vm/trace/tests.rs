@@ -0,0 +1,113 @@
+use super::*;
+use crate::{
+    asm::assemble,
+    vm::console::{IoConsole, OutputBuffer},
+};
+
+const PROGRAM: &str = "
+        .ORIG x3000
+        LD R0, COUNT
+LOOP    ADD R0, R0, #-1
+        BRp LOOP
+        ST R0, COUNT
+        HALT
+COUNT   .FILL #2
+        .END
+";
+
+/// Runs `PROGRAM` with a trace in `format`, and returns the records of the trace
+fn trace(format: TraceFormat) -> Vec<String> {
+    let assembly = assemble(PROGRAM).unwrap();
+    let mut vm = Lc3Vm::with_console(IoConsole::new("".as_bytes(), OutputBuffer::new()));
+    for (address, word) in (assembly.origin..).zip(&assembly.words) {
+        vm.memory_mut().write(address, *word);
+    }
+    vm.set_symbols(assembly.symbols);
+    let output = OutputBuffer::new();
+    vm.set_trace(Trace::new(output.clone(), format));
+    vm.run().unwrap();
+    output
+        .to_string_lossy()
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn test_text_trace() {
+    let records = trace(TraceFormat::Text);
+    assert_eq!(
+        records[..6],
+        [
+            "x3000                 2004  LD R0, COUNT              p  R0 x0000 -> x0002  read x3005 (COUNT) x0002",
+            "x3001 (LOOP)          103F  ADD R0, R0, #-1           p  R0 x0002 -> x0001",
+            "x3002 (LOOP+1)        03FE  BRp LOOP                  p",
+            "x3001 (LOOP)          103F  ADD R0, R0, #-1           z  R0 x0001 -> x0000",
+            "x3002 (LOOP+1)        03FE  BRp LOOP                  z",
+            "x3003 (LOOP+2)        3001  ST R0, COUNT              z  wrote x3005 (COUNT) x0002 -> x0000",
+        ]
+    );
+    // The HALT trap routine runs until the machine stops
+    assert!(records.last().unwrap().ends_with("halted"));
+}
+
+#[test]
+fn test_json_lines_trace() {
+    let records = trace(TraceFormat::JsonLines)
+        .iter()
+        .map(|record| serde_json::from_str(record).unwrap())
+        .collect::<Vec<Value>>();
+    assert_eq!(
+        records[0],
+        json!({
+            "pc": 0x3000,
+            "label": null,
+            "instr": 0x2004,
+            "disassembly": "LD R0, COUNT",
+            "registers": [
+                { "register": "R0", "old": 0, "new": 2 },
+                { "register": "COND", "old": 0b010, "new": 0b001 },
+            ],
+            "reads": [{ "address": 0x3005, "value": 2 }],
+            "writes": [],
+            "cond": "p",
+            "interrupt": null,
+            "exception": null,
+            "halted": false,
+            "error": null,
+        })
+    );
+    assert_eq!(records[3]["label"], "LOOP");
+    assert_eq!(records[3]["cond"], "z");
+    assert_eq!(
+        records[5]["writes"][0],
+        json!({ "address": 0x3005, "old": 2, "new": 0 })
+    );
+    assert_eq!(records.last().unwrap()["halted"], true);
+}
+
+#[test]
+fn test_trace_fault() {
+    let mut vm = Lc3Vm::with_console(IoConsole::new("".as_bytes(), OutputBuffer::new()));
+    // An illegal opcode, for which there is no exception service routine
+    vm.memory_mut().write(0x3000, 0xd000);
+    let output = OutputBuffer::new();
+    vm.set_trace(Trace::new(output.clone(), TraceFormat::JsonLines));
+    assert!(vm.run().is_err());
+
+    let records = output.to_string_lossy();
+    let record: Value = serde_json::from_str(records.trim_end()).unwrap();
+    assert_eq!(record["pc"], 0x3000);
+    assert_eq!(record["instr"], 0xd000);
+    assert_eq!(
+        record["error"],
+        "illegal opcode in instruction xD000 at x3000"
+    );
+}
+
+#[test]
+fn test_trace_format() {
+    assert_eq!("text".parse(), Ok(TraceFormat::Text));
+    assert_eq!("jsonl".parse(), Ok(TraceFormat::JsonLines));
+    assert_eq!("json".parse::<TraceFormat>(), Err(()));
+}
@@ -1,14 +1,27 @@
+//! This module contains the trap routines of the VM. The routines are implemented
+//! natively, and are installed in the trap vector table when the VM is created. Each
+//! native routine occupies a single `RTI` instruction in system space, and the
+//! routine runs when that instruction is fetched. Programs can replace the native
+//! routines, or add new ones, by writing to the trap vector table
+
 #[cfg(test)]
 mod tests;
 
-use super::{registers::RegisterName, Lc3Vm};
+use super::{
+    error::Fault, memory::DeviceRegister, registers::RegisterName, step::MemoryWrite, Lc3Vm,
+};
 use ascii::AsciiChar;
-use std::io::{stdin, stdout, Read, Write};
+
+/// The address of the native trap routine for `GETC`. The routines of the other trap
+/// vectors follow in the order of their vectors
+pub const NATIVE_TROUTINES_START: u16 = 0x0400;
+/// The `RTI` instruction that marks the address of a native trap routine
+const NATIVE_TROUTINE_INSTR: u16 = 0x8000;
 
 const IN_TROUTINE_PROMPT: &str = "Enter a character: ";
 const HALT_MESSAGE: &str = "LC3 VM execution halted\n";
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum TrapVector {
     Getc = 0x20,
     Out = 0x21,
@@ -34,45 +47,90 @@ impl TryFrom<u16> for TrapVector {
     }
 }
 
+impl TrapVector {
+    /// The trap vectors that have native trap routines
+    pub const ALL: [TrapVector; 6] = [
+        Self::Getc,
+        Self::Out,
+        Self::Puts,
+        Self::In,
+        Self::Putsp,
+        Self::Halt,
+    ];
+
+    /// The name of the trap routine, as written in assembly
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Getc => "GETC",
+            Self::Out => "OUT",
+            Self::Puts => "PUTS",
+            Self::In => "IN",
+            Self::Putsp => "PUTSP",
+            Self::Halt => "HALT",
+        }
+    }
+
+    /// The address of the native trap routine
+    pub fn native_address(self) -> u16 {
+        NATIVE_TROUTINES_START + (self as u16 - Self::Getc as u16)
+    }
+}
+
 impl Lc3Vm {
-    pub fn run_troutine(&mut self, trap_vec: TrapVector) {
-        let mut stdin = stdin().lock();
-        let mut stdout = stdout().lock();
+    /// Installs the native trap routines at their default addresses, and points the
+    /// trap vector table entries at them
+    pub fn install_native_troutines(&mut self) {
+        for trap_vec in TrapVector::ALL {
+            let address = trap_vec.native_address();
+            self.memory.write(trap_vec as u16, address);
+            self.memory.write(address, NATIVE_TROUTINE_INSTR);
+        }
+        self.native_troutines = true;
+    }
+
+    /// Finds the native trap routine whose address is `pc`. `None` is returned if
+    /// `instr`, the instruction at `pc`, is not the one that marks a native routine,
+    /// because the routine was overwritten, or if an operating system was booted
+    pub(crate) fn native_troutine_at(&self, pc: u16, instr: u16) -> Option<TrapVector> {
+        if !self.native_troutines || instr != NATIVE_TROUTINE_INSTR {
+            return None;
+        }
+        TrapVector::ALL
+            .into_iter()
+            .find(|trap_vec| trap_vec.native_address() == pc)
+    }
+
+    pub(crate) fn run_troutine(&mut self, trap_vec: TrapVector) -> Result<(), Fault> {
         match trap_vec {
-            TrapVector::Getc => self.getc_troutine(&mut stdin),
-            TrapVector::Out => self.out_troutine(&mut stdout),
-            TrapVector::Puts => self.puts_troutine(&mut stdout),
-            TrapVector::In => self.in_troutine(&mut stdin, &mut stdout),
-            TrapVector::Putsp => self.putsp_troutine(&mut stdout),
-            TrapVector::Halt => self.halt_troutine(&mut stdout),
+            TrapVector::Getc => self.getc_troutine(),
+            TrapVector::Out => self.out_troutine(),
+            TrapVector::Puts => self.puts_troutine(),
+            TrapVector::In => self.in_troutine(),
+            TrapVector::Putsp => self.putsp_troutine(),
+            TrapVector::Halt => self.halt_troutine(),
         }
     }
 
     /// Read a single character from the keyboard. The character is not echoed onto
     /// the console. Its ASCII code is copied into R0.
     /// The high eight bits of R0 are cleared.
-    fn getc_troutine<R>(&mut self, input_reader: &mut R)
-    where
-        R: Read,
-    {
-        let mut read_char: [u8; 1] = [0];
-        input_reader.read_exact(&mut read_char).unwrap();
-        let ascii_char = AsciiChar::new(read_char[0] as char);
+    fn getc_troutine(&mut self) -> Result<(), Fault> {
+        let read_char = self.memory.read_key()?;
+        let ascii_char = Self::parse_ascii(read_char)?;
         self.registers
             .set_reg_value(RegisterName::R0, ascii_char as u16);
+        Ok(())
     }
 
     /// Write a character in R0[7:0] to the console display.
-    fn out_troutine<W>(&mut self, output_writer: &mut W)
-    where
-        W: Write,
-    {
+    fn out_troutine(&mut self) -> Result<(), Fault> {
         let read_data = self.registers.get_reg_value(RegisterName::R0);
         // Read least significant bits for parsing ascii character to print
         let byte_slice: [u8; 2] = read_data.to_be_bytes();
         let char_byte = byte_slice[1];
-        let ascii_char = AsciiChar::from_ascii(char_byte).unwrap();
-        write!(output_writer, "{}", ascii_char).unwrap();
+        let ascii_char = Self::parse_ascii(char_byte)?;
+        self.write_display(&[ascii_char.as_byte()])?;
+        Ok(())
     }
 
     /// Write a string of ASCII characters to the console display.
@@ -80,15 +138,13 @@ impl Lc3Vm {
     /// one character per memory location, starting with the address
     /// specified in R0.
     /// Writing terminates with the occurrence of x0000 in a memory location
-    fn puts_troutine<W>(&mut self, output_writer: &mut W)
-    where
-        W: Write,
-    {
+    fn puts_troutine(&mut self) -> Result<(), Fault> {
         let str_start_addr = self.registers.get_reg_value(RegisterName::R0);
 
+        let mut output: Vec<u8> = Vec::new();
         let mut current_addr = str_start_addr;
         loop {
-            let mem_data = self.memory.read(current_addr);
+            let mem_data = self.read_memory(current_addr);
             // If null character (0) then terminate
             if mem_data == 0 {
                 break;
@@ -97,32 +153,27 @@ impl Lc3Vm {
             // Convert the u16 to u8, truncating the most significant bits
             let byte_slice: [u8; 2] = mem_data.to_be_bytes();
             let char_byte = byte_slice[1];
-            let ascii_char = match AsciiChar::from_ascii(char_byte) {
-                Ok(ch) => ch,
-                Err(_) => panic!("Invalid ASCII character encountered in puts trap vector!"),
-            };
+            let ascii_char = Self::parse_ascii(char_byte)?;
 
-            write!(output_writer, "{}", ascii_char).unwrap();
-            current_addr += 1;
+            output.push(ascii_char.as_byte());
+            current_addr = current_addr.wrapping_add(1);
         }
+        self.write_display(&output)?;
+        Ok(())
     }
 
     /// Print a prompt on the screen and read a single character from the keyboard.
     /// The character is echoed onto the console monitor, and its ASCII code is
     /// copied into R0. The high eight bits of R0 are cleared.
-    fn in_troutine<R, W>(&mut self, input_reader: &mut R, output_writer: &mut W)
-    where
-        R: Read,
-        W: Write,
-    {
+    fn in_troutine(&mut self) -> Result<(), Fault> {
         // We specify our own prompt
-        write!(output_writer, "{}", IN_TROUTINE_PROMPT).unwrap();
-        let mut input_buf: [u8; 1] = [0];
-        input_reader.read_exact(&mut input_buf).unwrap();
-        output_writer.write_all(&input_buf).unwrap();
-        let ascii_char = AsciiChar::new(input_buf[0] as char);
+        self.write_display(IN_TROUTINE_PROMPT.as_bytes())?;
+        let read_char = self.memory.read_key()?;
+        let ascii_char = Self::parse_ascii(read_char)?;
+        self.write_display(&[ascii_char.as_byte()])?;
         self.registers
             .set_reg_value(RegisterName::R0, ascii_char as u16);
+        Ok(())
     }
 
     /// Write a string of ASCII characters to the console. The characters are
@@ -137,27 +188,56 @@ impl Lc3Vm {
     /// character to be written.)
     ///
     /// Writing terminates with the occurrence of x0000 in a memory location
-    fn putsp_troutine(&mut self, output_writer: &mut impl Write) {
+    fn putsp_troutine(&mut self) -> Result<(), Fault> {
         let start_address = self.registers.get_reg_value(RegisterName::R0);
+        let mut output: Vec<u8> = Vec::new();
         let mut current_address = start_address;
         loop {
-            let mem_data = self.memory.read(current_address);
+            let mem_data = self.read_memory(current_address);
             if mem_data == 0 {
                 break;
             }
 
             let bytes_slice: [u8; 2] = mem_data.to_be_bytes();
-            let first_char = AsciiChar::new(bytes_slice[1] as char);
-            let second_char = AsciiChar::new(bytes_slice[0] as char);
-
-            write!(output_writer, "{}{}", first_char, second_char).unwrap();
-            current_address += 1;
+            let first_char = Self::parse_ascii(bytes_slice[1])?;
+            output.push(first_char.as_byte());
+            // An odd length string has x00 as the final high byte, which is not printed
+            if bytes_slice[0] != 0 {
+                let second_char = Self::parse_ascii(bytes_slice[0])?;
+                output.push(second_char.as_byte());
+            }
+            current_address = current_address.wrapping_add(1);
         }
+        self.write_display(&output)?;
+        Ok(())
     }
 
     /// Halt execution and print a message on the console.
-    fn halt_troutine(&mut self, output_writer: &mut impl Write) {
-        write!(output_writer, "{}", HALT_MESSAGE).unwrap();
+    fn halt_troutine(&mut self) -> Result<(), Fault> {
+        // Halt before writing, so that the VM stops even if the message can't be printed
         self.halt();
+        self.write_display(HALT_MESSAGE.as_bytes())?;
+        Ok(())
+    }
+
+    /// Writes characters to the console display. Each character is recorded as a write
+    /// to DDR, like the trap routines of an operating system would make, so that
+    /// watchpoints and traces see the output
+    fn write_display(&mut self, chars: &[u8]) -> Result<(), Fault> {
+        let address = DeviceRegister::Ddr.address();
+        for &char in chars {
+            self.memory_writes.push(MemoryWrite {
+                address,
+                old_value: self.memory.peek(address),
+                new_value: char as u16,
+            });
+        }
+        self.memory.console_mut().write_chars(chars)?;
+        Ok(())
+    }
+
+    /// Parses a byte read from or written to the console into an ASCII character
+    fn parse_ascii(byte: u8) -> Result<AsciiChar, Fault> {
+        AsciiChar::from_ascii(byte).map_err(|_| Fault::InvalidCharacter(byte as u16))
     }
 }
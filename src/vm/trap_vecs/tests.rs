@@ -1,29 +1,43 @@
-use super::IN_TROUTINE_PROMPT;
-use crate::vm::{registers::RegisterName, trap_vecs::HALT_MESSAGE, Lc3Vm};
+use super::{TrapVector, IN_TROUTINE_PROMPT};
+use crate::{
+    asm::assemble,
+    vm::{
+        console::{IoConsole, OutputBuffer},
+        error::Fault,
+        registers::{PrivilegeMode, RegisterName},
+        trap_vecs::HALT_MESSAGE,
+        Lc3Vm,
+    },
+};
 use ascii::AsciiChar;
-use std::str::from_utf8;
+
+/// Creates a VM which reads `input` from its console, and a buffer containing the
+/// console output
+fn vm_with_console(input: &'static str) -> (Lc3Vm, OutputBuffer) {
+    let output = OutputBuffer::new();
+    let console = IoConsole::new(input.as_bytes(), output.clone());
+    (Lc3Vm::with_console(console), output)
+}
 
 #[test]
 fn test_getc_troutine() {
-    let mut vm = Lc3Vm::new();
-    let mut input = "g".as_bytes();
-    vm.getc_troutine(&mut input);
+    let (mut vm, _) = vm_with_console("grs");
+    vm.getc_troutine().unwrap();
     let read_char = vm.registers.get_reg_value(RegisterName::R0);
     assert_eq!(read_char, 'g' as u16);
 
-    let mut input = "rs".as_bytes();
-    vm.getc_troutine(&mut input);
+    vm.getc_troutine().unwrap();
     let read_char = vm.registers.get_reg_value(RegisterName::R0);
     assert_eq!(read_char, 'r' as u16);
 }
 
 #[test]
 fn test_out_troutine() {
-    let mut vm = Lc3Vm::new();
+    let (mut vm, output) = vm_with_console("");
     let test_char = 'w' as u16;
     vm.registers.set_reg_value(RegisterName::R0, test_char);
-    let mut output: Vec<u8> = Vec::new();
-    vm.out_troutine(&mut output);
+    vm.out_troutine().unwrap();
+    let output = output.contents();
     assert_eq!(output.len(), 1);
     let read_char = output[0] as u16;
     assert_eq!(test_char, read_char);
@@ -31,7 +45,7 @@ fn test_out_troutine() {
 
 #[test]
 fn test_puts_troutine() {
-    let mut vm = Lc3Vm::new();
+    let (mut vm, output) = vm_with_console("");
     let start_address = 0x303b;
     vm.registers.set_reg_value(RegisterName::R0, start_address);
 
@@ -47,23 +61,20 @@ fn test_puts_troutine() {
     }
     vm.memory.write(current_address, 0);
 
-    let mut output: Vec<u8> = Vec::new();
-    vm.puts_troutine(&mut output);
-    let printed_string = from_utf8(&output).unwrap();
+    vm.puts_troutine().unwrap();
+    let printed_string = output.to_string_lossy();
     assert_eq!(test_string, printed_string);
 }
 
 #[test]
 fn test_in_troutine() {
-    let mut vm = Lc3Vm::new();
+    let (mut vm, output) = vm_with_console("F");
 
     let expected_char = 'F';
-    let mut input = "F".as_bytes();
-    let mut output: Vec<u8> = Vec::new();
 
-    vm.in_troutine(&mut input, &mut output);
+    vm.in_troutine().unwrap();
     let expected_output = format!("{}{}", IN_TROUTINE_PROMPT, expected_char);
-    let printed_output = from_utf8(&output).unwrap();
+    let printed_output = output.to_string_lossy();
     assert_eq!(printed_output, expected_output);
 
     let saved_char = vm.registers.get_reg_value(RegisterName::R0);
@@ -72,7 +83,7 @@ fn test_in_troutine() {
 
 #[test]
 fn test_putsp_troutine() {
-    let mut vm = Lc3Vm::new();
+    let (mut vm, output) = vm_with_console("");
     let start_address = 0x30BA;
     vm.registers.set_reg_value(RegisterName::R0, start_address);
 
@@ -100,19 +111,127 @@ fn test_putsp_troutine() {
     }
     vm.memory.write(current_address, 0);
 
-    let mut output: Vec<u8> = Vec::new();
-    vm.putsp_troutine(&mut output);
-    let print_str = from_utf8(&output).unwrap();
+    vm.putsp_troutine().unwrap();
+    let print_str = output.to_string_lossy();
     assert_eq!(test_str, print_str);
 }
 
 #[test]
 fn test_halt_troutine() {
-    let mut vm = Lc3Vm::new();
-    let mut output: Vec<u8> = Vec::new();
-    vm.halt_troutine(&mut output);
+    let (mut vm, output) = vm_with_console("");
+    vm.halt_troutine().unwrap();
     let running = vm.running();
     assert!(!running);
-    let printed_output = from_utf8(&output).unwrap();
+    let printed_output = output.to_string_lossy();
     assert_eq!(printed_output, HALT_MESSAGE);
 }
+
+#[test]
+fn test_puts_troutine_invalid_character() {
+    let (mut vm, _) = vm_with_console("");
+    let start_address = 0x4000;
+    vm.registers.set_reg_value(RegisterName::R0, start_address);
+    vm.memory.write(start_address, 'o' as u16);
+    vm.memory.write(start_address + 1, 0xe9);
+
+    let err = vm.puts_troutine().unwrap_err();
+    assert!(matches!(err, Fault::InvalidCharacter(0xe9)));
+}
+
+#[test]
+fn test_getc_troutine_no_input() {
+    let (mut vm, _) = vm_with_console("");
+    let err = vm.getc_troutine().unwrap_err();
+    assert!(matches!(err, Fault::Io(_)));
+}
+
+#[test]
+fn test_trap_runs_native_troutine() {
+    let (mut vm, output) = vm_with_console("");
+    vm.set_reg_val_by_id(0, 0x4000);
+    vm.memory.write(0x4000, 'o' as u16);
+    vm.memory.write(0x4001, 'k' as u16);
+    // TRAP x22 (PUTS)
+    vm.memory.write(0x3000, 0xf022);
+
+    let step = vm.step().unwrap();
+    assert_eq!(step.next_pc, TrapVector::Puts.native_address());
+    assert_eq!(vm.registers.privilege_mode(), PrivilegeMode::Supervisor);
+    assert!(output.contents().is_empty());
+
+    // The native routine runs, and returns with RTI. The output is recorded as writes
+    // to DDR
+    let step = vm.step().unwrap();
+    assert_eq!(output.contents(), b"ok");
+    let written = step
+        .memory_written
+        .iter()
+        .map(|write| (write.address, write.new_value))
+        .collect::<Vec<(u16, u16)>>();
+    assert_eq!(written, vec![(0xfe06, 'o' as u16), (0xfe06, 'k' as u16)]);
+    assert_eq!(vm.registers.program_counter(), 0x3001);
+    assert_eq!(vm.registers.privilege_mode(), PrivilegeMode::User);
+}
+
+#[test]
+fn test_trap_halt() {
+    let (mut vm, output) = vm_with_console("");
+    // TRAP x25 (HALT)
+    vm.memory.write(0x3000, 0xf025);
+    vm.run().unwrap();
+    assert!(!vm.running());
+    assert_eq!(output.contents(), HALT_MESSAGE.as_bytes());
+}
+
+#[test]
+fn test_user_defined_troutine() {
+    let (mut vm, output) = vm_with_console("");
+    // A trap routine for x26 that doubles R0, and a replacement for OUT that prints
+    // the character twice
+    let troutine = "
+        .ORIG x4000
+        ADD R0, R0, R0
+        RTI
+        .END";
+    let out_troutine = "
+        .ORIG x5000
+        STI R0, DDR
+        STI R0, DDR
+        RTI
+DDR     .FILL xFE06
+        .END";
+    let main = "
+        .ORIG x3000
+        ADD R0, R0, #15
+        TRAP x26
+        OUT
+        HALT
+        .END";
+    for source in [troutine, out_troutine, main] {
+        let assembly = assemble(source).unwrap();
+        for (address, word) in (assembly.origin..).zip(&assembly.words) {
+            vm.memory.write(address, *word);
+        }
+    }
+    vm.memory.write(0x26, 0x4000);
+    vm.memory.write(TrapVector::Out as u16, 0x5000);
+    vm.run().unwrap();
+    assert_eq!(vm.get_reg_val_by_id(0), 30);
+    assert!(output.to_string_lossy().starts_with("\u{1e}\u{1e}"));
+}
+
+#[test]
+fn test_overwritten_native_troutine() {
+    let (mut vm, _) = vm_with_console("");
+    let address = TrapVector::Halt.native_address();
+    assert_eq!(
+        vm.native_troutine_at(address, vm.memory.peek(address)),
+        Some(TrapVector::Halt)
+    );
+    assert_eq!(vm.native_troutine_at(address, 0x1234), None);
+    assert_eq!(vm.native_troutine_at(0x3000, 0x8000), None);
+
+    // Booting an operating system replaces the native routines
+    vm.boot_os();
+    assert_eq!(vm.native_troutine_at(address, 0x8000), None);
+}